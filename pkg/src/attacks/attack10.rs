@@ -1,6 +1,7 @@
+use crate::sys_bus::builder::{DeviceSpec, SystemBuilder};
 use crate::sys_bus::{
-    AttackType, DefaultBCEventHandler, DefaultEventHandler, Device, ErrMsg, EventHandler,
-    EventHandlerEmitter, Mode, Proto, State, System, Word, TR, WRD_EMPTY,
+    AttackType, DefaultBCEventHandler, Device, ErrMsg, EventHandler, EventHandlerEmitter, Mode,
+    Proto, State, System, Word, TR, WRD_EMPTY,
 };
 use std::sync::{Arc, Mutex};
 
@@ -53,7 +54,7 @@ impl EventHandler for CommandInvalidationAttack {
     fn verify(&mut self, system: &System) -> bool {
         // let last_log = &system.logs[system.logs.len() - 1];
         // target is waiting for data instead.
-        // return last_log.3 == self.target && last_log.4 == State::AwtData;
+        // return last_log.address == self.target && last_log.state == State::AwtData;
         let bc = &system.devices[0];
         return bc.lock().unwrap().timeout_times > 0;
     }
@@ -64,36 +65,17 @@ pub fn eval_attack10(w_delays: u128, proto: Proto) -> bool {
     let n_devices = 4;
     // normal device has 4ns delays (while attacker has zero)
     // let w_delays = 40000;
-    let mut sys_bus = System::new(n_devices as u32, w_delays);
-
-    // the last device is kept for attacker
-    for m in 0..n_devices - 1 {
-        if m == 0 {
-            sys_bus.run_d(
-                m as u8,
-                Mode::BC,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultBCEventHandler {
-                        total_device: n_devices - 1,
-                        target: 0,
-                        data: vec![1, 2, 3],
-                        proto: proto,
-                        proto_rotate: false,
-                    }),
-                })),
-                false,
-            );
-        } else {
-            sys_bus.run_d(
-                m as u8,
-                Mode::RT,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultEventHandler {}),
-                })),
-                false,
-            );
-        }
-    }
+    let bc = DeviceSpec::new(
+        0,
+        Mode::BC,
+        Box::new(DefaultBCEventHandler {
+            total_device: n_devices - 1,
+            target: 0,
+            data: vec![1, 2, 3],
+            proto: proto,
+            proto_rotate: false,
+        }),
+    );
     let attk = CommandInvalidationAttack {
         attack_times: Vec::new(),
         success: false,
@@ -104,7 +86,15 @@ pub fn eval_attack10(w_delays: u128, proto: Proto) -> bool {
         handler: Box::new(attk),
     }));
 
-    sys_bus.run_d(n_devices - 1, Mode::RT, Arc::clone(&attacker_router), true);
+    // the last device is kept for attacker
+    let attacker =
+        DeviceSpec::with_emitter(n_devices - 1, Mode::RT, Arc::clone(&attacker_router)).fake();
+    let mut sys_bus = SystemBuilder::new(w_delays)
+        .device(bc)
+        .rts(1..n_devices - 1)
+        .device(attacker)
+        .build()
+        .unwrap();
     sys_bus.go();
     sys_bus.sleep_ms(100);
     sys_bus.stop();
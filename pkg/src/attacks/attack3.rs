@@ -1,6 +1,7 @@
+use crate::sys_bus::builder::{DeviceSpec, SystemBuilder};
 use crate::sys_bus::{
-    AttackType, DefaultBCEventHandler, DefaultEventHandler, Device, ErrMsg, EventHandler,
-    EventHandlerEmitter, Mode, Proto, State, System, Word, TR, WRD_EMPTY,
+    AttackType, DefaultBCEventHandler, Device, ErrMsg, EventHandler, EventHandlerEmitter, Mode,
+    Proto, State, System, Word, TR, WRD_EMPTY,
 };
 use std::sync::{Arc, Mutex};
 
@@ -18,8 +19,7 @@ impl DataThrashingAgainstRT {
         self.attack_times.push(d.clock.elapsed().as_nanos());
         let mode_code = 30;
         let tr = TR::Receive;
-        let mut w = Word::new_cmd(self.target, mode_code, tr);
-        w.set_mode(1);
+        let w = Word::new_mode_cmd(self.target, mode_code, tr);
         d.write(w);
         self.success = true;
     }
@@ -65,7 +65,7 @@ impl EventHandler for DataThrashingAgainstRT {
         // let mut bc_ready_times = 0;
 
         // for l in &(system.devices[0].lock().unwrap().logs) {
-        //     if l.6 == ErrMsg::MsgBCReady {
+        //     if l.event == ErrMsg::MsgBCReady {
         //         bc_ready_times += 1;
         //         if bc_ready_times > 2 {
         //             // no more than twice
@@ -78,7 +78,7 @@ impl EventHandler for DataThrashingAgainstRT {
             let local_d = d.lock().unwrap();
             if local_d.address == self.target {
                 for l in &local_d.logs {
-                    if matches!(l.6, ErrMsg::MsgMCXClr { .. }) {
+                    if matches!(l.event, ErrMsg::MsgMCXClr { .. }) {
                         // the target's memory has been cleared
                         return true;
                     }
@@ -95,36 +95,17 @@ pub fn eval_attack3(w_delays: u128, proto: Proto) -> bool {
     let n_devices = 4;
     // normal device has 4ns delays (while attacker has zero)
     // let w_delays = 40000;
-    let mut sys_bus = System::new(n_devices as u32, w_delays);
-
-    // the last device is kept for attacker
-    for m in 0..n_devices - 1 {
-        if m == 0 {
-            sys_bus.run_d(
-                m as u8,
-                Mode::BC,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultBCEventHandler {
-                        total_device: n_devices - 1,
-                        target: 0,
-                        data: vec![1, 2, 3],
-                        proto: proto,
-                        proto_rotate: false,
-                    }),
-                })),
-                false,
-            );
-        } else {
-            sys_bus.run_d(
-                m as u8,
-                Mode::RT,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultEventHandler {}),
-                })),
-                false,
-            );
-        }
-    }
+    let bc = DeviceSpec::new(
+        0,
+        Mode::BC,
+        Box::new(DefaultBCEventHandler {
+            total_device: n_devices - 1,
+            target: 0,
+            data: vec![1, 2, 3],
+            proto: proto,
+            proto_rotate: false,
+        }),
+    );
     let attk = DataThrashingAgainstRT {
         attack_times: Vec::new(),
         word_count: 0u8,
@@ -136,7 +117,15 @@ pub fn eval_attack3(w_delays: u128, proto: Proto) -> bool {
         handler: Box::new(attk),
     }));
 
-    sys_bus.run_d(n_devices - 1, Mode::RT, Arc::clone(&attacker_emitter), true);
+    // the last device is kept for attacker
+    let attacker =
+        DeviceSpec::with_emitter(n_devices - 1, Mode::RT, Arc::clone(&attacker_emitter)).fake();
+    let mut sys_bus = SystemBuilder::new(w_delays)
+        .device(bc)
+        .rts(1..n_devices - 1)
+        .device(attacker)
+        .build()
+        .unwrap();
     sys_bus.go();
     sys_bus.sleep_ms(50);
     sys_bus.stop();
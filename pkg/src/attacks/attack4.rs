@@ -1,8 +1,8 @@
+use crate::sys_bus::builder::{DeviceSpec, SystemBuilder};
 use crate::sys_bus::{
-    AttackType, DefaultBCEventHandler, DefaultEventHandler, Device, ErrMsg, EventHandler,
-    EventHandlerEmitter, Mode, Proto, State, System, Word, BROADCAST_ADDRESS, TR, WRD_EMPTY,
+    AttackType, DefaultBCEventHandler, Device, ErrMsg, EventHandler, Mode, Proto, State, Word,
+    BROADCAST_ADDRESS, TR, WRD_EMPTY,
 };
-use std::sync::{Arc, Mutex};
 
 #[derive(Clone, Debug)]
 pub struct MITMAttackOnRTs {
@@ -108,54 +108,40 @@ pub fn test_attack4() {
     let n_devices = 8;
     // normal device has 4ns delays (while attacker has zero)
     let w_delays = 4000;
-    let mut sys_bus = System::new(n_devices as u32, w_delays);
-
+    let bc = DeviceSpec::new(
+        0,
+        Mode::BC,
+        Box::new(DefaultBCEventHandler {
+            total_device: n_devices - 1,
+            target: 0,
+            data: vec![1, 2, 3],
+            proto: Proto::BC2RT,
+            proto_rotate: true,
+        }),
+    );
     // the last device is kept for attacker
-    for m in 0..n_devices - 1 {
-        if m == 0 {
-            sys_bus.run_d(
-                m as u8,
-                Mode::BC,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultBCEventHandler {
-                        total_device: n_devices - 1,
-                        target: 0,
-                        data: vec![1, 2, 3],
-                        proto: Proto::BC2RT,
-                        proto_rotate: true,
-                    }),
-                })),
-                false,
-            );
-        } else {
-            sys_bus.run_d(
-                m as u8,
-                Mode::RT,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultEventHandler {}),
-                })),
-                false,
-            );
-        }
-    }
-    sys_bus.run_d(
+    let attacker = DeviceSpec::new(
         n_devices - 1,
         Mode::RT,
-        Arc::new(Mutex::new(EventHandlerEmitter {
-            handler: Box::new(MITMAttackOnRTs {
-                attack_times: Vec::new(),
-                word_count: 0u8,
-                injected_words: 0u8,
-                success: false,
-                target_src: 0u8,
-                target_dst: 0u8,
-                target_dst_found: false, // target found in traffic
-                target_src_found: false,
-                done: false,
-            }),
-        })),
-        true,
-    );
+        Box::new(MITMAttackOnRTs {
+            attack_times: Vec::new(),
+            word_count: 0u8,
+            injected_words: 0u8,
+            success: false,
+            target_src: 0u8,
+            target_dst: 0u8,
+            target_dst_found: false, // target found in traffic
+            target_src_found: false,
+            done: false,
+        }),
+    )
+    .fake();
+    let mut sys_bus = SystemBuilder::new(w_delays)
+        .device(bc)
+        .rts(1..n_devices - 1)
+        .device(attacker)
+        .build()
+        .unwrap();
     sys_bus.go();
     sys_bus.sleep_ms(10);
     sys_bus.stop();
@@ -1,6 +1,7 @@
+use crate::sys_bus::builder::{DeviceSpec, SystemBuilder};
 use crate::sys_bus::{
-    AttackType, DefaultBCEventHandler, DefaultEventHandler, Device, ErrMsg, EventHandler,
-    EventHandlerEmitter, Mode, Proto, State, System, Word, TR, WRD_EMPTY,
+    AttackType, DefaultBCEventHandler, Device, ErrMsg, EventHandler, EventHandlerEmitter, Mode,
+    Proto, State, System, Word, TR, WRD_EMPTY,
 };
 use std::sync::{Arc, Mutex};
 
@@ -20,10 +21,9 @@ impl ShutdownAttackRT {
             ErrMsg::MsgAttk(format!("Attacker>> Killing RT{}", self.target).to_string()),
         );
         let mode_code = 4;
-        let tr = TR::Receive;
+        let tr = TR::Transmit;
         self.attack_times.push(d.clock.elapsed().as_nanos());
-        let mut w = Word::new_cmd(self.target, mode_code, tr);
-        w.set_mode(1);
+        let w = Word::new_mode_cmd(self.target, mode_code, tr);
         d.write(w);
         self.success = true;
         // d.set_state(State::Off); // Not sure what's going on here yet.  TODO come back to this.
@@ -76,36 +76,17 @@ pub fn eval_attack5(w_delays: u128, proto: Proto) -> bool {
     let n_devices = 4;
     // normal device has 4ns delays (while attacker has zero)
     // let w_delays = 40000;
-    let mut sys_bus = System::new(n_devices as u32, w_delays);
-
-    // the last device is kept for attacker
-    for m in 0..n_devices - 1 {
-        if m == 0 {
-            sys_bus.run_d(
-                m as u8,
-                Mode::BC,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultBCEventHandler {
-                        total_device: n_devices - 1,
-                        target: 0,
-                        data: vec![1, 2, 3],
-                        proto: proto,
-                        proto_rotate: false,
-                    }),
-                })),
-                false,
-            );
-        } else {
-            sys_bus.run_d(
-                m as u8,
-                Mode::RT,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultEventHandler {}),
-                })),
-                false,
-            );
-        }
-    }
+    let bc = DeviceSpec::new(
+        0,
+        Mode::BC,
+        Box::new(DefaultBCEventHandler {
+            total_device: n_devices - 1,
+            target: 0,
+            data: vec![1, 2, 3],
+            proto: proto,
+            proto_rotate: false,
+        }),
+    );
     let attk = ShutdownAttackRT {
         attack_times: Vec::new(),
         word_count: 0u8,
@@ -117,7 +98,15 @@ pub fn eval_attack5(w_delays: u128, proto: Proto) -> bool {
         handler: Box::new(attk),
     }));
 
-    sys_bus.run_d(n_devices - 1, Mode::RT, Arc::clone(&attacker_router), true);
+    // the last device is kept for attacker
+    let attacker =
+        DeviceSpec::with_emitter(n_devices - 1, Mode::RT, Arc::clone(&attacker_router)).fake();
+    let mut sys_bus = SystemBuilder::new(w_delays)
+        .device(bc)
+        .rts(1..n_devices - 1)
+        .device(attacker)
+        .build()
+        .unwrap();
     sys_bus.go();
     sys_bus.sleep_ms(100);
     sys_bus.stop();
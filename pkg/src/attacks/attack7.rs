@@ -1,6 +1,7 @@
+use crate::sys_bus::builder::{DeviceSpec, SystemBuilder};
 use crate::sys_bus::{
-    AttackType, DefaultBCEventHandler, DefaultEventHandler, Device, ErrMsg, EventHandler,
-    EventHandlerEmitter, Mode, Proto, System, Word, BROADCAST_ADDRESS, TR, WRD_EMPTY,
+    AttackType, DefaultBCEventHandler, Device, ErrMsg, EventHandler, EventHandlerEmitter, Mode,
+    Proto, System, Word, BROADCAST_ADDRESS, TR, WRD_EMPTY,
 };
 use std::sync::{Arc, Mutex};
 
@@ -69,20 +70,20 @@ impl EventHandler for FakeStatusTrcmd {
     fn verify(&mut self, system: &System) -> bool {
         let mut attk_session = false;
         for l in &system.logs {
-            if matches!(l.6, ErrMsg::MsgAttk { .. }) {
+            if matches!(l.event, ErrMsg::MsgAttk { .. }) {
                 attk_session = true;
             }
             // dropped message during attack session
             if attk_session {
-                if l.6 == ErrMsg::MsgEntSteDrop {
-                    if l.5.attk() == (AttackType::AtkFakeStatusTrcmd as u32) {
+                if l.event == ErrMsg::MsgEntSteDrop {
+                    if l.word.attk() == (AttackType::AtkFakeStatusTrcmd as u32) {
                         return false;
                     } else {
                         return true;
                     }
                 }
             }
-            if l.6 == ErrMsg::MsgBCReady {
+            if l.event == ErrMsg::MsgBCReady {
                 attk_session = false;
             }
         }
@@ -95,36 +96,17 @@ pub fn eval_attack7(w_delays: u128, proto: Proto) -> bool {
     let n_devices = 3;
     // normal device has 4ns delays (while attacker has zero)
     // let w_delays = 40000;
-    let mut sys_bus = System::new(n_devices as u32, w_delays);
-
-    // the last device is kept for attacker
-    for m in 0..n_devices - 1 {
-        if m == 0 {
-            sys_bus.run_d(
-                m as u8,
-                Mode::BC,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultBCEventHandler {
-                        total_device: n_devices - 1,
-                        target: 0,
-                        data: vec![1, 2, 3],
-                        proto: proto,
-                        proto_rotate: false,
-                    }),
-                })),
-                false,
-            );
-        } else {
-            sys_bus.run_d(
-                m as u8,
-                Mode::RT,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultEventHandler {}),
-                })),
-                false,
-            );
-        }
-    }
+    let bc = DeviceSpec::new(
+        0,
+        Mode::BC,
+        Box::new(DefaultBCEventHandler {
+            total_device: n_devices - 1,
+            target: 0,
+            data: vec![1, 2, 3],
+            proto: proto,
+            proto_rotate: false,
+        }),
+    );
     let attk = FakeStatusTrcmd {
         attack_times: Vec::new(),
         success: false,
@@ -136,7 +118,15 @@ pub fn eval_attack7(w_delays: u128, proto: Proto) -> bool {
         handler: Box::new(attk),
     }));
 
-    sys_bus.run_d(n_devices - 1, Mode::RT, Arc::clone(&attacker_router), true);
+    // the last device is kept for attacker
+    let attacker =
+        DeviceSpec::with_emitter(n_devices - 1, Mode::RT, Arc::clone(&attacker_router)).fake();
+    let mut sys_bus = SystemBuilder::new(w_delays)
+        .device(bc)
+        .rts(1..n_devices - 1)
+        .device(attacker)
+        .build()
+        .unwrap();
     sys_bus.go();
     sys_bus.sleep_ms(100);
     sys_bus.stop();
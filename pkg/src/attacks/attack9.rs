@@ -1,6 +1,7 @@
+use crate::sys_bus::builder::{DeviceSpec, SystemBuilder};
 use crate::sys_bus::{
-    format_log, AttackType, DefaultBCEventHandler, DefaultEventHandler, Device, ErrMsg,
-    EventHandler, EventHandlerEmitter, Mode, Proto, State, System, Word, TR, WRD_EMPTY,
+    format_log, AttackType, DefaultBCEventHandler, Device, ErrMsg, EventHandler,
+    EventHandlerEmitter, Mode, Proto, State, System, Word, TR, WRD_EMPTY,
 };
 use std::sync::{Arc, Mutex};
 
@@ -76,11 +77,11 @@ impl EventHandler for DataCorruptionAttack {
     fn verify(&mut self, system: &System) -> bool {
         let mut recieved_faked = 0;
         for l in &system.logs {
-            if l.6 == ErrMsg::MsgBCReady {
+            if l.event == ErrMsg::MsgBCReady {
                 recieved_faked = 0;
             }
-            if l.6 == ErrMsg::MsgEntDat
-                && l.5.attk() == (AttackType::AtkDataCorruptionAttack as u32)
+            if l.event == ErrMsg::MsgEntDat
+                && l.word.attk() == (AttackType::AtkDataCorruptionAttack as u32)
             {
                 // println!("{} {}/{}", format_log(&l), recieved_faked, self.word_count);
                 recieved_faked += 1;
@@ -98,36 +99,17 @@ pub fn eval_attack9(w_delays: u128, proto: Proto) -> bool {
     let n_devices = 4;
     // normal device has 4ns delays (while attacker has zero)
     // let w_delays = 40000;
-    let mut sys_bus = System::new(n_devices as u32, w_delays);
-
-    // the last device is kept for attacker
-    for m in 0..n_devices - 1 {
-        if m == 0 {
-            sys_bus.run_d(
-                m as u8,
-                Mode::BC,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultBCEventHandler {
-                        total_device: n_devices - 1,
-                        target: 0,
-                        data: vec![1, 2, 3],
-                        proto: proto,
-                        proto_rotate: false,
-                    }),
-                })),
-                false,
-            );
-        } else {
-            sys_bus.run_d(
-                m as u8,
-                Mode::RT,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultEventHandler {}),
-                })),
-                false,
-            );
-        }
-    }
+    let bc = DeviceSpec::new(
+        0,
+        Mode::BC,
+        Box::new(DefaultBCEventHandler {
+            total_device: n_devices - 1,
+            target: 0,
+            data: vec![1, 2, 3],
+            proto: proto,
+            proto_rotate: false,
+        }),
+    );
     let attk = DataCorruptionAttack {
         attack_times: Vec::new(),
         word_count: 0u8,
@@ -139,7 +121,15 @@ pub fn eval_attack9(w_delays: u128, proto: Proto) -> bool {
         handler: Box::new(attk),
     }));
 
-    sys_bus.run_d(n_devices - 1, Mode::RT, Arc::clone(&attacker_router), true);
+    // the last device is kept for attacker
+    let attacker =
+        DeviceSpec::with_emitter(n_devices - 1, Mode::RT, Arc::clone(&attacker_router)).fake();
+    let mut sys_bus = SystemBuilder::new(w_delays)
+        .device(bc)
+        .rts(1..n_devices - 1)
+        .device(attacker)
+        .build()
+        .unwrap();
     sys_bus.go();
     sys_bus.sleep_ms(400);
     sys_bus.stop();
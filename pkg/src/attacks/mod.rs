@@ -9,9 +9,10 @@ pub mod attack7;
 pub mod attack8;
 pub mod attack9;
 
+use crate::sys_bus::builder::{DeviceSpec, SystemBuilder};
 use crate::sys_bus::{
-    format_log, AttackType, DefaultBCEventHandler, DefaultEventHandler, Device, ErrMsg,
-    EventHandler, EventHandlerEmitter, Mode, Proto, State, System, Word, TR, WRD_EMPTY,
+    format_log, AttackType, BusBound, DefaultBCEventHandler, DefaultEventHandler, Device, ErrMsg,
+    EventHandler, EventHandlerEmitter, Mode, Proto, State, Word, TR, WRD_EMPTY,
 };
 use attack1::CollisionAttackAgainstTheBus;
 use attack10::CommandInvalidationAttack;
@@ -29,6 +30,8 @@ use std::sync::{Arc, Mutex};
 pub struct AttackController {
     pub current_attack: AttackType,
     pub emitter: Arc<Mutex<EventHandlerEmitter>>,
+    // bus the attacker is wired to (`None`: every bus)
+    pub bus: Option<u8>,
 }
 
 impl AttackController {
@@ -111,6 +114,10 @@ impl AttackController {
                 target_found: false,
             }),
         };
+        let attack: Box<dyn EventHandler> = match self.bus {
+            Some(bus) => Box::new(BusBound { bus, inner: attack }),
+            None => attack,
+        };
         self.current_attack = attack_type;
         self.emitter.lock().unwrap().handler = attack;
     }
@@ -125,59 +132,40 @@ pub fn eval_attack_controller(
 ) -> HashMap<u32, i32> {
     // let n_devices = 3;
     // let w_delays = w_delays;
-    let mut sys_bus = System::new(n_devices as u32, w_delays);
-    for m in 0..(n_devices - 2) {
-        // let (s1, r1) = bounded(64);
-        // s_vec.lock().unwrap().push(s1);
-        if m == 0 {
-            sys_bus.run_d(
-                m as u8,
-                Mode::BC,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultBCEventHandler {
-                        total_device: n_devices - 2,
-                        target: 0,
-                        data: vec![1, 2, 3],
-                        proto: proto,
-                        proto_rotate: proto_rotate,
-                    }),
-                })),
-                false,
-            );
-        } else {
-            sys_bus.run_d(
-                m as u8,
-                Mode::RT,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultEventHandler {}),
-                })),
-                false,
-            );
-        }
-    }
-
-    sys_bus.run_d(
-        n_devices - 2,
-        Mode::BM,
-        Arc::new(Mutex::new(EventHandlerEmitter {
-            handler: Box::new(DefaultEventHandler {}),
-        })),
-        false,
+    let bc = DeviceSpec::new(
+        0,
+        Mode::BC,
+        Box::new(DefaultBCEventHandler {
+            total_device: n_devices - 2,
+            target: 0,
+            data: vec![1, 2, 3],
+            proto: proto,
+            proto_rotate: proto_rotate,
+        }),
     );
+    let bm = DeviceSpec::new(n_devices - 2, Mode::BM, Box::new(DefaultEventHandler {}));
 
     let mut attack_controller = AttackController {
         current_attack: AttackType::Benign,
         emitter: Arc::new(Mutex::new(EventHandlerEmitter {
             handler: Box::new(DefaultEventHandler {}),
         })),
+        bus: None,
     };
-
-    sys_bus.run_d(
+    let attacker = DeviceSpec::with_emitter(
         n_devices - 1,
         Mode::RT,
         Arc::clone(&attack_controller.emitter),
-        true,
-    );
+    )
+    .fake();
+
+    let mut sys_bus = SystemBuilder::new(w_delays)
+        .device(bc)
+        .rts(1..n_devices - 2)
+        .device(bm)
+        .device(attacker)
+        .build()
+        .unwrap();
 
     sys_bus.go();
     sys_bus.sleep_ms(1);
@@ -204,9 +192,9 @@ pub fn eval_attack_controller(
     sys_bus.join();
     let mut result = HashMap::new();
     for l in &sys_bus.logs {
-        if l.5.attk() != 0 {
+        if l.word.attk() != 0 {
             // println!("{} {}/{}", format_log(&l), recieved_faked, self.word_count);
-            *result.entry(l.5.attk()).or_insert(0) += 1;
+            *result.entry(l.word.attk()).or_insert(0) += 1;
         }
     }
     println!("{:?}", result);
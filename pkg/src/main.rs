@@ -6,13 +6,15 @@ mod sys_flight;
 use attacks::eval_attack_controller;
 use risk::eval_all;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use sys_bus::{eval_sys, AttackType, Proto};
 use sys_flight::fighter_md::eval_fighter_sim;
 
 fn main() {
     // eval_all();
     eval_sys(4_000, 6, Proto::RT2RT, false);
+    // usage: pkg [schedule.json] (the built-in fighter schedule without one)
+    let schedule = std::env::args().nth(1).map(PathBuf::from);
     // eval_fighter_sim("sample_data.sqlite", 4_000, 10_000, AttackType::Benign);
     // eval_fighter_sim("sample_data.sqlite", 4_000, 10_000, AttackType::AtkCollisionAttackAgainstTheBus);
     let paths = fs::read_dir("./msf/").unwrap();
@@ -37,14 +39,18 @@ fn main() {
                 p,
                 AttackType::from(attack_index),
             );
-            eval_fighter_sim(
+            if let Err(e) = eval_fighter_sim(
                 &p[..],
                 4_000,
                 // 10_000,
                 0, // max run time
                 attack_index.into(),
                 format!("{}_{}", ds_name, attack_index),
-            );
+                schedule.as_deref(),
+            ) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
         }
         break;
     }
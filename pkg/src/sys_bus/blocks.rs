@@ -0,0 +1,393 @@
+use crate::sys_bus::{Device, ErrMsg, EventHandler, Message, State, Word, WRD_EMPTY};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// blocks run by a single `on_bc_ready` without sending anything (a program
+// looping on its own gives the bus a turn)
+const MAX_STEPS: usize = 256;
+
+/// Test of the outcome of the last message executed by the program.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub enum Condition {
+    #[default]
+    Always,
+    // not (completely) answered in time
+    NoResponse,
+    // answered with none of the bits below set
+    Good,
+    // bit set in a status word of the message
+    MessageError,
+    ServiceRequest,
+    Busy,
+    SubsystemFlag,
+    TerminalFlag,
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    fn holds(&self, last: &LastMessage) -> bool {
+        let any = |bit: fn(&Word) -> u8| last.statuses.iter().any(|w| bit(w) == 1);
+        match self {
+            Condition::Always => true,
+            Condition::NoResponse => last.timed_out,
+            Condition::Good => {
+                !last.timed_out
+                    && !last.statuses.iter().any(|w| {
+                        w.message_errorbit() == 1
+                            || w.service_request_bit() == 1
+                            || w.busy_bit() == 1
+                            || w.subsystem_flag_bit() == 1
+                            || w.terminal_flag_bit() == 1
+                    })
+            }
+            Condition::MessageError => any(Word::message_errorbit),
+            Condition::ServiceRequest => any(Word::service_request_bit),
+            Condition::Busy => any(Word::busy_bit),
+            Condition::SubsystemFlag => any(Word::subsystem_flag_bit),
+            Condition::TerminalFlag => any(Word::terminal_flag_bit),
+            Condition::Not(c) => !c.holds(last),
+        }
+    }
+}
+
+/// What a command block does.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum Op {
+    Execute(Message),
+    // continue at the block with the label
+    Jump(String),
+    // same, coming back after the next `Return`
+    Call(String),
+    Return,
+    // over the next block
+    Skip,
+    // nothing is sent for this long (ns)
+    Delay(u128),
+    // starts the minor frame timer with the period (ns)
+    LoadFrameTimer(u128),
+    // waits for the frame timer to expire, then restarts it
+    WaitFrame,
+    Halt,
+}
+
+/// One block of a BC program: its op runs when the condition holds, the
+/// program goes on with the next block otherwise.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandBlock {
+    #[serde(default)]
+    pub label: Option<String>,
+    pub op: Op,
+    #[serde(default)]
+    pub condition: Condition,
+    // an `Execute` raises an interrupt once the message is done
+    #[serde(default)]
+    pub interrupt: bool,
+}
+
+/// Events of a `CommandBlockBC` (`ErrMsg::MsgBlock`), by block index.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BlockEvent {
+    Interrupt(usize),
+    Halted(usize),
+    // the frame timer expired before `WaitFrame` (ns late)
+    FrameOverrun(usize, u128),
+}
+
+/// Linked list of command blocks, as run by the BC engines of 1553 cards,
+/// e.g. `{"blocks": [{"label": "frame", "op": {"Execute": {"RT2BC": [1,
+/// 2]}}}, {"op": {"Jump": "frame"}, "condition": "Good"}, {"op": "Halt"}]}`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockProgram {
+    pub blocks: Vec<CommandBlock>,
+}
+
+impl BlockProgram {
+    pub fn from_json(s: &str) -> Result<BlockProgram, String> {
+        let program: BlockProgram = serde_json::from_str(s).map_err(|e| e.to_string())?;
+        program.labels()?;
+        Ok(program)
+    }
+
+    pub fn load(path: &Path) -> Result<BlockProgram, String> {
+        let s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        BlockProgram::from_json(&s)
+    }
+
+    /// Index of each label; every label jumped to must be defined once.
+    pub fn labels(&self) -> Result<HashMap<String, usize>, String> {
+        let mut labels = HashMap::new();
+        for (i, block) in self.blocks.iter().enumerate() {
+            if let Some(label) = &block.label {
+                if labels.insert(label.clone(), i).is_some() {
+                    return Err(format!("label {} defined twice", label));
+                }
+            }
+        }
+        for block in &self.blocks {
+            if let Op::Jump(label) | Op::Call(label) = &block.op {
+                if !labels.contains_key(label) {
+                    return Err(format!("unknown label {}", label));
+                }
+            }
+        }
+        Ok(labels)
+    }
+}
+
+// outcome of the last message executed
+#[derive(Debug, Default)]
+struct LastMessage {
+    statuses: Vec<Word>,
+    timed_out: bool,
+    // block to raise an interrupt for once done
+    interrupt: Option<usize>,
+}
+
+/// BC handler running a `BlockProgram` from its first block.
+pub struct CommandBlockBC {
+    pub program: BlockProgram,
+    labels: HashMap<String, usize>,
+    pc: usize,
+    // return addresses of the calls
+    stack: Vec<usize>,
+    last: LastMessage,
+    // period and start of the minor frame timer
+    frame_timer: Option<(u128, u128)>,
+    halted: bool,
+}
+
+#[allow(unused)]
+impl CommandBlockBC {
+    pub fn new(program: BlockProgram) -> Result<Self, String> {
+        let labels = program.labels()?;
+        Ok(CommandBlockBC {
+            program,
+            labels,
+            pc: 0,
+            stack: Vec::new(),
+            last: LastMessage::default(),
+            frame_timer: None,
+            halted: false,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        CommandBlockBC::new(BlockProgram::load(path)?)
+    }
+
+    // runs the block at `pc`; false once the program is over
+    fn step(&mut self, d: &mut Device) -> bool {
+        let Some(block) = self.program.blocks.get(self.pc).cloned() else {
+            // (falling off the end of the list halts)
+            d.log(WRD_EMPTY, ErrMsg::MsgBlock(BlockEvent::Halted(self.pc)));
+            return false;
+        };
+        let at = self.pc;
+        self.pc += 1;
+        if !block.condition.holds(&self.last) {
+            return true;
+        }
+        let now = d.clock.elapsed().as_nanos();
+        match block.op {
+            Op::Execute(msg) => {
+                self.last = LastMessage {
+                    interrupt: block.interrupt.then_some(at),
+                    ..LastMessage::default()
+                };
+                d.act(&msg);
+            }
+            Op::Jump(label) => self.pc = self.labels[&label],
+            Op::Call(label) => {
+                self.stack.push(self.pc);
+                self.pc = self.labels[&label];
+            }
+            Op::Return => match self.stack.pop() {
+                Some(pc) => self.pc = pc,
+                None => {
+                    d.log(WRD_EMPTY, ErrMsg::MsgBlock(BlockEvent::Halted(at)));
+                    return false;
+                }
+            },
+            Op::Skip => self.pc += 1,
+            Op::Delay(ns) => d.bc_next_at = now + ns,
+            Op::LoadFrameTimer(period) => self.frame_timer = Some((period, now)),
+            Op::WaitFrame => {
+                if let Some((period, start)) = self.frame_timer {
+                    let end = start + period;
+                    if now > end {
+                        let event = BlockEvent::FrameOverrun(at, now - end);
+                        d.log(WRD_EMPTY, ErrMsg::MsgBlock(event));
+                        self.frame_timer = Some((period, now));
+                    } else {
+                        self.frame_timer = Some((period, end));
+                        d.bc_next_at = end;
+                    }
+                }
+            }
+            Op::Halt => {
+                d.log(WRD_EMPTY, ErrMsg::MsgBlock(BlockEvent::Halted(at)));
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl EventHandler for CommandBlockBC {
+    fn on_bc_ready(&mut self, d: &mut Device) {
+        if let Some(at) = self.last.interrupt.take() {
+            d.log(WRD_EMPTY, ErrMsg::MsgBlock(BlockEvent::Interrupt(at)));
+        }
+        let now = d.clock.elapsed().as_nanos();
+        for _ in 0..MAX_STEPS {
+            if self.halted {
+                return;
+            }
+            self.halted = !self.step(d);
+            if d.state != State::Idle || !d.write_queue.is_empty() || d.bc_next_at > now {
+                return;
+            }
+        }
+    }
+    fn on_sts(&mut self, d: &mut Device, w: &mut Word) {
+        // (an answer to a retry after a timeout)
+        self.last.timed_out = false;
+        self.last.statuses.push(*w);
+        self.default_on_sts(d, w);
+    }
+    fn on_bc_timeout(&mut self, d: &mut Device) {
+        self.last.timed_out = true;
+        self.default_on_bc_timeout(d);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::builder::SystemBuilder;
+    use crate::sys_bus::{LogRecord, StatusFlag, System, TR};
+
+    fn eval_program(program: BlockProgram, ms: u64) -> System {
+        let bc = CommandBlockBC::new(program).unwrap();
+        let mut sys_bus = SystemBuilder::test_bus(Box::new(bc)).build().unwrap();
+        let mut rt = sys_bus.devices[2].lock().unwrap();
+        rt.set_flag(StatusFlag::ServiceRequest, true);
+        drop(rt);
+        sys_bus.go();
+        sys_bus.sleep_ms(ms);
+        sys_bus.stop();
+        sys_bus.join();
+        sys_bus
+    }
+
+    fn bc_records(sys_bus: &System, f: impl Fn(&LogRecord) -> bool) -> Vec<LogRecord> {
+        let bc = sys_bus.devices[0].lock().unwrap();
+        bc.logs.iter().filter(|l| f(l)).cloned().collect()
+    }
+
+    #[test]
+    fn test_block_program() {
+        let program = BlockProgram::from_json(
+            r#"{"blocks": [
+                {"label": "frame", "op": {"Execute": {"RT2BC": [1, 2]}}},
+                {"op": {"Jump": "frame"}, "condition": {"Not": "Good"}},
+                {"op": "Halt"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            program.blocks[1].condition,
+            Condition::Not(Box::new(Condition::Good))
+        );
+        assert_eq!(program.blocks[2].condition, Condition::Always);
+        assert_eq!(program.labels().unwrap()["frame"], 0);
+        let unknown = r#"{"blocks": [{"op": {"Call": "nowhere"}}]}"#;
+        assert!(BlockProgram::from_json(unknown)
+            .unwrap_err()
+            .contains("nowhere"));
+        let twice = r#"{"blocks": [{"label": "a", "op": "Return"}, {"label": "a", "op": "Halt"}]}"#;
+        assert!(BlockProgram::from_json(twice).is_err());
+
+        let path = std::env::temp_dir().join("sys_bus_blocks_test.json");
+        fs::write(&path, r#"{"blocks": [{"op": {"Delay": 1000}}]}"#).unwrap();
+        let bc = CommandBlockBC::load(&path).unwrap();
+        assert_eq!(bc.program.blocks[0].op, Op::Delay(1_000));
+        fs::remove_file(&path).unwrap();
+        assert!(CommandBlockBC::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_command_block_frames() {
+        // RT 1 and 2 every 1 ms minor frame, with the service request of
+        // RT 2 answered by a subroutine
+        let program = BlockProgram::from_json(
+            r#"{"blocks": [
+                {"op": {"LoadFrameTimer": 1000000}},
+                {"label": "frame", "op": {"Execute": {"BC2RT": [1, [1, 2]]}}, "interrupt": true},
+                {"op": {"Execute": {"RT2BC": [2, 1]}}},
+                {"op": {"Call": "service"}, "condition": "ServiceRequest"},
+                {"op": "WaitFrame"},
+                {"op": {"Jump": "frame"}},
+                {"label": "service", "op": {"Execute": {"ModeCode": [2, 16, null]}}},
+                {"op": "Return"}]}"#,
+        )
+        .unwrap();
+        let sys_bus = eval_program(program, 20);
+        let commands = bc_records(&sys_bus, |l| l.is_bc_command());
+        let frames: Vec<u128> = commands
+            .iter()
+            .filter(|l| l.word.address() == 1)
+            .map(|l| l.time)
+            .collect();
+        assert!(frames.len() >= 19);
+        // on the 1 ms grid (but for the ns the very first write waits)
+        assert!(frames[1..].windows(2).all(|t| t[1] - t[0] == 1_000_000));
+        let vector_requests = commands
+            .iter()
+            .filter(|l| l.word.is_mode_cmd() && l.word.mode_code() == 16)
+            .count();
+        // (answering it clears the request)
+        assert_eq!(vector_requests, 1);
+        let interrupts = bc_records(&sys_bus, |l| {
+            l.event == ErrMsg::MsgBlock(BlockEvent::Interrupt(1))
+        });
+        assert!(interrupts.len() + 1 >= frames.len());
+        assert!(bc_records(&sys_bus, |l| matches!(
+            l.event,
+            ErrMsg::MsgBlock(BlockEvent::FrameOverrun(..))
+        ))
+        .is_empty());
+    }
+
+    #[test]
+    fn test_command_block_branches() {
+        let program = BlockProgram::from_json(
+            r#"{"blocks": [
+                {"op": {"Execute": {"RT2BC": [7, 1]}}},
+                {"op": {"Jump": "lost"}, "condition": "NoResponse"},
+                {"op": {"Execute": {"BC2RT": [1, [9]]}}},
+                {"label": "lost", "op": "Skip", "condition": {"Not": "Good"}},
+                {"op": {"Execute": {"BC2RT": [1, [8]]}}},
+                {"op": {"Execute": {"RT2BC": [1, 1]}}},
+                {"op": "Skip", "condition": "Good"},
+                {"op": {"Execute": {"BC2RT": [1, [7]]}}},
+                {"op": "Halt"}]}"#,
+        )
+        .unwrap();
+        // (long enough for a timeout while the BC warms up)
+        let sys_bus = eval_program(program, 50);
+        let to_rt1: Vec<TR> = bc_records(&sys_bus, |l| l.is_bc_command())
+            .iter()
+            .filter(|l| l.word.address() == 1)
+            .map(|l| l.word.tr())
+            .collect();
+        assert_eq!(to_rt1, vec![TR::Transmit]);
+        let halted = bc_records(&sys_bus, |l| matches!(l.event, ErrMsg::MsgBlock(_)));
+        assert_eq!(halted.len(), 1);
+        assert_eq!(halted[0].event, ErrMsg::MsgBlock(BlockEvent::Halted(8)));
+    }
+}
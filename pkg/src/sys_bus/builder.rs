@@ -0,0 +1,373 @@
+use crate::sys_bus::fault::FaultInjector;
+use crate::sys_bus::illegal::Illegalization;
+use crate::sys_bus::log::{FilteredSink, LogConfig, LogFilter, LogSink};
+use crate::sys_bus::retry::RetryPolicy;
+use crate::sys_bus::timing::TimingProfile;
+use crate::sys_bus::{
+    DefaultEventHandler, EventHandler, EventHandlerEmitter, Mode, System, TimingPolicy,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// One device of a topology.
+pub struct DeviceSpec {
+    pub address: u8,
+    pub mode: Mode,
+    pub emitter: Arc<Mutex<EventHandlerEmitter>>,
+    // attacker: mimics the protocol but never responds as a real terminal
+    pub fake: bool,
+    // overrides the write delays of the system
+    pub write_delays: Option<u128>,
+    // sampled write delays instead of fixed ones
+    pub timing: Option<TimingProfile>,
+    // backup BC: bus silence before it takes control (ns, 0: only when
+    // handed over)
+    pub takeover_after: Option<u128>,
+}
+
+impl DeviceSpec {
+    pub fn new(address: u8, mode: Mode, handler: Box<dyn EventHandler>) -> Self {
+        let emitter = Arc::new(Mutex::new(EventHandlerEmitter { handler }));
+        DeviceSpec::with_emitter(address, mode, emitter)
+    }
+
+    /// Device driven by an emitter kept by the caller (e.g. to swap the
+    /// handler while running, as `AttackController` does).
+    pub fn with_emitter(address: u8, mode: Mode, emitter: Arc<Mutex<EventHandlerEmitter>>) -> Self {
+        DeviceSpec {
+            address,
+            mode,
+            emitter,
+            fake: false,
+            write_delays: None,
+            timing: None,
+            takeover_after: None,
+        }
+    }
+
+    pub fn fake(mut self) -> Self {
+        self.fake = true;
+        self
+    }
+
+    #[allow(unused)]
+    pub fn delays(mut self, write_delays: u128) -> Self {
+        self.write_delays = Some(write_delays);
+        self
+    }
+
+    #[allow(unused)]
+    pub fn timing(mut self, profile: TimingProfile) -> Self {
+        self.timing = Some(profile);
+        self
+    }
+
+    /// Backup BC: an RT (with a BC handler) that accepts dynamic bus control
+    /// and takes control after `takeover_after` ns of bus silence.
+    #[allow(unused)]
+    pub fn backup(mut self, takeover_after: u128) -> Self {
+        self.takeover_after = Some(takeover_after);
+        self
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BuildError {
+    // two real devices share an address
+    DuplicateAddress(u8),
+    // number of real BCs found
+    BusControllers(usize),
+    // channels asked for vs devices declared
+    ChannelCount { channels: u32, devices: u32 },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::DuplicateAddress(a) => write!(f, "address {} is used twice", a),
+            BuildError::BusControllers(n) => write!(f, "{} active BCs (exactly one expected)", n),
+            BuildError::ChannelCount { channels, devices } => {
+                write!(f, "{} channels for {} devices", channels, devices)
+            }
+        }
+    }
+}
+
+/// Declares the devices of a bus (in the order they get their channels) and
+/// returns the `System` with every device running, waiting for `go`.
+pub struct SystemBuilder {
+    write_delays: u128,
+    home_dir: Option<String>,
+    seed: Option<u64>,
+    n_buses: u8,
+    channels: Option<u32>,
+    phy: bool,
+    timing_policy: TimingPolicy,
+    retry_policy: RetryPolicy,
+    illegalization: Illegalization,
+    faults: Option<FaultInjector>,
+    log_config: LogConfig,
+    log_sinks: Vec<FilteredSink>,
+    devices: Vec<DeviceSpec>,
+}
+
+#[allow(unused)]
+impl SystemBuilder {
+    pub fn new(write_delays: u128) -> Self {
+        SystemBuilder {
+            write_delays,
+            home_dir: None,
+            seed: None,
+            n_buses: 1,
+            channels: None,
+            phy: false,
+            timing_policy: TimingPolicy::Record,
+            retry_policy: RetryPolicy::default(),
+            illegalization: Illegalization::default(),
+            faults: None,
+            log_config: LogConfig::default(),
+            log_sinks: Vec::new(),
+            devices: Vec::new(),
+        }
+    }
+
+    /// Log directory (a timestamp by default).
+    pub fn home_dir(mut self, home_dir: String) -> Self {
+        self.home_dir = Some(home_dir);
+        self
+    }
+
+    /// Runs on the discrete-event engine (`System::new_virtual`).
+    pub fn virtual_time(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn buses(mut self, n_buses: u8) -> Self {
+        self.n_buses = n_buses;
+        self
+    }
+
+    /// Channels to allocate, checked against the devices (by default one per
+    /// device).
+    pub fn channels(mut self, channels: u32) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    pub fn physical_layer(mut self, on: bool) -> Self {
+        self.phy = on;
+        self
+    }
+
+    pub fn timing_policy(mut self, policy: TimingPolicy) -> Self {
+        self.timing_policy = policy;
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    pub fn illegalization(mut self, illegalization: Illegalization) -> Self {
+        self.illegalization = illegalization;
+        self
+    }
+
+    pub fn faults(mut self, faults: FaultInjector) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    pub fn log_config(mut self, config: LogConfig) -> Self {
+        self.log_config = config;
+        self
+    }
+
+    pub fn log_sink(mut self, filter: LogFilter, sink: Arc<Mutex<dyn LogSink>>) -> Self {
+        self.log_sinks.push(FilteredSink { filter, sink });
+        self
+    }
+
+    pub fn device(mut self, spec: DeviceSpec) -> Self {
+        self.devices.push(spec);
+        self
+    }
+
+    /// Plain RTs (`DefaultEventHandler`).
+    pub fn rts(mut self, addresses: impl IntoIterator<Item = u8>) -> Self {
+        for address in addresses {
+            let spec = DeviceSpec::new(address, Mode::RT, Box::new(DefaultEventHandler {}));
+            self.devices.push(spec);
+        }
+        self
+    }
+
+    fn validate(&self) -> Result<(), BuildError> {
+        let mut addresses = HashSet::new();
+        for d in self.devices.iter().filter(|d| !d.fake) {
+            if !addresses.insert(d.address) {
+                return Err(BuildError::DuplicateAddress(d.address));
+            }
+        }
+        let n_bc = self
+            .devices
+            .iter()
+            .filter(|d| d.mode == Mode::BC && !d.fake)
+            .count();
+        if n_bc != 1 {
+            return Err(BuildError::BusControllers(n_bc));
+        }
+        let devices = self.devices.len() as u32;
+        match self.channels {
+            Some(channels) if channels != devices => {
+                Err(BuildError::ChannelCount { channels, devices })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn build(self) -> Result<System, BuildError> {
+        self.validate()?;
+        let n_devices = self.devices.len() as u32;
+        let mut sys_bus = match (self.home_dir, self.seed) {
+            (Some(home_dir), Some(seed)) => {
+                System::new_virtual_with_name(n_devices, self.write_delays, home_dir, seed)
+            }
+            (None, Some(seed)) => System::new_virtual(n_devices, self.write_delays, seed),
+            (Some(home_dir), None) => System::new_with_name(n_devices, self.write_delays, home_dir),
+            (None, None) => System::new(n_devices, self.write_delays),
+        };
+        sys_bus.set_n_buses(self.n_buses);
+        sys_bus.set_physical_layer(self.phy);
+        sys_bus.set_timing_policy(self.timing_policy);
+        sys_bus.set_retry_policy(self.retry_policy);
+        sys_bus.illegalization = self.illegalization;
+        sys_bus.log_config = self.log_config;
+        sys_bus.log_sinks = self.log_sinks;
+        if let Some(faults) = self.faults {
+            sys_bus.set_faults(faults);
+        }
+        for d in self.devices {
+            sys_bus.plug(d);
+        }
+        Ok(sys_bus)
+    }
+}
+
+#[cfg(test)]
+impl SystemBuilder {
+    /// The bus of most tests: `bc` at address 0 and RTs 1 and 2, in virtual
+    /// time, without the system log files.
+    pub fn test_bus(bc: Box<dyn EventHandler>) -> Self {
+        SystemBuilder::new(4_000).virtual_time(0).test_devices(bc)
+    }
+
+    /// The devices (and logs) of `test_bus` on this builder.
+    pub fn test_devices(self, bc: Box<dyn EventHandler>) -> Self {
+        let config = LogConfig {
+            sys_logs: false,
+            ..LogConfig::default()
+        };
+        self.log_config(config)
+            .device(DeviceSpec::new(0, Mode::BC, bc))
+            .rts(1..3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::log::MemorySink;
+    use crate::sys_bus::{DefaultBCEventHandler, Proto};
+
+    fn bc() -> DeviceSpec {
+        DeviceSpec::new(
+            0,
+            Mode::BC,
+            Box::new(DefaultBCEventHandler {
+                total_device: 3,
+                target: 0,
+                data: vec![1, 2, 3],
+                proto: Proto::BC2RT,
+                proto_rotate: true,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_builder_validation() {
+        let err = |b: SystemBuilder| b.build().err().unwrap();
+        assert_eq!(
+            err(SystemBuilder::new(4_000).device(bc()).rts([1, 2, 1])),
+            BuildError::DuplicateAddress(1)
+        );
+        assert_eq!(
+            err(SystemBuilder::new(4_000).rts([1, 2])),
+            BuildError::BusControllers(0)
+        );
+        assert_eq!(
+            {
+                let mut bc2 = bc();
+                bc2.address = 2;
+                err(SystemBuilder::new(4_000).device(bc()).device(bc2).rts([1]))
+            },
+            BuildError::BusControllers(2)
+        );
+        assert_eq!(
+            err(SystemBuilder::new(4_000)
+                .device(bc())
+                .rts([1, 2])
+                .channels(4)),
+            BuildError::ChannelCount {
+                channels: 4,
+                devices: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_builder_runs() {
+        let attacker = DeviceSpec::new(1, Mode::RT, Box::new(DefaultEventHandler {}))
+            .fake()
+            .delays(0);
+        let writes = Arc::new(Mutex::new(MemorySink::default()));
+        let home_dir = std::env::temp_dir().join("sv1dur_test_builder");
+        let mut sys_bus = SystemBuilder::new(4_000)
+            .virtual_time(0)
+            .home_dir(home_dir.to_str().unwrap().to_owned())
+            .log_config(LogConfig {
+                sys_logs: false,
+                ..LogConfig::default()
+            })
+            .log_sink(
+                LogFilter::default().devices([1]).kinds(["MsgWrt"]),
+                writes.clone(),
+            )
+            .device(bc())
+            .rts([1, 2])
+            .device(attacker)
+            .build()
+            .unwrap();
+        assert_eq!(sys_bus.transmitters.len(), 4);
+        sys_bus.go();
+        sys_bus.sleep_ms(20);
+        sys_bus.stop();
+        sys_bus.join();
+        let attacker = sys_bus.devices[3].lock().unwrap();
+        assert!(attacker.fake);
+        assert_eq!(attacker.write_delays, 0);
+        drop(attacker);
+        assert_eq!(sys_bus.devices[0].lock().unwrap().timeout_times, 0);
+        let writes = &writes.lock().unwrap().logs;
+        assert!(!writes.is_empty());
+        assert!(writes
+            .iter()
+            .all(|l| l.device == 1 && l.event.kind() == "MsgWrt"));
+        assert!(!home_dir.exists());
+        assert!(sys_bus.devices[1].lock().unwrap().logs.len() > 10);
+    }
+}
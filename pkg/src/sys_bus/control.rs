@@ -0,0 +1,262 @@
+use crate::sys_bus::log::{LogFilter, LogRecord};
+use crate::sys_bus::{ErrMsg, Mode, State};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// Condition on the records of the devices that freezes the system.
+#[allow(unused)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Breakpoint {
+    // the BC puts a command for the RT on the bus
+    CommandTo(u8),
+    // a device enters the state
+    EnterState(State),
+    // a record of the kind (`ErrMsg::kind`), e.g. "MsgAttk"
+    Kind(String),
+    // any word put on the bus
+    Word,
+    // the BC is done with its message and ready for the next one
+    BcReady,
+    Filter(LogFilter),
+}
+
+impl Breakpoint {
+    pub fn matches(&self, l: &LogRecord) -> bool {
+        match self {
+            Breakpoint::CommandTo(rt) => l.is_bc_command() && l.word.address() == *rt,
+            Breakpoint::EnterState(state) => {
+                matches!(l.event, ErrMsg::MsgStaChg(_)) && l.state == *state
+            }
+            Breakpoint::Kind(kind) => l.event.kind() == *kind,
+            Breakpoint::Word => matches!(l.event, ErrMsg::MsgWrt(_)),
+            Breakpoint::BcReady => l.mode == Mode::BC && l.event == ErrMsg::MsgBCReady,
+            Breakpoint::Filter(filter) => filter.matches(l),
+        }
+    }
+}
+
+/// Why the system is frozen: the breakpoint (`None` id for a step) and the
+/// record that hit it.
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct BreakHit {
+    pub id: Option<usize>,
+    pub breakpoint: Breakpoint,
+    pub record: LogRecord,
+}
+
+#[derive(Debug, Default)]
+struct Breakpoints {
+    next_id: usize,
+    set: Vec<(usize, Breakpoint)>,
+    // one-shot breakpoint of a step
+    step: Option<Breakpoint>,
+}
+
+/// Execution control shared by a system and its devices. Devices check the
+/// breakpoints on each record they log; while paused, device threads wait
+/// at the top of their loop (without the lock of their device) and the
+/// virtual engine stops handling events.
+#[derive(Debug, Default)]
+pub struct ExecControl {
+    paused: AtomicBool,
+    // some breakpoint is set (spares the lock on every record)
+    armed: AtomicBool,
+    breakpoints: Mutex<Breakpoints>,
+    hit: Mutex<Option<BreakHit>>,
+    // device threads waiting in `park`
+    parked: AtomicUsize,
+    gate: Mutex<()>,
+    resumed: Condvar,
+}
+
+impl ExecControl {
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        let _gate = self.gate.lock().unwrap();
+        self.paused.store(false, Ordering::SeqCst);
+        *self.hit.lock().unwrap() = None;
+        self.resumed.notify_all();
+    }
+
+    pub fn add(&self, breakpoint: Breakpoint) -> usize {
+        let mut bps = self.breakpoints.lock().unwrap();
+        bps.next_id += 1;
+        let id = bps.next_id;
+        bps.set.push((id, breakpoint));
+        self.armed.store(true, Ordering::SeqCst);
+        id
+    }
+
+    pub fn remove(&self, id: usize) -> bool {
+        let mut bps = self.breakpoints.lock().unwrap();
+        let len = bps.set.len();
+        bps.set.retain(|(i, _)| *i != id);
+        self.rearm(&bps);
+        bps.set.len() != len
+    }
+
+    pub fn clear(&self) {
+        let mut bps = self.breakpoints.lock().unwrap();
+        bps.set.clear();
+        self.rearm(&bps);
+    }
+
+    /// Freezes the system at the next record matching `breakpoint`, once.
+    pub fn step(&self, breakpoint: Option<Breakpoint>) {
+        let mut bps = self.breakpoints.lock().unwrap();
+        bps.step = breakpoint;
+        self.rearm(&bps);
+    }
+
+    fn rearm(&self, bps: &Breakpoints) {
+        let armed = !bps.set.is_empty() || bps.step.is_some();
+        self.armed.store(armed, Ordering::SeqCst);
+    }
+
+    /// Checks a record just logged by a device; pauses on a hit (true).
+    pub fn check(&self, l: &LogRecord) -> bool {
+        if !self.armed.load(Ordering::Relaxed) || self.is_paused() {
+            return false;
+        }
+        let mut bps = self.breakpoints.lock().unwrap();
+        if self.is_paused() {
+            // another device got there first
+            return false;
+        }
+        let hit = match &bps.step {
+            Some(bp) if bp.matches(l) => Some((None, bps.step.take().unwrap())),
+            _ => bps
+                .set
+                .iter()
+                .find(|(_, bp)| bp.matches(l))
+                .map(|(id, bp)| (Some(*id), bp.clone())),
+        };
+        let Some((id, breakpoint)) = hit else {
+            return false;
+        };
+        self.rearm(&bps);
+        self.pause();
+        *self.hit.lock().unwrap() = Some(BreakHit {
+            id,
+            breakpoint,
+            record: l.clone(),
+        });
+        true
+    }
+
+    /// The hit that froze the system, until it resumes.
+    pub fn hit(&self) -> Option<BreakHit> {
+        self.hit.lock().unwrap().clone()
+    }
+
+    /// Blocks a device thread while the system is paused (or until `exit`);
+    /// `idle` runs on every wake up, at least every 10ms.
+    pub fn park(&self, exit: &AtomicBool, mut idle: impl FnMut()) {
+        self.parked.fetch_add(1, Ordering::SeqCst);
+        let mut gate = self.gate.lock().unwrap();
+        while self.is_paused() && !exit.load(Ordering::Relaxed) {
+            idle();
+            gate = self
+                .resumed
+                .wait_timeout(gate, Duration::from_millis(10))
+                .unwrap()
+                .0;
+        }
+        self.parked.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn parked(&self) -> usize {
+        self.parked.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::{Word, TR, WRD_EMPTY};
+
+    fn record(mode: Mode, state: State, word: Word, event: ErrMsg) -> LogRecord {
+        LogRecord {
+            time: 0,
+            mode,
+            device: 0,
+            address: 0,
+            state,
+            word,
+            event,
+            avg_delta_t: 0,
+        }
+    }
+
+    #[test]
+    fn test_breakpoint_matches() {
+        let cmd = record(
+            Mode::BC,
+            State::BusyTrx,
+            Word::new_cmd(5, 2, TR::Receive),
+            ErrMsg::MsgWrt(2),
+        );
+        assert!(Breakpoint::CommandTo(5).matches(&cmd));
+        assert!(!Breakpoint::CommandTo(4).matches(&cmd));
+        assert!(Breakpoint::Word.matches(&cmd));
+        let data = record(
+            Mode::BC,
+            State::BusyTrx,
+            Word::new_data(5),
+            ErrMsg::MsgWrt(1),
+        );
+        assert!(!Breakpoint::CommandTo(5).matches(&data));
+        let enter = record(Mode::RT, State::AwtData, WRD_EMPTY, ErrMsg::MsgStaChg(0));
+        assert!(Breakpoint::EnterState(State::AwtData).matches(&enter));
+        let stay = record(Mode::RT, State::AwtData, WRD_EMPTY, ErrMsg::MsgEntDat);
+        assert!(!Breakpoint::EnterState(State::AwtData).matches(&stay));
+        let attack = record(
+            Mode::RT,
+            State::Idle,
+            WRD_EMPTY,
+            ErrMsg::MsgAttk("x".into()),
+        );
+        assert!(Breakpoint::Kind("MsgAttk".to_owned()).matches(&attack));
+    }
+
+    #[test]
+    fn test_exec_control() {
+        let control = ExecControl::default();
+        let attack = record(
+            Mode::RT,
+            State::Idle,
+            WRD_EMPTY,
+            ErrMsg::MsgAttk("x".into()),
+        );
+        control.check(&attack);
+        assert!(!control.is_paused());
+
+        let id = control.add(Breakpoint::Kind("MsgAttk".to_owned()));
+        control.check(&attack);
+        assert!(control.is_paused());
+        assert_eq!(control.hit().unwrap().id, Some(id));
+        control.resume();
+        assert!(control.hit().is_none());
+        assert!(control.remove(id));
+        assert!(!control.remove(id));
+
+        // a step stops once
+        let ready = record(Mode::BC, State::Idle, WRD_EMPTY, ErrMsg::MsgBCReady);
+        control.step(Some(Breakpoint::BcReady));
+        control.check(&ready);
+        assert_eq!(control.hit().unwrap().id, None);
+        control.resume();
+        control.check(&ready);
+        assert!(!control.is_paused());
+    }
+}
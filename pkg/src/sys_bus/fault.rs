@@ -0,0 +1,191 @@
+use crate::sys_bus::phy::WORD_BITS;
+use crate::sys_bus::Word;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Electrical fault between the transmitters and the receivers. Devices are
+/// given by id (order in which they were added to the system).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum Fault {
+    // every bit of every word is flipped with probability `ber`
+    BitErrors { ber: f64 },
+    // the stub of the device is cut: it neither sends nor receives
+    StubDisconnect { device: u32 },
+    // reflections (lost termination) garble a bit of a word with probability
+    // `p`, for these receivers only
+    Reflection { receivers: Vec<u32>, p: f64 },
+    // words from one device to another are lost with probability `p`
+    LinkDrop { from: u32, to: u32, p: f64 },
+}
+
+/// Fault that damaged a word, carried along with it (`Word::fault`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum FaultLabel {
+    Clean = 0,
+    BitErrors = 1,
+    Reflection = 2,
+}
+
+impl From<u8> for FaultLabel {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => FaultLabel::Clean,
+            1 => FaultLabel::BitErrors,
+            _ => FaultLabel::Reflection,
+        }
+    }
+}
+
+impl FaultLabel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FaultLabel::Clean => "",
+            FaultLabel::BitErrors => "~ber",
+            FaultLabel::Reflection => "~rfl",
+        }
+    }
+}
+
+/// A fault active from `start` until `end` (ns on the system clock, none:
+/// until the end of the run).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct FaultEvent {
+    pub start: u128,
+    pub end: Option<u128>,
+    pub fault: Fault,
+}
+
+impl FaultEvent {
+    fn active(&self, now: u128) -> bool {
+        now >= self.start && self.end.is_none_or(|end| now < end)
+    }
+}
+
+#[derive(Deserialize)]
+struct FaultScript {
+    #[serde(default)]
+    seed: u64,
+    events: Vec<FaultEvent>,
+}
+
+/// Applies the scripted faults to each word on its way to each receiver.
+#[derive(Debug)]
+pub struct FaultInjector {
+    pub events: Vec<FaultEvent>,
+    rng: StdRng,
+}
+
+impl FaultInjector {
+    pub fn new(events: Vec<FaultEvent>, seed: u64) -> Self {
+        FaultInjector {
+            events,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// e.g. `{"seed": 1, "events": [{"start": 0, "end": 5000000, "fault":
+    /// {"LinkDrop": {"from": 0, "to": 2, "p": 0.5}}}]}`
+    pub fn from_json(s: &str) -> Result<Self, String> {
+        let script: FaultScript = serde_json::from_str(s).map_err(|e| e.to_string())?;
+        Ok(FaultInjector::new(script.events, script.seed))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        FaultInjector::from_json(&s)
+    }
+
+    /// The word as device `to` gets it from device `from` at `now`; none when
+    /// it is lost.
+    pub fn deliver(&mut self, from: u32, to: u32, mut w: Word, now: u128) -> Option<Word> {
+        let rng = &mut self.rng;
+        for e in self.events.iter().filter(|e| e.active(now)) {
+            match &e.fault {
+                Fault::StubDisconnect { device } if *device == from || *device == to => {
+                    return None;
+                }
+                Fault::LinkDrop { from: f, to: t, p }
+                    if (*f, *t) == (from, to) && rng.gen::<f64>() < *p =>
+                {
+                    return None;
+                }
+                Fault::BitErrors { ber } => {
+                    for b in 0..WORD_BITS {
+                        if rng.gen::<f64>() < *ber {
+                            w.0 ^= 1 << b;
+                            w.set_fault(FaultLabel::BitErrors as u8);
+                        }
+                    }
+                }
+                Fault::Reflection { receivers, p }
+                    if receivers.contains(&to) && rng.gen::<f64>() < *p =>
+                {
+                    w.0 ^= 1 << rng.gen_range(0..WORD_BITS);
+                    w.set_fault(FaultLabel::Reflection as u8);
+                }
+                _ => {}
+            }
+        }
+        Some(w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_fault_script() {
+        let mut faults = FaultInjector::from_json(
+            r#"{"events": [
+                {"start": 0, "end": 100, "fault": {"StubDisconnect": {"device": 1}}},
+                {"start": 0, "end": null, "fault": {"LinkDrop": {"from": 0, "to": 2, "p": 1.0}}},
+                {"start": 200, "end": null, "fault": {"Reflection": {"receivers": [3], "p": 1.0}}}
+            ]}"#,
+        )
+        .unwrap();
+        let w = Word::new_data(0x55);
+        // disconnected both ways, until 100
+        assert!(faults.deliver(0, 1, w, 50).is_none());
+        assert!(faults.deliver(1, 3, w, 50).is_none());
+        assert!(faults.deliver(0, 1, w, 100).is_some());
+        // one link only
+        assert!(faults.deliver(0, 2, w, 50).is_none());
+        assert!(faults.deliver(2, 0, w, 50).is_some());
+        // garbled for RT@3 only, from 200
+        assert_eq!(faults.deliver(0, 3, w, 150).unwrap().0, w.0);
+        let r = faults.deliver(0, 3, w, 250).unwrap();
+        assert_eq!(((r.0 ^ w.0) & 0xf_ffff).count_ones(), 1);
+        assert_eq!(r.fault(), FaultLabel::Reflection);
+        assert_eq!(
+            faults.deliver(0, 1, w, 250).unwrap().fault(),
+            FaultLabel::Clean
+        );
+        assert!(FaultInjector::from_json(r#"{"events": [{"start": 0}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_bit_errors_rate() {
+        let fault = Fault::BitErrors { ber: 0.01 };
+        let mut faults = FaultInjector::new(
+            vec![FaultEvent {
+                start: 0,
+                end: None,
+                fault,
+            }],
+            7,
+        );
+        let w = Word::new_data(0);
+        let flipped: u32 = (0..10_000)
+            .map(|_| (faults.deliver(0, 1, w, 0).unwrap().0 ^ w.0) & 0xf_ffff)
+            .map(|x| x.count_ones())
+            .sum();
+        // 200_000 bits
+        assert!((1_600..2_400).contains(&flipped));
+    }
+}
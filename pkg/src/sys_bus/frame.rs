@@ -0,0 +1,274 @@
+use crate::sys_bus::{Device, ErrMsg, EventHandler, Message, BROADCAST_ADDRESS, WRD_EMPTY};
+use serde::{Deserialize, Serialize};
+
+/// Frame events logged by a `FrameScheduler` BC (`ErrMsg::MsgFrame`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FrameEvent {
+    // a minor frame starts (index in the major frame)
+    Start(usize),
+    // the messages of a minor frame did not fit in it: how late (ns) and
+    // how many were dropped (with those of the frames missed altogether)
+    Overrun {
+        minor: usize,
+        late: u128,
+        skipped: usize,
+    },
+}
+
+/// Cyclic executive table: a major frame of minor frames of `minor_period`
+/// ns each, e.g. 50 minor frames of 20 ms for 50 Hz in a 1 Hz major frame.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrameTable {
+    pub minor_period: u128,
+    // messages of each minor frame, sent in order from its start
+    pub minor_frames: Vec<Vec<Message>>,
+    // each minor frame opens with a synchronize mode code (broadcast)
+    #[serde(default)]
+    pub sync: bool,
+}
+
+#[allow(unused)]
+impl FrameTable {
+    pub fn new(minor_period: u128, n_minor: usize) -> Self {
+        FrameTable {
+            minor_period,
+            minor_frames: vec![Vec::new(); n_minor],
+            sync: false,
+        }
+    }
+
+    pub fn sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    /// Sends `msg` in every `every`-th minor frame from `offset`, e.g. every
+    /// 5th for 10 Hz with 50 Hz minor frames.
+    pub fn schedule(mut self, msg: Message, every: usize, offset: usize) -> Self {
+        for frame in self
+            .minor_frames
+            .iter_mut()
+            .skip(offset)
+            .step_by(every.max(1))
+        {
+            frame.push(msg.clone());
+        }
+        self
+    }
+
+    pub fn major_period(&self) -> u128 {
+        self.minor_period * self.minor_frames.len() as u128
+    }
+}
+
+/// BC handler running a `FrameTable`: minor frames start on a fixed grid
+/// from the first call (the BC waits for the next one once a frame is
+/// done); messages still due when a frame ends are dropped and reported.
+#[allow(unused)]
+pub struct FrameScheduler {
+    pub table: FrameTable,
+    // start of the current minor frame (device clock), `None` before the first
+    start: Option<u128>,
+    minor: usize,
+    // next message of the current minor frame
+    next: usize,
+    // the frame is done, waiting for the next one to start
+    waiting: bool,
+}
+
+#[allow(unused)]
+impl FrameScheduler {
+    pub fn new(table: FrameTable) -> Self {
+        FrameScheduler {
+            table,
+            start: None,
+            minor: 0,
+            next: 0,
+            waiting: false,
+        }
+    }
+
+    fn begin(&mut self, d: &mut Device, minor: usize, start: u128) {
+        self.minor = minor;
+        self.start = Some(start);
+        self.next = 0;
+        self.waiting = false;
+        d.log(WRD_EMPTY, ErrMsg::MsgFrame(FrameEvent::Start(minor)));
+        if self.table.sync {
+            d.act_mode_code(BROADCAST_ADDRESS, 1, None);
+        }
+    }
+
+    // starts the frame after the current one, late at `now`; frames that
+    // should have ended by now are skipped
+    fn next_frame(&mut self, d: &mut Device, end: u128, now: u128) {
+        let n_minor = self.table.minor_frames.len();
+        let period = self.table.minor_period;
+        let overrun = !self.waiting && now > end;
+        let mut skipped = self.table.minor_frames[self.minor].len() - self.next;
+        let late = now.saturating_sub(end);
+        let missed = late / period;
+        for m in 1..=missed as usize {
+            skipped += self.table.minor_frames[(self.minor + m) % n_minor].len();
+        }
+        if overrun || skipped > 0 {
+            let event = FrameEvent::Overrun {
+                minor: self.minor,
+                late,
+                skipped,
+            };
+            d.log(WRD_EMPTY, ErrMsg::MsgFrame(event));
+        }
+        let minor = (self.minor + 1 + missed as usize) % n_minor;
+        self.begin(d, minor, end + missed * period);
+    }
+}
+
+impl EventHandler for FrameScheduler {
+    fn on_bc_ready(&mut self, d: &mut Device) {
+        let period = self.table.minor_period;
+        if self.table.minor_frames.is_empty() || period == 0 {
+            return;
+        }
+        let now = d.clock.elapsed().as_nanos();
+        let began = match self.start {
+            None => {
+                self.begin(d, 0, now);
+                true
+            }
+            Some(start) if now >= start + period => {
+                self.next_frame(d, start + period, now);
+                true
+            }
+            Some(_) => false,
+        };
+        if began && self.table.sync {
+            // the synchronize mode code takes this turn
+            return;
+        }
+        match self.table.minor_frames[self.minor].get(self.next).cloned() {
+            Some(msg) => {
+                self.next += 1;
+                d.act(&msg);
+            }
+            None => {
+                self.waiting = true;
+                d.bc_next_at = self.start.unwrap() + period;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::builder::SystemBuilder;
+    use crate::sys_bus::log::LogRecord;
+    use crate::sys_bus::{System, TR};
+
+    fn eval_frames(table: FrameTable, ms: u64) -> System {
+        let bc = Box::new(FrameScheduler::new(table));
+        let mut sys_bus = SystemBuilder::test_bus(bc).build().unwrap();
+        sys_bus.go();
+        sys_bus.sleep_ms(ms);
+        sys_bus.stop();
+        sys_bus.join();
+        sys_bus
+    }
+
+    fn frame_events(sys_bus: &System) -> Vec<(u128, FrameEvent)> {
+        let bc = sys_bus.devices[0].lock().unwrap();
+        bc.logs
+            .iter()
+            .filter_map(|l: &LogRecord| match &l.event {
+                ErrMsg::MsgFrame(e) => Some((l.time, e.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_frame_table() {
+        let table = FrameTable::new(20_000_000, 50)
+            .schedule(Message::BC2RT(1, vec![1]), 1, 0)
+            .schedule(Message::RT2BC(2, 2), 5, 2);
+        assert_eq!(table.major_period(), 1_000_000_000);
+        assert!(table
+            .minor_frames
+            .iter()
+            .all(|f| f[0] == Message::BC2RT(1, vec![1])));
+        let at_10hz: Vec<usize> = (0..50)
+            .filter(|i| table.minor_frames[*i].len() == 2)
+            .collect();
+        assert_eq!(at_10hz, (2..50).step_by(5).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_frame_scheduler() {
+        let table = FrameTable::new(1_000_000, 4)
+            .sync(true)
+            .schedule(Message::BC2RT(1, vec![1, 2]), 1, 0)
+            .schedule(Message::RT2BC(2, 2), 2, 1);
+        let sys_bus = eval_frames(table, 20);
+        let events = frame_events(&sys_bus);
+        let starts: Vec<u128> = events.iter().map(|(t, _)| *t).collect();
+        assert!(events
+            .iter()
+            .all(|(_, e)| matches!(e, FrameEvent::Start(_))));
+        assert!(starts.len() >= 19);
+        // on the grid of the first one
+        assert!(starts
+            .iter()
+            .all(|t| (t - starts[0]).is_multiple_of(1_000_000)));
+        let minors: Vec<usize> = events
+            .iter()
+            .map(|(_, e)| match e {
+                FrameEvent::Start(m) => *m,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert!(minors.iter().enumerate().all(|(i, m)| i % 4 == *m));
+
+        // every frame opens with the synchronize mode code, RT2 answers
+        // every other frame (the last frame started is left out, the run may
+        // stop in it)
+        let last = *starts.last().unwrap();
+        let starts = &starts[..starts.len() - 1];
+        let bc = sys_bus.devices[0].lock().unwrap();
+        let commands: Vec<_> = bc
+            .logs
+            .iter()
+            .filter(|l| l.is_bc_command() && l.time < last)
+            .collect();
+        let syncs = commands.iter().filter(|l| l.word.is_mode_cmd()).count();
+        assert_eq!(syncs, starts.len());
+        let to_rt2 = commands
+            .iter()
+            .filter(|l| l.word.address() == 2 && l.word.tr() == TR::Transmit)
+            .count();
+        assert_eq!(to_rt2, starts.len() / 2);
+        assert_eq!(sys_bus.metrics().frame_overruns, 0);
+    }
+
+    #[test]
+    fn test_frame_overrun() {
+        // three messages of about 200 us in frames of 300 us
+        let table = FrameTable::new(300_000, 2).schedule(Message::BC2RT(1, vec![0; 8]), 1, 0);
+        let table = table
+            .schedule(Message::BC2RT(2, vec![0; 8]), 1, 0)
+            .schedule(Message::BC2RT(1, vec![0; 8]), 1, 0);
+        let sys_bus = eval_frames(table, 5);
+        let overruns: Vec<FrameEvent> = frame_events(&sys_bus)
+            .into_iter()
+            .map(|(_, e)| e)
+            .filter(|e| matches!(e, FrameEvent::Overrun { .. }))
+            .collect();
+        assert!(!overruns.is_empty());
+        assert!(overruns.iter().all(|e| match e {
+            FrameEvent::Overrun { late, skipped, .. } => *skipped > 0 && *late < 300_000,
+            _ => false,
+        }));
+        assert_eq!(sys_bus.metrics().frame_overruns, overruns.len() as u64);
+    }
+}
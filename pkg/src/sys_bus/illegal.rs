@@ -0,0 +1,100 @@
+use crate::sys_bus::{Word, TR};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// One illegalized command pattern of an RT; a missing field matches any
+/// value. `word_count` only applies to data commands and `mode_code` to mode
+/// commands.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct IllegalCmd {
+    pub sub_address: Option<u8>,
+    pub tr: Option<TR>,
+    pub word_count: Option<u8>,
+    pub mode_code: Option<u8>,
+}
+
+impl IllegalCmd {
+    pub fn matches(&self, w: &Word) -> bool {
+        self.sub_address.is_none_or(|sa| sa == w.sub_address())
+            && self.tr.as_ref().is_none_or(|tr| *tr == w.tr())
+            && self
+                .word_count
+                .is_none_or(|wc| !w.is_mode_cmd() && wc == w.dword_count())
+            && self
+                .mode_code
+                .is_none_or(|mc| w.is_mode_cmd() && mc == w.mode_code())
+    }
+}
+
+/// Illegalization tables of the RTs, keyed by RT address, e.g.
+/// `{"1": [{"sub_address": 5, "tr": "Transmit"}, {"mode_code": 4}]}`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Illegalization {
+    pub terminals: HashMap<u8, Vec<IllegalCmd>>,
+}
+
+impl Illegalization {
+    pub fn from_json(s: &str) -> Result<Illegalization, String> {
+        serde_json::from_str(s).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Illegalization, String> {
+        let s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Illegalization::from_json(&s)
+    }
+
+    /// Table of the RT at `address` (empty: every command is legal).
+    pub fn rules(&self, address: u8) -> Vec<IllegalCmd> {
+        self.terminals.get(&address).cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::BROADCAST_ADDRESS;
+
+    #[test]
+    fn test_illegal_cmd_matches() {
+        let table = Illegalization::from_json(
+            r#"{"1": [{"sub_address": 1, "tr": "Transmit"}, {"word_count": 5}, {"mode_code": 4}]}"#,
+        )
+        .unwrap();
+        let rules = table.rules(1);
+        assert_eq!(rules.len(), 3);
+        assert!(table.rules(2).is_empty());
+        let illegal = |w: Word| rules.iter().any(|r| r.matches(&w));
+        assert!(illegal(Word::new_cmd(1, 2, TR::Transmit)));
+        assert!(!illegal(Word::new_cmd(1, 2, TR::Receive)));
+        assert!(illegal(Word::new_cmd(1, 5, TR::Receive)));
+        assert!(illegal(Word::new_mode_cmd(
+            BROADCAST_ADDRESS,
+            4,
+            TR::Transmit
+        )));
+        // (mode code 5 has the bits of word count 5)
+        assert!(!illegal(Word::new_mode_cmd(1, 5, TR::Receive)));
+        assert!(Illegalization::from_json(r#"{"1": [{"subaddress": 1}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_illegalization_load() {
+        let path = std::env::temp_dir().join("sys_bus_illegal_test.json");
+        fs::write(&path, r#"{"3": [{"mode_code": 4}]}"#).unwrap();
+        let table = Illegalization::load(&path).unwrap();
+        assert_eq!(
+            table.rules(3),
+            vec![IllegalCmd {
+                mode_code: Some(4),
+                ..Default::default()
+            }]
+        );
+        fs::remove_file(&path).unwrap();
+        assert!(Illegalization::load(&path).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::sys_bus::retry::Failure;
+use crate::sys_bus::{Message, Word};
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::time::Duration;
+
+/// What the BC got back for an injected message, over all its attempts.
+#[allow(unused)]
+#[derive(Clone, Debug, Default)]
+pub struct Transfer {
+    pub retries: u8,
+    pub statuses: Vec<Word>,
+    // data words received by the BC (RT to BC)
+    pub data: Vec<u32>,
+    // BC clock when first sent and when done (ns)
+    pub sent: u128,
+    pub done: u128,
+}
+
+/// How an injected message ended.
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub enum Outcome {
+    Completed(Transfer),
+    // the last attempt failed
+    Failed(Failure, Transfer),
+    // never sent (the RT is marked failed) or lost with the BC (switched
+    // off, bus control handed over, system stopped)
+    Dropped,
+}
+
+/// A message waiting for the BC, and where its outcome goes.
+#[derive(Debug)]
+pub struct Injected {
+    pub msg: Message,
+    pub done: Sender<Outcome>,
+}
+
+/// The injected message the BC is busy with.
+#[derive(Clone, Debug)]
+pub struct InFlight {
+    pub done: Sender<Outcome>,
+    pub transfer: Transfer,
+    // failure of the last attempt
+    pub failure: Option<Failure>,
+}
+
+impl InFlight {
+    pub fn new(done: Sender<Outcome>, now: u128) -> Self {
+        InFlight {
+            done,
+            transfer: Transfer {
+                sent: now,
+                ..Transfer::default()
+            },
+            failure: None,
+        }
+    }
+
+    pub fn finish(mut self, now: u128) {
+        self.transfer.done = now;
+        let outcome = match self.failure {
+            Some(failure) => Outcome::Failed(failure, self.transfer),
+            None => Outcome::Completed(self.transfer),
+        };
+        // (nobody may be waiting anymore)
+        let _ = self.done.send(outcome);
+    }
+}
+
+/// Sends one-shot messages to whichever device is the BC, to be sent at its
+/// next gap between scheduled messages; see `System::injector`.
+#[derive(Clone, Debug)]
+pub struct Injector {
+    pub tx: Sender<Injected>,
+}
+
+impl Injector {
+    pub fn inject(&self, msg: Message) -> MessageHandle {
+        let (done, rx) = bounded(1);
+        // (the receiving end lives as long as the system)
+        let _ = self.tx.send(Injected { msg, done });
+        MessageHandle { rx, outcome: None }
+    }
+}
+
+/// Completion of an injected message.
+pub struct MessageHandle {
+    rx: Receiver<Outcome>,
+    outcome: Option<Outcome>,
+}
+
+#[allow(unused)]
+impl MessageHandle {
+    /// The outcome, if the message is done.
+    pub fn try_outcome(&mut self) -> Option<Outcome> {
+        if self.outcome.is_none() {
+            self.outcome = match self.rx.try_recv() {
+                Ok(outcome) => Some(outcome),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => Some(Outcome::Dropped),
+            };
+        }
+        self.outcome.clone()
+    }
+
+    /// Waits for the outcome (real time, for a threaded system; a virtual one
+    /// only moves on in `System::sleep_ms`).
+    pub fn wait(&mut self, timeout: Duration) -> Option<Outcome> {
+        if self.outcome.is_none() {
+            self.outcome = match self.rx.recv_timeout(timeout) {
+                Ok(outcome) => Some(outcome),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => Some(Outcome::Dropped),
+            };
+        }
+        self.outcome.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::builder::SystemBuilder;
+    use crate::sys_bus::frame::{FrameScheduler, FrameTable};
+    use crate::sys_bus::{BROADCAST_ADDRESS, SA_DEFAULT};
+
+    #[test]
+    fn test_message_handle() {
+        let injector = Injector {
+            tx: crossbeam_channel::unbounded().0,
+        };
+        let mut handle = injector.inject(Message::RT2BC(1, 1));
+        // (the channel is gone with its receiver)
+        assert!(matches!(handle.try_outcome(), Some(Outcome::Dropped)));
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let injector = Injector { tx };
+        let mut handle = injector.inject(Message::RT2BC(1, 1));
+        assert!(handle.try_outcome().is_none());
+        let injected = rx.try_recv().unwrap();
+        let mut in_flight = InFlight::new(injected.done, 10);
+        in_flight.failure = Some(Failure::Timeout);
+        in_flight.finish(30);
+        match handle.wait(Duration::from_millis(10)) {
+            Some(Outcome::Failed(Failure::Timeout, t)) => assert_eq!((t.sent, t.done), (10, 30)),
+            o => panic!("{:?}", o),
+        }
+        // kept once received
+        assert!(handle.try_outcome().is_some());
+    }
+
+    #[test]
+    fn test_inject_into_frame_gaps() {
+        let table = FrameTable::new(1_000_000, 2).schedule(Message::BC2RT(1, vec![1]), 1, 0);
+        let bc = Box::new(FrameScheduler::new(table));
+        let mut sys_bus = SystemBuilder::test_bus(bc).build().unwrap();
+        sys_bus.go();
+        sys_bus.sleep_ms(5);
+        let mut read = sys_bus.inject(Message::RT2BC(2, 3));
+        let mut sync = sys_bus.inject(Message::ModeCode(BROADCAST_ADDRESS, 1, None));
+        // nobody at 7 (timeouts are long while the BC warms up)
+        let mut lost = sys_bus.inject(Message::RT2BC(7, 1));
+        assert!(read.try_outcome().is_none());
+        sys_bus.sleep_ms(40);
+        sys_bus.stop();
+        sys_bus.join();
+
+        let Some(Outcome::Completed(transfer)) = read.try_outcome() else {
+            panic!("{:?}", read.try_outcome());
+        };
+        assert_eq!(transfer.data, vec![1, 2, 3]);
+        assert_eq!(transfer.statuses.len(), 1);
+        assert_eq!(transfer.statuses[0].address(), 2);
+        // sent in the gap after the scheduled message of the frame
+        assert_eq!(
+            (transfer.sent / 1_000_000, transfer.done / 1_000_000),
+            (5, 5)
+        );
+        assert!(matches!(sync.try_outcome(), Some(Outcome::Completed(t)) if t.statuses.is_empty()));
+        assert!(matches!(
+            lost.try_outcome(),
+            Some(Outcome::Failed(Failure::Timeout, _))
+        ));
+        // the scheduled traffic goes on, but for the frames the BC spent
+        // waiting for RT 7
+        let metrics = sys_bus.metrics();
+        assert!(metrics.traffic[&1][&SA_DEFAULT].messages > 20);
+        assert_eq!(metrics.frame_overruns, 1);
+    }
+}
@@ -0,0 +1,520 @@
+use crate::sys_bus::{format_log, ErrMsg, Mode, State, Word, SYNC_CMD_STS};
+use crossbeam_channel::Sender;
+use serde::{Deserialize, Serialize};
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Version of the serialized `LogRecord`, bumped on any change of its fields
+/// (or of the enums it holds).
+pub const LOG_SCHEMA_VERSION: u32 = 6;
+
+/// One entry of a device log.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogRecord {
+    // ns on the system clock
+    pub time: u128,
+    pub mode: Mode,
+    // device id (order in which it was added to the system)
+    pub device: u32,
+    pub address: u8,
+    pub state: State,
+    // the raw 32 bits of the word (`WRD_EMPTY` when there is none)
+    pub word: Word,
+    pub event: ErrMsg,
+    pub avg_delta_t: u128,
+}
+
+impl LogRecord {
+    /// A command written by the BC: the start of a message on the bus.
+    pub fn is_bc_command(&self) -> bool {
+        self.mode == Mode::BC
+            && matches!(self.event, ErrMsg::MsgWrt(_))
+            && self.word.sync() == SYNC_CMD_STS
+    }
+}
+
+/// Queries over logs in time order (`System::logs`, `Device::logs`).
+#[allow(unused)]
+pub trait LogQuery {
+    fn records(&self) -> &[LogRecord];
+
+    fn of_device(&self, id: u32) -> impl Iterator<Item = &LogRecord> {
+        self.records().iter().filter(move |l| l.device == id)
+    }
+
+    fn of_address(&self, address: u8) -> impl Iterator<Item = &LogRecord> {
+        self.records().iter().filter(move |l| l.address == address)
+    }
+
+    /// Records of an `ErrMsg::kind`, e.g. "MsgEntDat".
+    fn of_kind<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a LogRecord> {
+        self.records()
+            .iter()
+            .filter(move |l| l.event.kind() == kind)
+    }
+
+    /// Records from `start` (included) to `end` (excluded).
+    fn window(&self, start: u128, end: u128) -> &[LogRecord] {
+        let records = self.records();
+        let from = records.partition_point(|l| l.time < start);
+        let to = records.partition_point(|l| l.time < end);
+        &records[from..to.max(from)]
+    }
+
+    /// Records grouped by bus message: each group starts with a command
+    /// written by the BC and runs until the next one.
+    fn messages(&self) -> impl Iterator<Item = &[LogRecord]> {
+        let records = self.records();
+        let starts: Vec<usize> = (0..records.len())
+            .filter(|i| records[*i].is_bc_command())
+            .collect();
+        let ends: Vec<usize> = starts
+            .iter()
+            .skip(1)
+            .copied()
+            .chain([records.len()])
+            .collect();
+        starts
+            .into_iter()
+            .zip(ends)
+            .map(move |(s, e)| &records[s..e])
+    }
+}
+
+impl LogQuery for [LogRecord] {
+    fn records(&self) -> &[LogRecord] {
+        self
+    }
+}
+
+impl LogQuery for Vec<LogRecord> {
+    fn records(&self) -> &[LogRecord] {
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct LogHeader {
+    schema_version: u32,
+}
+
+/// Reads what `JsonLinesSink` wrote, checking the schema version.
+#[allow(unused)]
+pub fn read_json_lines(reader: impl BufRead) -> Result<Vec<LogRecord>, String> {
+    let mut lines = reader.lines();
+    let header = lines
+        .next()
+        .ok_or("empty log")?
+        .map_err(|e| e.to_string())?;
+    let header: LogHeader = serde_json::from_str(&header).map_err(|e| e.to_string())?;
+    if header.schema_version != LOG_SCHEMA_VERSION {
+        return Err(format!(
+            "log schema {} (expected {})",
+            header.schema_version, LOG_SCHEMA_VERSION
+        ));
+    }
+    lines
+        .map(|l| serde_json::from_str(&l.map_err(|e| e.to_string())?).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// What gets recorded while running, see `System::set_log_config`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LogConfig {
+    // print each entry as it is logged
+    pub print: bool,
+    // one `<device>.log` per device
+    pub device_logs: bool,
+    // `sys_bus.log`, `sys_bus.flight.log` and the `.dat` of the monitors
+    pub sys_logs: bool,
+    // records go through a `LogStream` to the files and sinks while running,
+    // instead of being written on `join`
+    pub stream: bool,
+    // records the stream holds before the devices wait for the writer
+    pub stream_capacity: usize,
+    // lateness (ns) tolerated by the time-ordered merge of the stream
+    pub reorder_window: u128,
+    // records are also kept in `Device::logs` (and merged in `System::logs`);
+    // without it `timing_violations` and attack checks see no logs
+    pub retain: bool,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            print: false,
+            device_logs: false,
+            sys_logs: true,
+            stream: false,
+            stream_capacity: 4096,
+            reorder_window: 1_000_000,
+            retain: true,
+        }
+    }
+}
+
+/// Entries a sink is given; an empty list lets everything through.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogFilter {
+    // device ids
+    pub devices: Vec<u32>,
+    pub modes: Vec<Mode>,
+    // `ErrMsg::kind`, e.g. "MsgEntCmd"
+    pub kinds: Vec<String>,
+}
+
+#[allow(unused)]
+impl LogFilter {
+    pub fn devices(mut self, devices: impl IntoIterator<Item = u32>) -> Self {
+        self.devices.extend(devices);
+        self
+    }
+
+    pub fn modes(mut self, modes: impl IntoIterator<Item = Mode>) -> Self {
+        self.modes.extend(modes);
+        self
+    }
+
+    pub fn kinds<'a>(mut self, kinds: impl IntoIterator<Item = &'a str>) -> Self {
+        self.kinds.extend(kinds.into_iter().map(|k| k.to_owned()));
+        self
+    }
+
+    /// The same filter without the records of `mode`; `None` when nothing
+    /// would be left.
+    pub fn without_mode(mut self, mode: Mode) -> Option<Self> {
+        if self.modes.is_empty() {
+            self.modes = vec![Mode::RT, Mode::BC, Mode::BM];
+        }
+        self.modes.retain(|m| *m != mode);
+        (!self.modes.is_empty()).then_some(self)
+    }
+
+    pub fn matches(&self, l: &LogRecord) -> bool {
+        (self.devices.is_empty() || self.devices.contains(&l.device))
+            && (self.modes.is_empty() || self.modes.contains(&l.mode))
+            && (self.kinds.is_empty() || self.kinds.contains(&l.event.kind()))
+    }
+}
+
+/// Destination of the merged system logs, fed in time order by
+/// `System::join`.
+pub trait LogSink: Send {
+    fn write(&mut self, l: &LogRecord) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Appends to `path`, creating the directories on the way.
+pub fn open_log(path: &Path) -> io::Result<File> {
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir)?;
+    }
+    OpenOptions::new().append(true).create(true).open(path)
+}
+
+/// The lines of `LogConfig::print`, for the entries that pass the filter.
+#[allow(unused)]
+pub struct StdoutSink;
+
+impl LogSink for StdoutSink {
+    fn write(&mut self, l: &LogRecord) -> io::Result<()> {
+        writeln!(io::stdout(), "{}", format_log(l))
+    }
+}
+
+/// One line per entry, `format_log` by default as in `sys_bus.log`.
+pub struct TextSink {
+    file: BufWriter<File>,
+    format: fn(&LogRecord) -> String,
+}
+
+impl TextSink {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        TextSink::with_format(path, format_log)
+    }
+
+    pub fn with_format(path: &Path, format: fn(&LogRecord) -> String) -> io::Result<Self> {
+        Ok(TextSink {
+            file: BufWriter::new(open_log(path)?),
+            format,
+        })
+    }
+}
+
+impl LogSink for TextSink {
+    fn write(&mut self, l: &LogRecord) -> io::Result<()> {
+        writeln!(self.file, "{}", (self.format)(l))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// One row per entry, with the word split into its common fields.
+pub struct CsvSink {
+    file: BufWriter<File>,
+}
+
+#[allow(unused)]
+impl CsvSink {
+    pub const HEADER: &'static str =
+        "time,mode,device,address,state,word,parity,attk,bus,fault,kind,message,avg_delta_t";
+
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let is_new = !path.exists();
+        let mut file = BufWriter::new(open_log(path)?);
+        if is_new {
+            writeln!(file, "{}", CsvSink::HEADER)?;
+        }
+        Ok(CsvSink { file })
+    }
+}
+
+impl LogSink for CsvSink {
+    fn write(&mut self, l: &LogRecord) -> io::Result<()> {
+        writeln!(
+            self.file,
+            "{},{},{},{},{},{:#x},{},{},{},{},{},\"{}\",{}",
+            l.time,
+            l.mode,
+            l.device,
+            l.address,
+            l.state,
+            l.word.all(),
+            l.word.parity_bit(),
+            l.word.attk(),
+            l.word.bus(),
+            l.word.fault() as u8,
+            l.event.kind(),
+            l.event.value().replace('"', "\"\""),
+            l.avg_delta_t
+        )
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// `LogRecord`s as JSON, one per line, after a `{"schema_version": ..}`
+/// header line; see `read_json_lines`.
+pub struct JsonLinesSink {
+    file: BufWriter<File>,
+}
+
+#[allow(unused)]
+impl JsonLinesSink {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let header = LogHeader {
+            schema_version: LOG_SCHEMA_VERSION,
+        };
+        writeln!(file, "{}", serde_json::to_string(&header)?)?;
+        Ok(JsonLinesSink { file })
+    }
+}
+
+impl LogSink for JsonLinesSink {
+    fn write(&mut self, l: &LogRecord) -> io::Result<()> {
+        writeln!(self.file, "{}", serde_json::to_string(l)?)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Hands the entries to a receiver (`System::subscribe_logs`); entries the
+/// receiver is not ready for are dropped and reported as errors.
+pub struct ChannelSink {
+    pub tx: Sender<LogRecord>,
+}
+
+impl LogSink for ChannelSink {
+    fn write(&mut self, l: &LogRecord) -> io::Result<()> {
+        self.tx
+            .try_send(l.clone())
+            .map_err(|e| io::Error::new(io::ErrorKind::WouldBlock, e.to_string()))
+    }
+}
+
+/// Keeps the entries, e.g. for tests or in-process analysis.
+#[allow(unused)]
+#[derive(Default)]
+pub struct MemorySink {
+    pub logs: Vec<LogRecord>,
+}
+
+impl LogSink for MemorySink {
+    fn write(&mut self, l: &LogRecord) -> io::Result<()> {
+        self.logs.push(l.clone());
+        Ok(())
+    }
+}
+
+/// A sink with the entries it takes; the caller may keep a handle on the
+/// sink (e.g. to read a `MemorySink` back).
+#[derive(Clone)]
+pub struct FilteredSink {
+    pub filter: LogFilter,
+    pub sink: Arc<Mutex<dyn LogSink>>,
+}
+
+impl FilteredSink {
+    pub fn write(&self, l: &LogRecord) -> io::Result<()> {
+        match self.filter.matches(l) {
+            true => self.sink.lock().unwrap().write(l),
+            false => Ok(()),
+        }
+    }
+
+    pub fn write_all(&self, logs: &[LogRecord]) -> io::Result<()> {
+        let mut sink = self.sink.lock().unwrap();
+        for l in logs.iter().filter(|l| self.filter.matches(l)) {
+            sink.write(l)?;
+        }
+        sink.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::{TR, WRD_EMPTY};
+
+    fn entry(time: u128, id: u32, mode: Mode, word: Word, e: ErrMsg) -> LogRecord {
+        LogRecord {
+            time,
+            mode,
+            device: id,
+            address: id as u8,
+            state: State::Idle,
+            word,
+            event: e,
+            avg_delta_t: 0,
+        }
+    }
+
+    fn bus_logs() -> Vec<LogRecord> {
+        let cmd = Word::new_cmd(1, 1, TR::Receive);
+        vec![
+            entry(5, 0, Mode::BC, WRD_EMPTY, ErrMsg::MsgBCReady),
+            entry(10, 0, Mode::BC, cmd, ErrMsg::MsgWrt(1)),
+            entry(12, 1, Mode::RT, cmd, ErrMsg::MsgEntCmdRcv),
+            entry(30, 0, Mode::BC, Word::new_data(7), ErrMsg::MsgWrt(0)),
+            entry(32, 1, Mode::RT, Word::new_data(7), ErrMsg::MsgEntDat),
+            entry(
+                40,
+                0,
+                Mode::BC,
+                Word::new_cmd(2, 0, TR::Transmit),
+                ErrMsg::MsgWrt(0),
+            ),
+            entry(
+                45,
+                2,
+                Mode::RT,
+                WRD_EMPTY,
+                ErrMsg::MsgAttk("a \"b\"".to_owned()),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let logs = bus_logs();
+        let (cmd, dat) = (&logs[2], &logs[4]);
+        assert!(LogFilter::default().matches(cmd));
+        let f = LogFilter::default().devices([1]);
+        assert!(f.matches(cmd) && !f.matches(&logs[1]));
+        let f = LogFilter::default()
+            .modes([Mode::RT])
+            .kinds(["MsgEntCmdRcv"]);
+        assert!(f.matches(cmd) && !f.matches(dat));
+        let f = LogFilter::default().kinds(["MsgWrt", "MsgEntDat"]);
+        assert!(!f.matches(cmd) && f.matches(dat));
+        let f = LogFilter::default().without_mode(Mode::BC).unwrap();
+        assert!(f.matches(cmd) && !f.matches(&logs[0]));
+        assert!(LogFilter::default()
+            .modes([Mode::BM])
+            .without_mode(Mode::BM)
+            .is_none());
+        assert_eq!(ErrMsg::MsgBCTimeout(4).kind(), "MsgBCTimeout");
+    }
+
+    #[test]
+    fn test_log_query() {
+        let logs = bus_logs();
+        assert_eq!(logs.of_device(1).count(), 2);
+        assert_eq!(logs.of_address(2).count(), 1);
+        assert_eq!(logs.of_kind("MsgWrt").count(), 3);
+        let times = |ls: &[LogRecord]| ls.iter().map(|l| l.time).collect::<Vec<u128>>();
+        assert_eq!(times(logs.window(10, 32)), vec![10, 12, 30]);
+        assert!(logs.window(50, 60).is_empty());
+        assert!(logs.window(30, 10).is_empty());
+        let messages: Vec<Vec<u128>> = logs.messages().map(times).collect();
+        assert_eq!(messages, vec![vec![10, 12, 30, 32], vec![40, 45]]);
+        assert_eq!(logs[..3].messages().count(), 1);
+    }
+
+    #[test]
+    fn test_log_sinks() {
+        let logs = bus_logs();
+        let memory = Arc::new(Mutex::new(MemorySink::default()));
+        let sink = FilteredSink {
+            filter: LogFilter::default().devices([1]),
+            sink: memory.clone(),
+        };
+        sink.write_all(&logs).unwrap();
+        assert_eq!(memory.lock().unwrap().logs.len(), 2);
+
+        let dir = std::env::temp_dir().join("sv1dur_test_log_sinks");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("logs.csv");
+        for _ in 0..2 {
+            let mut csv = CsvSink::create(&path).unwrap();
+            csv.write(&logs[6]).unwrap();
+            csv.flush().unwrap();
+        }
+        let s = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = s.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CsvSink::HEADER);
+        assert!(
+            lines[1].ends_with(",MsgAttk,\"a \"\"b\"\"\",0"),
+            "{}",
+            lines[1]
+        );
+        let mut text = TextSink::create(&dir.join("logs.log")).unwrap();
+        text.write(&logs[0]).unwrap();
+        text.flush().unwrap();
+        let s = std::fs::read_to_string(dir.join("logs.log")).unwrap();
+        assert_eq!(s, format!("{}\n", format_log(&logs[0])));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_log_json_lines() {
+        let logs = bus_logs();
+        let path = std::env::temp_dir().join("sv1dur_test_log.jsonl");
+        let mut sink = JsonLinesSink::create(&path).unwrap();
+        for l in &logs {
+            sink.write(l).unwrap();
+        }
+        sink.flush().unwrap();
+        let s = std::fs::read_to_string(&path).unwrap();
+        assert!(s.starts_with(&format!("{{\"schema_version\":{}}}\n", LOG_SCHEMA_VERSION)));
+        assert!(s.contains(r#""event":{"MsgWrt":1}"#), "{}", s);
+        let read = read_json_lines(s.as_bytes()).unwrap();
+        assert_eq!(read.len(), logs.len());
+        for (a, b) in read.iter().zip(&logs) {
+            assert_eq!(format_log(a), format_log(b));
+        }
+        let old = s.replacen(&format!(":{}}}", LOG_SCHEMA_VERSION), ":0}", 1);
+        assert!(read_json_lines(old.as_bytes()).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::sys_bus::frame::FrameEvent;
+use crate::sys_bus::log::{LogRecord, LogSink};
+use crate::sys_bus::retry::RecoveryEvent;
+use crate::sys_bus::{bus_name, AttackType, ErrMsg, Mode, Word, RT_WORD_LOAD_TIME, TR};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+// width of a bucket of the latency histograms (ns)
+pub const LATENCY_BUCKET: u128 = 10_000;
+
+/// Commands sent to a subaddress and the data words they announced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Traffic {
+    pub messages: u64,
+    pub words: u64,
+}
+
+/// Latencies of a kind of message (ns), counted by `LATENCY_BUCKET` wide
+/// buckets named after their lower bound.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct LatencyHistogram {
+    pub count: u64,
+    pub min: u128,
+    pub max: u128,
+    pub mean: u128,
+    pub buckets: BTreeMap<u128, u64>,
+    #[serde(skip)]
+    total: u128,
+}
+
+impl LatencyHistogram {
+    pub fn add(&mut self, latency: u128) {
+        if self.count == 0 || latency < self.min {
+            self.min = latency;
+        }
+        self.max = self.max.max(latency);
+        self.count += 1;
+        self.total += latency;
+        self.mean = self.total / self.count as u128;
+        *self
+            .buckets
+            .entry(latency / LATENCY_BUCKET * LATENCY_BUCKET)
+            .or_default() += 1;
+    }
+}
+
+/// Report of a run, see `System::metrics` (and `sys_bus.metrics.json`).
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Metrics {
+    // from the first record to the last one (ns)
+    pub duration: u128,
+    // words written on the bus, by bus
+    pub words: BTreeMap<char, u64>,
+    // share of the duration each bus carried a word (%)
+    pub bus_utilization: BTreeMap<char, f64>,
+    pub messages: u64,
+    // by RT address, then subaddress
+    pub traffic: BTreeMap<u8, BTreeMap<u8, Traffic>>,
+    // from the first command of a message to the BC being ready for the next
+    // one, by kind of message (timed out ones left out)
+    pub latency: BTreeMap<String, LatencyHistogram>,
+    pub retries: u64,
+    // escalation steps taken and messages given up by the BC
+    pub escalations: u64,
+    pub given_up: u64,
+    pub timeouts: u64,
+    pub parity_errors: u64,
+    // words received corrupted by a collision (by every receiver)
+    pub collisions: u64,
+    // words written with an attack label, by attack
+    pub attack_words: BTreeMap<String, u64>,
+    // minor frames of a frame scheduler that overran
+    pub frame_overruns: u64,
+}
+
+// a message of a BC, from its first command on the bus
+struct Open {
+    start: u128,
+    first: Word,
+    // words the BC wrote for it
+    words: u32,
+    kind: &'static str,
+    timed_out: bool,
+}
+
+fn kind(first: &Word, second: Option<&Word>) -> &'static str {
+    if first.is_mode_cmd() {
+        return "ModeCode";
+    }
+    match (first.tr(), second) {
+        (TR::Receive, Some(_)) => "RT2RT",
+        (TR::Receive, None) => "BC2RT",
+        (TR::Transmit, _) => "RT2BC",
+    }
+}
+
+/// Builds the `Metrics` of a run from the records of all the devices, fed
+/// in time order as a log sink.
+#[derive(Default)]
+pub struct MetricsCollector {
+    metrics: Metrics,
+    first: Option<u128>,
+    // by BC (device id)
+    open: HashMap<u32, Open>,
+}
+
+impl MetricsCollector {
+    pub fn report(&self) -> Metrics {
+        let mut metrics = self.metrics.clone();
+        for (bus, words) in &metrics.words {
+            let busy = (*words as u128 * RT_WORD_LOAD_TIME) as f64;
+            let utilization = match metrics.duration {
+                0 => 0.0,
+                d => 100.0 * busy / d as f64,
+            };
+            metrics.bus_utilization.insert(*bus, utilization);
+        }
+        metrics
+    }
+
+    fn close(&mut self, bc: u32, end: u128) {
+        if let Some(open) = self.open.remove(&bc) {
+            if !open.timed_out {
+                let latency = end - open.start;
+                self.metrics
+                    .latency
+                    .entry(open.kind.to_owned())
+                    .or_default()
+                    .add(latency);
+            }
+        }
+    }
+
+    fn command(&mut self, l: &LogRecord) {
+        let w = l.word;
+        let traffic = self
+            .metrics
+            .traffic
+            .entry(w.address())
+            .or_default()
+            .entry(w.sub_address())
+            .or_default();
+        traffic.messages += 1;
+        traffic.words += w.n_data_words() as u64;
+        // the transmit command right after the receive one of an RT to RT
+        // transfer belongs to the same message
+        if let Some(open) = self.open.get_mut(&l.device) {
+            let rt2rt = open.words == 1 && !open.first.is_mode_cmd();
+            if rt2rt && open.first.tr() == TR::Receive && w.tr() == TR::Transmit {
+                open.kind = kind(&open.first, Some(&w));
+                open.words += 1;
+                return;
+            }
+        }
+        // a new message ends the one before (broadcasts are not waited for)
+        self.close(l.device, l.time);
+        self.metrics.messages += 1;
+        self.open.insert(
+            l.device,
+            Open {
+                start: l.time,
+                first: w,
+                words: 1,
+                kind: kind(&w, None),
+                timed_out: false,
+            },
+        );
+    }
+}
+
+impl LogSink for MetricsCollector {
+    fn write(&mut self, l: &LogRecord) -> io::Result<()> {
+        let first = *self.first.get_or_insert(l.time);
+        self.metrics.duration = self.metrics.duration.max(l.time.saturating_sub(first));
+        match &l.event {
+            ErrMsg::MsgWrt(_) => {
+                *self
+                    .metrics
+                    .words
+                    .entry(bus_name(l.word.bus()))
+                    .or_default() += 1;
+                if l.word.attk() != 0 {
+                    let attack = AttackType::from(l.word.attk() as i32);
+                    *self
+                        .metrics
+                        .attack_words
+                        .entry(format!("{:?}", attack))
+                        .or_default() += 1;
+                }
+                if l.is_bc_command() {
+                    self.command(l);
+                } else if l.mode == Mode::BC {
+                    if let Some(open) = self.open.get_mut(&l.device) {
+                        open.words += 1;
+                    }
+                }
+            }
+            ErrMsg::MsgBCReady => self.close(l.device, l.time),
+            ErrMsg::MsgBCTimeout(_) => {
+                self.metrics.timeouts += 1;
+                if let Some(open) = self.open.get_mut(&l.device) {
+                    open.timed_out = true;
+                }
+            }
+            ErrMsg::MsgBCRetry(_) => self.metrics.retries += 1,
+            ErrMsg::MsgRecovery(RecoveryEvent::Escalate { .. }) => self.metrics.escalations += 1,
+            ErrMsg::MsgRecovery(RecoveryEvent::GiveUp { .. }) => self.metrics.given_up += 1,
+            ErrMsg::MsgFrame(FrameEvent::Overrun { .. }) => self.metrics.frame_overruns += 1,
+            ErrMsg::MsgEntErrPty(_, 0) => self.metrics.parity_errors += 1,
+            ErrMsg::MsgEntErrPty(..) => self.metrics.collisions += 1,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::{State, WRD_EMPTY};
+
+    fn record(time: u128, mode: Mode, word: Word, event: ErrMsg) -> LogRecord {
+        LogRecord {
+            time,
+            mode,
+            device: 0,
+            address: 0,
+            state: State::Idle,
+            word,
+            event,
+            avg_delta_t: 0,
+        }
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut h = LatencyHistogram::default();
+        for latency in [45_000, 52_000, 58_000] {
+            h.add(latency);
+        }
+        assert_eq!((h.count, h.min, h.max, h.mean), (3, 45_000, 58_000, 51_666));
+        assert_eq!(h.buckets, BTreeMap::from([(40_000, 1), (50_000, 2)]));
+    }
+
+    #[test]
+    fn test_metrics_collector() {
+        let mut c = MetricsCollector::default();
+        let mut atk = Word::new_data(1);
+        atk.set_attk(AttackType::AtkFakeStatusReccmd as u32);
+        let records = [
+            record(0, Mode::BC, WRD_EMPTY, ErrMsg::MsgBCReady),
+            // RT 2 to RT 1
+            record(
+                10_000,
+                Mode::BC,
+                Word::new_cmd(1, 1, TR::Receive),
+                ErrMsg::MsgWrt(1),
+            ),
+            record(
+                30_000,
+                Mode::BC,
+                Word::new_cmd(2, 1, TR::Transmit),
+                ErrMsg::MsgWrt(0),
+            ),
+            record(60_000, Mode::RT, Word::new_status(2), ErrMsg::MsgWrt(1)),
+            record(80_000, Mode::RT, Word::new_data(5), ErrMsg::MsgWrt(0)),
+            record(100_000, Mode::RT, atk, ErrMsg::MsgWrt(0)),
+            record(
+                100_000,
+                Mode::RT,
+                atk,
+                ErrMsg::MsgEntErrPty(100_000, -2_000),
+            ),
+            record(150_000, Mode::BC, WRD_EMPTY, ErrMsg::MsgBCReady),
+            // unanswered
+            record(
+                160_000,
+                Mode::BC,
+                Word::new_cmd(3, 0, TR::Transmit),
+                ErrMsg::MsgWrt(0),
+            ),
+            record(190_000, Mode::BC, WRD_EMPTY, ErrMsg::MsgBCTimeout(180_000)),
+            record(200_000, Mode::BC, WRD_EMPTY, ErrMsg::MsgBCReady),
+        ];
+        for l in &records {
+            c.write(l).unwrap();
+        }
+        let m = c.report();
+        assert_eq!(m.duration, 200_000);
+        assert_eq!(m.words[&'A'], 6);
+        assert_eq!(m.bus_utilization[&'A'], 60.0);
+        assert_eq!(m.messages, 2);
+        let sa = crate::sys_bus::SA_DEFAULT;
+        assert_eq!(
+            m.traffic[&1][&sa],
+            Traffic {
+                messages: 1,
+                words: 1
+            }
+        );
+        assert_eq!(
+            m.traffic[&3][&sa],
+            Traffic {
+                messages: 1,
+                words: 0
+            }
+        );
+        assert_eq!(m.latency["RT2RT"].max, 140_000);
+        assert!(!m.latency.contains_key("RT2BC"));
+        assert_eq!((m.timeouts, m.collisions, m.parity_errors), (1, 1, 0));
+        assert_eq!(m.attack_words["AtkFakeStatusReccmd"], 1);
+    }
+}
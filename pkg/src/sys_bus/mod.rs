@@ -1,29 +1,88 @@
 use bitfield::bitfield;
 use chrono::Utc;
-use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender, TryRecvError};
 use spin_sleep;
 use std::collections::VecDeque;
 use std::fmt;
-use std::fs::{create_dir, read_dir, File, OpenOptions};
+use std::fs::{read_dir, File};
 use std::io::prelude::*;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::time::{Duration, Instant};
 pub const WRD_EMPTY: Word = Word { 0: 0 };
 pub const ATK_DEFAULT_DELAYS: u128 = 4_000;
-pub const CONFIG_PRINT_LOGS: bool = false;
-pub const CONFIG_SAVE_DEVICE_LOGS: bool = false;
-pub const CONFIG_SAVE_SYS_LOGS: bool = true;
 pub const BROADCAST_ADDRESS: u8 = 31;
 pub const RT_WORD_LOAD_TIME: u128 = 20_000;
 pub const BC_WARMUP_STEPS: u128 = 20;
+// buses a word can be tagged with (3 bits in `Word::bus`)
+pub const MAX_BUSES: u8 = 8;
+// sync waveforms (first half of each of the 3 bit times, bit 0 first):
+// command/status is positive then negative, data the inverse.
+pub const SYNC_CMD_STS: u8 = 0b011;
+pub const SYNC_DATA: u8 = 0b100;
+// sub-addresses 0 and 31 mark a mode command
+pub const SA_MODE_CODE: u8 = 0;
+pub const SA_MODE_CODE_ALT: u8 = 31;
+// sub-address used by the plain data transfers
+pub const SA_DEFAULT: u8 = 1;
+// a status word only answers a command seen less than this long before
+pub const STATUS_WINDOW: u128 = 100_000;
+// bus timing (ns): RT response time, BC no-response timeout and the dead
+// time between two messages
+pub const RESPONSE_TIME_MIN: u128 = 4_000;
+pub const RESPONSE_TIME_MAX: u128 = 12_000;
+pub const NO_RESPONSE_TIMEOUT: u128 = 14_000;
+pub const MIN_INTERMESSAGE_GAP: u128 = 4_000;
+// added per word to the timeout of a message in a real-time run, where the
+// host schedules the device threads (ns)
+pub const THREAD_ALLOWANCE: u128 = 50_000;
+// a step gives up after this long without a match (ms), and a pause waits
+// this long at most for the device threads to stop
+pub const STEP_LIMIT_MS: u64 = 1_000;
+pub const PARK_TIMEOUT: Duration = Duration::from_secs(1);
+use blocks::BlockEvent;
+use builder::{DeviceSpec, SystemBuilder};
+use control::{BreakHit, Breakpoint, ExecControl};
+use fault::{FaultInjector, FaultLabel};
+use frame::FrameEvent;
+use illegal::{IllegalCmd, Illegalization};
 use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use inject::{InFlight, Injected, Injector, MessageHandle};
+use log::{
+    open_log, ChannelSink, FilteredSink, LogConfig, LogFilter, LogRecord, LogSink, TextSink,
+};
+use metrics::{Metrics, MetricsCollector};
 use num_format::{Locale, ToFormattedString};
+use phy::{PhyError, Waveform};
+use power::{Port, Ports, Power, PowerEvent, PowerLine};
+use retry::{Escalation, Failure, Recovery, RecoveryEvent, RetryBus, RetryPolicy};
+use serde::{Deserialize, Serialize};
+use sim::VirtualBus;
+use snapshot::{DeviceCounters, DeviceSnapshot, SnapshotSlot, SNAPSHOT_INTERVAL};
+use stream::{LogSender, LogStream, LogStreamStats};
+use timing::{Timing, TimingProfile, TimingProfiles};
+
+pub mod blocks;
+pub mod builder;
+pub mod control;
+pub mod fault;
+pub mod frame;
+pub mod illegal;
+pub mod inject;
+pub mod log;
+pub mod metrics;
+pub mod phy;
+pub mod power;
+pub mod retry;
+pub mod sim;
+pub mod snapshot;
+pub mod stream;
+pub mod timing;
 
 #[allow(unused)]
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ErrMsg {
     MsgEmpt,
     // show write queue size
@@ -33,10 +92,15 @@ pub enum ErrMsg {
     MsgStaChg(usize),
     MsgEntWrdRec,
     MsgEntErrPty(i128, i128),
+    // physical layer: no transition in the given bit time, bad sync
+    MsgEntErrMan(u8),
+    MsgEntErrSync,
     MsgEntCmd,
     MsgEntCmdRcv,
     MsgEntCmdTrx,
     MsgEntCmdMcx,
+    // command illegalized for this RT
+    MsgEntCmdIlg,
     MsgEntDat,
     MsgEntSte,
     // dropped status word
@@ -48,9 +112,45 @@ pub enum ErrMsg {
     // Flight system level log
     MsgFlight(String),
     MsgBCTimeout(u128),
+    // BC re-issues the last message on the given bus
+    MsgBCRetry(u8),
+    // bus timing rule broken
+    MsgTiming(TimingViolation),
+    // word lost on its way to the given device (fault injection)
+    MsgFaultDrop(u32),
+    // plugged in, switched off or on, booted or failed
+    MsgPower(PowerEvent),
+    // the device takes or gives up the control of the bus
+    MsgBusCtrl(BusControl),
+    // minor frame start or overrun of a frame scheduler
+    MsgFrame(FrameEvent),
+    // what the BC does about a failed message
+    MsgRecovery(RecoveryEvent),
+    // interrupt, halt or frame overrun of a command block program
+    MsgBlock(BlockEvent),
+}
+
+/// How control of the bus changes hands (`ErrMsg::MsgBusCtrl`).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BusControl {
+    // a backup BC after the bus stayed silent
+    Takeover,
+    // offered by the BC (dynamic bus control mode code)
+    Accepted,
+    // the BC handed it over
+    Released,
 }
 
 impl ErrMsg {
+    /// Name of the variant, e.g. "MsgBCTimeout" (see `LogFilter`).
+    pub fn kind(&self) -> String {
+        let name = format!("{:?}", self);
+        match name.find('(') {
+            Some(i) => name[..i].to_owned(),
+            None => name,
+        }
+    }
+
     fn value(&self) -> String {
         use ErrMsg::*;
         match self {
@@ -66,10 +166,13 @@ impl ErrMsg {
                 lag
             )
             .to_string(),
+            MsgEntErrMan(bit) => format!("Manchester Error({})", bit),
+            MsgEntErrSync => "Sync Error".to_owned(),
             MsgEntCmd => "CMD Received".to_owned(),
             MsgEntCmdRcv => "CMD RCV Received".to_owned(),
             MsgEntCmdTrx => "CMD TRX Received".to_owned(),
             MsgEntCmdMcx => "CMD MCX Received".to_owned(),
+            MsgEntCmdIlg => "CMD Illegal Received".to_owned(),
             MsgEntDat => "Data Received".to_owned(),
             MsgEntSte => "Status Received".to_owned(),
             MsgEntSteDrop => "Status Dropped".to_owned(),
@@ -79,30 +182,95 @@ impl ErrMsg {
             MsgBMLog => "BM".to_owned(),
             MsgBCTimeout(timeout) => format!("BC Timeout {}", timeout).to_string(),
             MsgFlight(msg) => msg.to_owned(),
+            MsgBCRetry(bus) => format!("BC Retry on Bus {}", bus_name(*bus)),
+            MsgTiming(v) => format!("Timing {}", v),
+            MsgFaultDrop(to) => format!("Fault: lost for {:02}", to),
+            MsgPower(PowerEvent::On(boot)) => format!("Power On (boot {})", boot),
+            MsgPower(e) => format!("Power {:?}", e),
+            MsgBusCtrl(c) => format!("Bus Control {:?}", c),
+            MsgFrame(FrameEvent::Start(minor)) => format!("Minor Frame {}", minor),
+            MsgFrame(FrameEvent::Overrun {
+                minor,
+                late,
+                skipped,
+            }) => format!("Frame {} Overrun by {} ({} dropped)", minor, late, skipped),
+            MsgRecovery(RecoveryEvent::Retry {
+                rt,
+                cause,
+                attempt,
+                bus,
+                delay,
+            }) => format!(
+                "{:?} RT{:02}: Retry {} on Bus {} in {}",
+                cause,
+                rt,
+                attempt,
+                bus_name(*bus),
+                delay
+            ),
+            MsgRecovery(RecoveryEvent::Escalate { rt, cause, step }) => {
+                format!("{:?} RT{:02}: {:?}", cause, rt, step)
+            }
+            MsgRecovery(RecoveryEvent::GiveUp { rt, cause }) => {
+                format!("{:?} RT{:02}: Give Up", cause, rt)
+            }
+            MsgRecovery(RecoveryEvent::Skipped(rt)) => format!("RT{:02} Failed: Skipped", rt),
+            MsgBlock(BlockEvent::FrameOverrun(block, late)) => {
+                format!("Block {} Frame Overrun by {}", block, late)
+            }
+            MsgBlock(e) => format!("Block {:?}", e),
         }
     }
 }
 
-pub fn format_log(l: &(u128, Mode, u32, u8, State, Word, ErrMsg, u128)) -> String {
+pub fn format_log(l: &LogRecord) -> String {
     return format!(
         "{:>12} {}{:02}-{:02} {:^22} {} {:^22} avg_d_t:{}",
-        l.0.to_formatted_string(&Locale::en),
-        l.1,
-        l.2,
-        l.3,
-        l.4.to_string(),
-        l.5,
-        l.6.value(),
-        l.7
+        l.time.to_formatted_string(&Locale::en),
+        l.mode,
+        l.device,
+        l.address,
+        l.state.to_string(),
+        l.word,
+        l.event.value(),
+        l.avg_delta_t
+    );
+}
+
+pub fn format_log_bm(l: &LogRecord) -> String {
+    // return format!("{} {:?}", l.time, l.word,);
+    return format!(
+        "{},{},{}, {}, {}, {}",
+        l.time,
+        l.word.all(),
+        l.word.parity_bit(),
+        l.word.attk(),
+        l.word.bus(),
+        l.word.fault() as u8
     );
 }
 
-pub fn format_log_bm(l: &(u128, Mode, u32, u8, State, Word, ErrMsg, u128)) -> String {
-    // return format!("{} {:?}", l.0, l.5,);
-    return format!("{},{},{}, {}", l.0, l.5.all(), l.5.parity_bit(), l.5.attk());
+/// Lines of `sys_bus.flight.log`.
+pub fn format_log_flight(l: &LogRecord) -> String {
+    match &l.event {
+        ErrMsg::MsgFlight(msg) => format!("{} {}", l.time, msg),
+        e => format!("{} {}", l.time, e.value()),
+    }
+}
+
+// `join` merges the records of the BC and the RTs only; the sinks fed from
+// the stream take the same, the monitors go to their own files
+fn merged_only(sink: FilteredSink) -> Option<FilteredSink> {
+    let filter = sink.filter.without_mode(Mode::BM)?;
+    Some(FilteredSink { filter, ..sink })
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// Bus 0 is bus A, 1 is bus B, ...
+pub fn bus_name(bus: u8) -> char {
+    (b'A' + bus) as char
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[repr(u8)]
 pub enum TR {
     Receive = 0,
@@ -120,7 +288,8 @@ impl From<u8> for TR {
 }
 
 bitfield! {
-    #[derive(Copy, Clone)]
+    #[derive(Copy, Clone, Serialize, Deserialize)]
+    #[serde(transparent)]
     pub struct Word(u32);
     impl Debug;
     u8;
@@ -139,11 +308,8 @@ bitfield! {
     pub parity_bit, set_parity_bit: 19, 19;
     // for command:
     pub into TR, tr, set_tr: 8, 8;
-    // it was 13, 9 but since we use instrumentation bit
-    // we have kept reduce the sub-address space to 15.
-    pub sub_address, set_sub_address: 13, 10;
-    pub mode, set_mode: 13, 11;
-    // pub mode, set_mode: 13, 9;
+    // sub-address 0 or 31 is a mode command (word count is then the mode code)
+    pub sub_address, set_sub_address: 13, 9;
     pub dword_count, set_dword_count: 18, 14;
     pub mode_code, set_mode_code: 18, 14;
     // for data word
@@ -152,18 +318,29 @@ bitfield! {
     pub data, set_data: 18, 3;
     // additional (attack type):
     pub attk, set_attk: 24,21;
+    // additional (bus the word was transmitted on):
+    pub u8, bus, set_bus: 27,25;
+    // additional (fault that damaged the word on its way):
+    pub u8, into FaultLabel, fault, set_fault: 30,28;
 }
 
 impl fmt::Display for Word {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "w:{:#027b}[{:02}]", self.0, self.attk()) // We need an extra 2 bits for '0b' on top of the number of bits we're printing
+        write!(
+            f,
+            "w:{:#027b}[{:02}]{}{}",
+            self.0 & 0x1ff_ffff,
+            self.attk(),
+            bus_name(self.bus()),
+            self.fault().name()
+        ) // We need an extra 2 bits for '0b' on top of the number of bits we're printing
     }
 }
 
 impl Word {
     pub fn new_status(src_addr: u8) -> Word {
         let mut w = Word { 0: 0 };
-        w.set_sync(1);
+        w.set_sync(SYNC_CMD_STS);
         w.set_address(src_addr);
         w.calculate_parity_bit();
         return w;
@@ -171,6 +348,7 @@ impl Word {
 
     pub fn new_data(val: u32) -> Word {
         let mut w = Word { 0: 0 };
+        w.set_sync(SYNC_DATA);
         w.set_data(val as u32);
         w.calculate_parity_bit();
         return w;
@@ -178,36 +356,154 @@ impl Word {
 
     pub fn new_cmd(addr: u8, dword_count: u8, tr: TR) -> Word {
         let mut w = Word { 0: 0 };
-        w.set_sync(1);
+        w.set_sync(SYNC_CMD_STS);
         w.set_tr(tr as u8); // 1: transmit, 0: receive
         w.set_address(addr); // the RT address which is five bits long
                              // address 11111 (31) is reserved for broadcast protocol
-
+        w.set_sub_address(SA_DEFAULT);
         w.set_dword_count(dword_count); // the quantity of data that will follow after the command
-        w.set_mode(2);
-        w.set_instrumentation_bit(1);
         w.calculate_parity_bit();
         return w;
     }
+
+    pub fn new_mode_cmd(addr: u8, mode_code: u8, tr: TR) -> Word {
+        let mut w = Word::new_cmd(addr, mode_code, tr);
+        w.set_sub_address(SA_MODE_CODE);
+        w.calculate_parity_bit();
+        w
+    }
+
+    pub fn is_mode_cmd(&self) -> bool {
+        self.sub_address() == SA_MODE_CODE || self.sub_address() == SA_MODE_CODE_ALT
+    }
+
+    /// Whether the addressed RT answers with a status word (not on broadcast
+    /// and not for the simulator's own mode codes 30 and 31).
+    pub fn expects_status(&self) -> bool {
+        self.address() != BROADCAST_ADDRESS && !(self.is_mode_cmd() && self.mode_code() >= 30)
+    }
+
+    /// Status of an RT not sending its data (busy, or the command was in
+    /// error); a status never carries the instrumentation bit, so a command
+    /// word read as status does not count.
+    pub fn is_no_data_status(&self) -> bool {
+        (self.busy_bit() == 1 || self.message_errorbit() == 1) && self.instrumentation_bit() == 0
+    }
+
+    /// Mode codes an RT accepts: defined ones with the right T/R bit (the
+    /// ones transmitting data cannot be broadcast), plus 30 and 31.
+    pub fn is_legal_mode_cmd(&self) -> bool {
+        match self.mode_code() {
+            0 | 2 | 16 | 18 | 19 => {
+                self.tr() == TR::Transmit && self.address() != BROADCAST_ADDRESS
+            }
+            1 | 3..=8 => self.tr() == TR::Transmit,
+            17 | 20 | 21 => self.tr() == TR::Receive,
+            30 | 31 => true,
+            _ => false,
+        }
+    }
+
+    /// Data words following a command (mode codes 16 to 21 carry one).
+    pub fn n_data_words(&self) -> u8 {
+        if self.is_mode_cmd() {
+            (16..=21).contains(&self.mode_code()) as u8
+        } else {
+            self.dword_count()
+        }
+    }
     #[allow(unused)]
     pub fn calculate_parity_bit(&mut self) {
         /*
         This code will calculate and apply the parity bit.  This will not affect other bits in the bitfield.
+        Odd parity covers the 16 information bits (3..18), not the sync.
         */
-        // let mask = u32::MAX - 1; //MAX-1 leaves the paritybit empty (I think this assumption may be wrong.  I think this is actually the sync bits)
-        let mask = u32::MAX - 2u32.pow(19); // This will likely be the code we need.  It keeps all of the bits outside of the "19" bit.
-        let int = self.all() & mask;
-        let parity_odd = true;
+        let int = self.data();
+        // the total number of ones (parity bit included) has to be odd
         if int.count_ones() % 2 == 0 {
-            self.set_parity_bit(!parity_odd as u8);
+            self.set_parity_bit(1);
         } else {
-            self.set_parity_bit(parity_odd as u8);
+            self.set_parity_bit(0);
         }
     }
 }
 
+/// Time source shared by every device of a system. `Real` follows the host
+/// clock (one thread per device), `Virtual` is a simulated nanosecond clock
+/// advanced by the discrete-event engine in [`sim`].
 #[allow(unused)]
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
+pub enum Clock {
+    Real(Arc<RealTime>),
+    Virtual(Arc<AtomicU64>),
+}
+
+/// Host time since the start of a system, less the time it spent paused.
+#[derive(Debug)]
+pub struct RealTime {
+    start: Instant,
+    // total time paused (ns)
+    paused: AtomicU64,
+    // time the clock stopped at, plus one (0: running)
+    frozen: AtomicU64,
+}
+
+impl Clock {
+    pub fn real() -> Self {
+        Clock::Real(Arc::new(RealTime {
+            start: Instant::now(),
+            paused: AtomicU64::new(0),
+            frozen: AtomicU64::new(0),
+        }))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match self {
+            Clock::Real(rt) => match rt.frozen.load(Ordering::SeqCst) {
+                0 => rt.start.elapsed() - Duration::from_nanos(rt.paused.load(Ordering::SeqCst)),
+                frozen => Duration::from_nanos(frozen - 1),
+            },
+            Clock::Virtual(now) => Duration::from_nanos(now.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Blocks the calling device for `ns`. On a virtual clock the time is
+    /// consumed by advancing the simulated clock instead of spinning.
+    pub fn sleep_ns(&self, ns: u64) {
+        match self {
+            Clock::Real(..) => spin_sleep::SpinSleeper::new(100_000).sleep_ns(ns),
+            Clock::Virtual(now) => {
+                now.fetch_add(ns, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Stops the time (a real clock; virtual time only moves with the
+    /// engine anyway).
+    pub fn freeze(&self) {
+        if let Clock::Real(rt) = self {
+            let now = self.elapsed().as_nanos() as u64;
+            let _ = rt
+                .frozen
+                .compare_exchange(0, now + 1, Ordering::SeqCst, Ordering::SeqCst);
+        }
+    }
+
+    /// Lets the time go on from where `freeze` stopped it.
+    pub fn thaw(&self) {
+        if let Clock::Real(rt) = self {
+            let frozen = rt.frozen.load(Ordering::SeqCst);
+            if frozen > 0 {
+                let real = rt.start.elapsed().as_nanos() as u64;
+                rt.paused.store(real - (frozen - 1), Ordering::SeqCst);
+                rt.frozen.store(0, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Mode {
     RT,
     BC,
@@ -221,7 +517,7 @@ impl fmt::Display for Mode {
 }
 
 #[allow(unused)]
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum State {
     Idle,
     Off,
@@ -252,6 +548,9 @@ pub trait EventHandler: Send {
     fn on_err_parity(&mut self, d: &mut Device, w: &mut Word, recv_time: i128, lag: i128) {
         self.default_on_err_parity(d, w, recv_time, lag);
     }
+    fn on_err_phy(&mut self, d: &mut Device, w: &mut Word, err: PhyError, recv_time: i128) {
+        self.default_on_err_phy(d, w, err, recv_time);
+    }
     fn on_cmd(&mut self, d: &mut Device, w: &mut Word) {
         self.default_on_cmd(d, w);
     }
@@ -280,10 +579,11 @@ pub trait EventHandler: Send {
     }
     fn default_on_bc_timeout(&mut self, d: &mut Device) {
         d.log(WRD_EMPTY, ErrMsg::MsgBCTimeout(d.timeout));
-        let mut reset_cmd = Word::new_cmd(BROADCAST_ADDRESS, 0, TR::Receive);
-        reset_cmd.set_mode(1);
-        reset_cmd.set_mode_code(30);
+        let reset_cmd = Word::new_mode_cmd(BROADCAST_ADDRESS, 30, TR::Receive);
         d.write(reset_cmd);
+        if let Some(rt) = d.awaited_rt() {
+            d.recover(rt, Failure::Timeout);
+        }
     }
     fn default_on_data_write(&mut self, d: &mut Device, dword_count: u8) {
         for i in 0..dword_count {
@@ -300,6 +600,20 @@ pub trait EventHandler: Send {
     fn default_on_err_parity(&mut self, d: &mut Device, w: &mut Word, recv_time: i128, lag: i128) {
         // log error tba
         d.log(*w, ErrMsg::MsgEntErrPty(recv_time, lag));
+        if d.mode == Mode::RT && d.state == State::AwtData && !d.fake {
+            // the message being received is lost
+            d.flag_message_error(false);
+        }
+    }
+    fn default_on_err_phy(&mut self, d: &mut Device, w: &mut Word, err: PhyError, recv_time: i128) {
+        match err {
+            PhyError::Parity => return self.on_err_parity(d, w, recv_time, 0),
+            PhyError::Manchester(bit) => d.log(*w, ErrMsg::MsgEntErrMan(bit)),
+            PhyError::Sync => d.log(*w, ErrMsg::MsgEntErrSync),
+        }
+        if d.mode == Mode::RT && d.state == State::AwtData && !d.fake {
+            d.flag_message_error(false);
+        }
     }
     fn default_on_cmd(&mut self, d: &mut Device, w: &mut Word) {
         // cmds are only for RT, matching self's address
@@ -308,15 +622,42 @@ pub trait EventHandler: Send {
             // 31 is the boardcast address
             if destination == d.address || destination == BROADCAST_ADDRESS {
                 // d.log(*w, ErrMsg::MsgEntCmd);
+                // respond on the bus the command came from
+                d.bus = w.bus();
                 d.number_of_current_cmd += 1;
                 // if there was previously a command word recieved
                 // cancel previous command (clear state)
                 if d.number_of_current_cmd >= 2 {
+                    // data missing (unless it is the transmit half of a rt2rt)
+                    if d.state == State::AwtData
+                        && !(w.tr() == TR::Transmit && d.dword_count == 0)
+                        && !d.fake
+                    {
+                        d.flag_message_error(false);
+                    }
                     // cancel whatever going to write
                     d.write_queue.clear();
                     d.reset_all_stateful();
                 }
-                if w.tr() == TR::Receive && (w.mode() == 1 || w.mode() == 0) {
+                if !(w.is_mode_cmd() && (w.mode_code() == 2 || w.mode_code() == 18)) {
+                    // (what "transmit last command" reports)
+                    d.last_cmd = *w;
+                }
+                if !d.fake && d.illegal_cmds.iter().any(|r| r.matches(w)) {
+                    d.log(*w, ErrMsg::MsgEntCmdIlg);
+                    let brdcst = destination == BROADCAST_ADDRESS;
+                    if w.tr() == TR::Receive && w.n_data_words() > 0 {
+                        // the data is discarded, status after the last word
+                        d.set_state(State::AwtData);
+                        d.dword_count = 0;
+                        d.dword_count_expected = w.n_data_words();
+                        d.in_brdcst = brdcst;
+                        d.in_illegal = true;
+                    } else {
+                        d.flag_message_error(!brdcst);
+                        d.reset_all_stateful();
+                    }
+                } else if w.is_mode_cmd() {
                     // shutdown etc mode change command
                     self.on_cmd_mcx(d, w);
                 } else {
@@ -339,10 +680,16 @@ pub trait EventHandler: Send {
     fn default_on_cmd_trx(&mut self, d: &mut Device, w: &mut Word) {
         // may be triggered after cmd
         d.log(*w, ErrMsg::MsgEntCmdTrx);
-        if !d.fake {
+        if !d.fake && w.address() == BROADCAST_ADDRESS {
+            // illegal, nobody answers a broadcast transmit command
+            d.flag_message_error(false);
+        } else if !d.fake {
             d.set_state(State::BusyTrx);
-            d.write(Word::new_status(d.address));
-            self.on_data_write(d, w.dword_count());
+            d.write_status(d.status_word());
+            // a busy RT only answers with its status
+            if !d.busy {
+                self.on_data_write(d, w.dword_count());
+            }
             // for i in 0..w.dword_count() {
             //     d.write(Word::new_data((i + 1) as u32));
             // }
@@ -366,19 +713,95 @@ pub trait EventHandler: Send {
             // may be triggered after cmd
             if !d.fake {
                 // actual operation not triggerred for attackers
+                let brdcst = w.address() == BROADCAST_ADDRESS;
+                if !w.is_legal_mode_cmd() {
+                    d.flag_message_error(!brdcst);
+                    return;
+                }
                 // mode code match for command:
                 match w.mode_code() {
+                    0 if !brdcst => {
+                        // dynamic bus control (accepted if the RT can be a BC,
+                        // it takes control once the status is out)
+                        let mut sts = d.status_word();
+                        sts.set_dynamic_bus_control_accpt_bit(d.bc_capable as u8);
+                        d.write_status(sts);
+                        d.control_accepted = d.bc_capable;
+                    }
+                    1 => {
+                        // synchronize (without data word)
+                        d.sync_time = d.clock.elapsed().as_nanos();
+                        d.respond_status(brdcst);
+                    }
+                    2 if !brdcst => {
+                        // transmit status word (the last one, unchanged)
+                        d.write(d.last_status);
+                    }
+                    3 => {
+                        // initiate self test (bit 0: terminal, bit 1: subsystem fault)
+                        d.bit_word = d.terminal_flag as u32 | (d.subsystem_flag as u32) << 1;
+                        d.respond_status(brdcst);
+                    }
                     4 => {
-                        // Mode code for TX shutdown
+                        if d.n_buses > 1 {
+                            // Mode code for TX shutdown (of the other buses)
+                            for bus in 0..d.n_buses {
+                                if bus != w.bus() {
+                                    d.tx_shutdown[bus as usize] = true;
+                                }
+                            }
+                            d.respond_status(brdcst);
+                        } else {
+                            // single bus: the RT goes off the bus
+                            d.reset_all_stateful();
+                            d.set_state(State::Off);
+                        }
+                    }
+                    5 => {
+                        // override transmitter shutdown
+                        d.tx_shutdown.fill(false);
+                        d.respond_status(brdcst);
+                    }
+                    6 => {
+                        // inhibit terminal flag bit
+                        d.terminal_flag_inhibit = true;
+                        d.respond_status(brdcst);
+                    }
+                    7 => {
+                        // override inhibit terminal flag bit
+                        d.terminal_flag_inhibit = false;
+                        d.respond_status(brdcst);
+                    }
+                    8 => {
+                        // reset remote terminal
+                        d.write_queue.clear();
                         d.reset_all_stateful();
-                        d.set_state(State::Off);
+                        d.tx_shutdown.fill(false);
+                        d.terminal_flag_inhibit = false;
+                        d.respond_status(brdcst);
                     }
-                    17 => {
-                        // synchronization
-                        // ccmd indicating that the next data word
+                    16 if !brdcst => {
+                        // transmit vector word (the service request is answered)
+                        d.write_status(d.status_word());
+                        d.write(Word::new_data(d.vector_word));
+                        d.service_request = false;
+                    }
+                    18 if !brdcst => {
+                        // transmit last command (status unchanged)
+                        d.write(d.last_status);
+                        d.write(Word::new_data(d.last_cmd.data()));
+                    }
+                    19 if !brdcst => {
+                        // transmit BIT word
+                        d.write_status(d.status_word());
+                        d.write(Word::new_data(d.bit_word));
+                    }
+                    17 | 20 | 21 => {
+                        // synchronization / (override) selected transmitter
+                        // shutdown: ccmd indicating that the next data word
                         // is related to the current command
-                        // (in this case, the clock to be synced)
-                        d.ccmd = 1;
+                        d.ccmd = w.mode_code();
+                        d.in_brdcst = brdcst;
                         d.set_state(State::AwtData);
                     }
                     30 => {
@@ -401,11 +824,40 @@ pub trait EventHandler: Send {
     fn default_on_dat(&mut self, d: &mut Device, w: &mut Word) {
         if d.state == State::AwtData {
             d.log(*w, ErrMsg::MsgEntDat);
-            if d.ccmd == 1 {
-                // TBA:  synchronize clock to data
-                // (clock is u128 but data is not u16..)
-                // maybe set the microscecond component of the clock
+            if d.in_illegal {
+                d.dword_count += 1;
+                if d.dword_count == d.dword_count_expected {
+                    let brdcst = d.in_brdcst;
+                    d.flag_message_error(!brdcst);
+                    d.reset_all_stateful();
+                }
+            } else if d.ccmd != 0 {
+                // the data word of a mode command
+                match d.ccmd {
+                    17 => {
+                        // synchronize: keep the time of the sync and its data
+                        // (clock is u128 but data is not u16..)
+                        d.sync_time = d.clock.elapsed().as_nanos();
+                        d.sync_word = w.data();
+                    }
+                    20 => {
+                        // selected transmitter shutdown
+                        if let Some(tx) = d.tx_shutdown.get_mut(w.data() as usize) {
+                            *tx = true;
+                        }
+                    }
+                    21 => {
+                        // override selected transmitter shutdown
+                        if let Some(tx) = d.tx_shutdown.get_mut(w.data() as usize) {
+                            *tx = false;
+                        }
+                    }
+                    _ => {}
+                }
                 d.ccmd = 0;
+                let brdcst = d.in_brdcst;
+                d.reset_all_stateful();
+                d.respond_status(brdcst);
             } else {
                 if d.dword_count < d.dword_count_expected {
                     d.memory.push(w.data());
@@ -415,8 +867,9 @@ pub trait EventHandler: Send {
                     d.set_state(State::BusyTrx);
                     if d.mode != Mode::BC {
                         // only real RT will responding status message
+                        // (none to a broadcast)
                         if !d.fake {
-                            d.write(Word::new_status(d.address));
+                            d.respond_status(d.in_brdcst);
                         }
                     }
                     self.on_memory_ready(d);
@@ -428,6 +881,7 @@ pub trait EventHandler: Send {
     fn default_on_sts(&mut self, d: &mut Device, w: &mut Word) {
         if d.mode == Mode::BC {
             d.log(*w, ErrMsg::MsgEntSte);
+            let failed = w.message_errorbit() == 1 && d.awaited_rt() == Some(w.address());
             // check delta_t
             let mut check_delta_t = false;
             match d.state {
@@ -435,7 +889,12 @@ pub trait EventHandler: Send {
                     //(transmitter confirmation)
                     // rt2bc
                     if src == w.address() {
-                        d.set_state(State::AwtData)
+                        if w.is_no_data_status() {
+                            // no data follows
+                            d.reset_all_stateful();
+                        } else {
+                            d.set_state(State::AwtData)
+                        }
                     }
                     check_delta_t = true;
                 }
@@ -444,12 +903,18 @@ pub trait EventHandler: Send {
                     // bc2rt
                     if dest == w.address() {
                         d.reset_all_stateful();
+                        let offered = matches!(d.last_msg, Some(Message::ModeCode(_, 0, _)));
+                        if offered && w.dynamic_bus_control_accpt_bit() == 1 {
+                            d.release_control();
+                        }
                     }
                 }
                 State::AwtStsTrxR2R(src, dest) => {
                     //(transmitter confirmation)
                     // rt2rt
-                    if src == w.address() {
+                    if src == w.address() && w.is_no_data_status() {
+                        d.reset_all_stateful();
+                    } else if src == w.address() {
                         d.set_state(State::AwtStsRcvR2R(src, dest));
                         d.delta_t_start = d.clock.elapsed().as_nanos();
                     }
@@ -473,6 +938,12 @@ pub trait EventHandler: Send {
                 d.delta_t_avg += delta_t;
                 d.delta_t_count += 1;
             }
+            if failed {
+                d.note_failure(Failure::MessageError);
+                if d.message_policy().on_message_error {
+                    d.recover(w.address(), Failure::MessageError);
+                }
+            }
         }
     }
     fn verify(&mut self, _: &System) -> bool {
@@ -481,6 +952,10 @@ pub trait EventHandler: Send {
     fn get_attk_type(&self) -> AttackType {
         AttackType::Benign
     }
+    // the only bus this handler listens and talks on (`None` for all buses)
+    fn bus(&self) -> Option<u8> {
+        None
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -497,9 +972,9 @@ pub struct Device {
     pub id: u32,
     pub dword_count: u8,
     pub dword_count_expected: u8,
-    pub clock: Instant,
-    pub logs: Vec<(u128, Mode, u32, u8, State, Word, ErrMsg, u128)>,
-    pub transmitters: Vec<Sender<Word>>,
+    pub clock: Clock,
+    pub logs: Vec<LogRecord>,
+    pub ports: Ports,
     pub read_queue: Vec<(u128, Word, bool)>,
     pub write_queue: VecDeque<(u128, Word)>,
     pub write_delays: u128,
@@ -509,14 +984,417 @@ pub struct Device {
     pub delta_t_count: u128,
     pub timeout: u128,
     pub timeout_times: u128,
+    // the BC handler has nothing to send before this time (ns)
+    pub bc_next_at: u128,
     pub time_write_ready: u128,
+    // number of redundant buses the device is attached to
+    pub n_buses: u8,
+    // bus used for transmission
+    pub bus: u8,
+    // last message issued by a BC (for retries)
+    pub last_msg: Option<Message>,
+    // subaddress of the commands of a BC (`SA_DEFAULT` unless its handler
+    // sets it for a message)
+    pub sub_address: u8,
+    // message to re-issue as soon as the BC is ready
+    pub retry: Option<Message>,
+    // times the current message has been re-issued
+    pub retries: u8,
+    // what a BC does about failed messages, and where it is at
+    pub retry_policy: RetryPolicy,
+    pub recovery: Recovery,
+    // one-shot messages for whichever device is the BC, and the one it is
+    // busy with
+    pub injected: Receiver<Injected>,
+    pub in_flight: Option<InFlight>,
+    // protocol phase of each bus (tells status words from commands)
+    pub bus_phase: Vec<BusPhase>,
+    // reaction of a BC to the bus timing
+    pub timing_policy: TimingPolicy,
+    // line levels of the word being received on each bus (physical layer on)
+    pub phy_rx: Option<Vec<Option<Waveform>>>,
+    // faults of the bus, shared by all devices
+    pub faults: Option<Arc<Mutex<FaultInjector>>>,
+    // last status word sent and last command received (mode codes 2 and 18)
+    pub last_status: Word,
+    pub last_cmd: Word,
+    // built-in test result (mode code 19) and vector word (mode code 16)
+    pub bit_word: u32,
+    pub vector_word: u32,
+    // conditions reported in the status word (see `StatusFlag`)
+    pub service_request: bool,
+    pub busy: bool,
+    pub subsystem_flag: bool,
+    pub terminal_flag: bool,
+    pub terminal_flag_inhibit: bool,
+    // transmitter of each bus shut down by mode code
+    pub tx_shutdown: Vec<bool>,
+    // accepts dynamic bus control
+    pub bc_capable: bool,
+    // backup BC: takes control after this much bus silence (ns, 0: never)
+    pub takeover_after: u128,
+    // last word seen on the bus (`None`: silence not counted yet)
+    pub bus_heard: Option<u128>,
+    // dynamic bus control accepted, control is taken once the status is out
+    pub control_accepted: bool,
+    // time and data word of the last synchronize mode code
+    pub sync_time: u128,
+    pub sync_word: u32,
+    // illegalization table, and whether the data being received is for an
+    // illegal command (discarded)
+    pub illegal_cmds: Vec<IllegalCmd>,
+    pub in_illegal: bool,
+    // what to record (copied from the system)
+    pub log_config: LogConfig,
+    pub log_stream: Option<LogSender>,
+    // delays sampled before each write (`None`: `write_delays`)
+    pub timing_profile: Option<Timing>,
+    pub counters: DeviceCounters,
+    // where `publish` leaves the snapshots read by the system, and when
+    pub snapshot: SnapshotSlot,
+    pub published_at: u128,
+    // breakpoints and pause of the system
+    pub control: Arc<ExecControl>,
+    // switched by the system, and as last applied by `sync_power`
+    pub power: Arc<PowerLine>,
+    pub power_seen: Power,
+}
+
+/// Conditions of an RT (or its subsystem) reported in its status words. The
+/// message error bit is not one of them: it is set per message by the RT.
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatusFlag {
+    // the subsystem needs the BC's attention (answered by mode code 16)
+    ServiceRequest,
+    // the subsystem is updating, data cannot be moved
+    Busy,
+    // subsystem fault
+    SubsystemFlag,
+    // terminal fault
+    TerminalFlag,
+}
+
+/// Protocol phase of one bus as followed by a terminal: the responses the
+/// last command asked for. A command/status-sync word is a status word only
+/// when the bus is waiting for the response of that terminal next.
+#[derive(Clone, Debug, Default)]
+pub struct BusPhase {
+    // pending responses in order: status word of `Some(address)` or a data word
+    expected: VecDeque<Option<u8>>,
+    // the last word was a receive command (a rt2rt transmit command may follow)
+    rcv_cmd: bool,
+    // time the last word was seen
+    last_seen: u128,
+    // start of the latest word and end of the last complete one
+    last_start: u128,
+    last_end: u128,
+    // the missing response has been reported
+    silence_reported: bool,
+}
+
+impl BusPhase {
+    /// A word starts on the bus (still loading).
+    pub fn start(&mut self, start: u128) {
+        self.last_start = start;
+        self.silence_reported = false;
+    }
+
+    /// Checks the dead time before a word that started at `start` against the
+    /// end of the previous one, then takes it as the last word of the bus.
+    pub fn check_timing(&mut self, w: &Word, status: bool, start: u128) -> Option<TimingViolation> {
+        let gap = start.saturating_sub(self.last_end);
+        let new_msg = w.sync() == SYNC_CMD_STS && !status && self.expected.is_empty();
+        let first = self.last_end == 0;
+        self.start(start);
+        self.last_end = start + RT_WORD_LOAD_TIME;
+        if first {
+            None
+        } else if status && !(RESPONSE_TIME_MIN..=RESPONSE_TIME_MAX).contains(&gap) {
+            Some(TimingViolation::ResponseTime {
+                rt: w.address(),
+                gap,
+            })
+        } else if new_msg && gap < MIN_INTERMESSAGE_GAP {
+            Some(TimingViolation::IntermessageGap { gap })
+        } else {
+            None
+        }
+    }
+
+    /// When the terminal that should respond next is late, if nothing has
+    /// started on the bus since the last word.
+    pub fn no_response_deadline(&self) -> Option<(u8, u128)> {
+        match self.expected.front() {
+            Some(Some(rt)) if self.last_start < self.last_end && !self.silence_reported => {
+                Some((*rt, self.last_end + NO_RESPONSE_TIMEOUT))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_status(&self, w: &Word, now: u128) -> bool {
+        w.sync() == SYNC_CMD_STS
+            && now <= self.last_seen + STATUS_WINDOW
+            && self.expected.front() == Some(&Some(w.address()))
+    }
+
+    pub fn advance(&mut self, w: &Word, status: bool, now: u128) {
+        self.last_seen = now;
+        if w.sync() != SYNC_CMD_STS {
+            if self.expected.front() == Some(&None) {
+                self.expected.pop_front();
+            }
+            self.rcv_cmd = false;
+        } else if status {
+            if self.expected.front() == Some(&Some(w.address())) {
+                self.expected.pop_front();
+            }
+            self.rcv_cmd = false;
+        } else {
+            // a new command
+            let responder = match w.expects_status() {
+                true => Some(w.address()),
+                false => None,
+            };
+            let data = std::iter::repeat_n(None, w.n_data_words() as usize);
+            let mut expected = VecDeque::new();
+            if w.tr() == TR::Transmit {
+                expected.extend(responder.map(Some));
+                expected.extend(data);
+                if self.rcv_cmd {
+                    // rt2rt: the receiver answers after the transmitter's data
+                    expected.extend(self.expected.iter().filter(|e| e.is_some()));
+                }
+            } else {
+                expected.extend(data);
+                expected.extend(responder.map(Some));
+            }
+            self.expected = expected;
+            self.rcv_cmd = w.tr() == TR::Receive && !w.is_mode_cmd();
+        }
+    }
+}
+
+/// Bus timing rule broken, seen by a BC or a BM (dead times in ns).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TimingViolation {
+    // status outside of the 4 to 12µs response time
+    ResponseTime { rt: u8, gap: u128 },
+    // command less than 4µs after the previous message
+    IntermessageGap { gap: u128 },
+    // nothing from the RT 14µs after the last word
+    NoResponse { rt: u8 },
+}
+
+impl fmt::Display for TimingViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimingViolation::ResponseTime { rt, gap } => write!(f, "Response RT{} {}ns", rt, gap),
+            TimingViolation::IntermessageGap { gap } => write!(f, "Gap {}ns", gap),
+            TimingViolation::NoResponse { rt } => write!(f, "No Response RT{}", rt),
+        }
+    }
+}
+
+/// How a BC reacts to the bus timing.
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimingPolicy {
+    // violations are only logged
+    Record,
+    // also ends the message as a timeout (and retries) 14µs after the last
+    // word when the RT has not started to respond
+    Enforce,
+}
+
+/// A BC-initiated transfer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+    // destination, data
+    BC2RT(u8, Vec<u32>),
+    // source, word count
+    RT2BC(u8, u8),
+    // source, destination, word count
+    RT2RT(u8, u8, u8),
+    // destination, mode code, data word
+    ModeCode(u8, u8, Option<u32>),
 }
 
 impl Device {
-    pub fn write(&mut self, mut val: Word) {
+    pub fn write(&mut self, val: Word) {
+        self.write_on_bus(self.bus, val);
+    }
+
+    /// Status word reflecting the current condition of the terminal.
+    pub fn status_word(&self) -> Word {
+        let mut sts = Word::new_status(self.address);
+        sts.set_service_request_bit(self.service_request as u8);
+        sts.set_busy_bit(self.busy as u8);
+        sts.set_subsystem_flag_bit(self.subsystem_flag as u8);
+        if self.terminal_flag && !self.terminal_flag_inhibit {
+            sts.set_terminal_flag_bit(1);
+        }
+        sts.calculate_parity_bit();
+        sts
+    }
+
+    /// Raises (or clears) a condition reported in the status words.
+    #[allow(unused)]
+    pub fn set_flag(&mut self, flag: StatusFlag, on: bool) {
+        match flag {
+            StatusFlag::ServiceRequest => self.service_request = on,
+            StatusFlag::Busy => self.busy = on,
+            StatusFlag::SubsystemFlag => self.subsystem_flag = on,
+            StatusFlag::TerminalFlag => self.terminal_flag = on,
+        }
+    }
+
+    /// Marks the current message as erroneous: the status (with the message
+    /// error bit) is sent for an illegal command, otherwise only kept for
+    /// "transmit status word".
+    pub fn flag_message_error(&mut self, respond: bool) {
+        let mut sts = self.status_word();
+        sts.set_message_errorbit(1);
+        if respond {
+            self.write_status(sts);
+        } else {
+            sts.calculate_parity_bit();
+            self.last_status = sts;
+        }
+    }
+
+    /// Sends a status word, remembered for "transmit status word".
+    pub fn write_status(&mut self, mut sts: Word) {
+        sts.calculate_parity_bit();
+        self.last_status = sts;
+        self.write(sts);
+    }
+
+    /// Answers a message with the current status. A broadcast is not answered:
+    /// the status is only kept, with the broadcast received bit set, for the
+    /// "transmit status word" and "transmit last command" mode codes.
+    pub fn respond_status(&mut self, brdcst: bool) {
+        if brdcst {
+            let mut sts = self.status_word();
+            sts.set_brdcst_received_bit(1);
+            sts.calculate_parity_bit();
+            self.last_status = sts;
+        } else {
+            self.write_status(self.status_word());
+        }
+    }
+
+    /// Follows a word received from the bus (that started at `start`); true
+    /// when a command/status-sync word is a status word. The BC takes every
+    /// such word as a response.
+    pub fn observe(&mut self, w: &Word, start: u128) -> bool {
+        let now = self.clock.elapsed().as_nanos();
+        let phase = &mut self.bus_phase[w.bus() as usize];
+        let status = phase.is_status(w, now);
+        let violation = phase.check_timing(w, status, start);
+        phase.advance(w, status, now);
+        self.log_timing(violation);
+        status || (self.mode == Mode::BC && w.sync() == SYNC_CMD_STS)
+    }
+
+    /// Delay before writing the word just taken from the write queue, with
+    /// `wq` words left behind it.
+    pub fn write_delay(&mut self, wq: usize) -> u128 {
+        match &mut self.timing_profile {
+            Some(timing) => timing.next_delay(wq),
+            None => self.write_delays,
+        }
+    }
+
+    /// Logs a word this device has put on the bus and follows it; a terminal
+    /// knows whether it sent a command or its own status.
+    pub fn transmitted(&mut self, w: Word, wq: usize) {
+        self.log(w, ErrMsg::MsgWrt(wq));
+        let now = self.clock.elapsed().as_nanos();
+        self.bus_heard = Some(now);
+        let own_status = self.mode == Mode::RT && !self.fake && w.address() == self.address;
+        let phase = &mut self.bus_phase[w.bus() as usize];
+        let status = match self.mode {
+            Mode::BC => false,
+            _ => own_status || phase.is_status(&w, now),
+        };
+        let violation = phase.check_timing(&w, status, now);
+        phase.advance(&w, status, now);
+        self.log_timing(violation);
+    }
+
+    fn log_timing(&mut self, violation: Option<TimingViolation>) {
+        // only the BC and the monitors check the bus timing
+        if let Some(v) = violation {
+            if self.mode != Mode::RT {
+                self.log(WRD_EMPTY, ErrMsg::MsgTiming(v));
+            }
+        }
+    }
+
+    /// Time at which a BC gives up on the response to its message.
+    pub fn no_response_deadline(&self) -> Option<u128> {
+        match (self.mode, self.state) {
+            (Mode::BC, State::Idle) => None,
+            (Mode::BC, _) if self.write_queue.is_empty() => self
+                .bus_phase
+                .get(self.bus as usize)
+                .and_then(|p| p.no_response_deadline())
+                .map(|(_, deadline)| deadline),
+            _ => None,
+        }
+    }
+
+    /// Reports a response missing at `current`; true if the message has to
+    /// be given up as per the timing policy.
+    pub fn check_no_response(&mut self, current: u128) -> bool {
+        if self.no_response_deadline().is_none_or(|t| current <= t) {
+            return false;
+        }
+        let phase = &mut self.bus_phase[self.bus as usize];
+        let (rt, _) = phase.no_response_deadline().unwrap();
+        phase.silence_reported = true;
+        self.log_timing(Some(TimingViolation::NoResponse { rt }));
+        self.timing_policy == TimingPolicy::Enforce
+    }
+
+    /// Timing violations logged so far.
+    #[allow(unused)]
+    pub fn timing_violations(&self) -> Vec<TimingViolation> {
+        self.logs
+            .iter()
+            .filter_map(|l| match l.event {
+                ErrMsg::MsgTiming(v) => Some(v),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The word this device sends as device `to` gets it, after the faults
+    /// of the bus; none when it is lost.
+    pub fn deliver(&mut self, to: u32, w: Word) -> Option<Word> {
+        let faults = match &self.faults {
+            Some(faults) => Arc::clone(faults),
+            None => return Some(w),
+        };
+        let now = self.clock.elapsed().as_nanos();
+        let delivered = faults.lock().unwrap().deliver(self.id, to, w, now);
+        if delivered.is_none() {
+            self.log(w, ErrMsg::MsgFaultDrop(to));
+        }
+        delivered
+    }
+
+    pub fn write_on_bus(&mut self, bus: u8, mut val: Word) {
+        if self.tx_shutdown.get(bus as usize) == Some(&true) {
+            // transmitter shut down by mode code
+            return;
+        }
         if self.fake {
             val.set_attk(self.atk_type as u32);
         }
+        val.set_bus(bus);
         self.write_queue.push_back((0, val));
     }
 
@@ -534,6 +1412,7 @@ impl Device {
         self.dword_count = 0;
         self.dword_count_expected = 0;
         self.in_brdcst = false;
+        self.in_illegal = false;
         self.timeout = 0;
         // return the previous number of cmd
         // in case it shouldn't be reseted.
@@ -545,89 +1424,487 @@ impl Device {
         if self.delta_t_count > 0 {
             avg_delta_t = self.delta_t_avg / self.delta_t_count;
         }
-        let l = (
-            self.clock.elapsed().as_nanos(),
-            self.mode,
-            self.id,
-            self.address,
-            self.state,
+        let l = LogRecord {
+            time: self.clock.elapsed().as_nanos(),
+            mode: self.mode,
+            device: self.id,
+            address: self.address,
+            state: self.state,
             word,
-            e,
+            event: e,
             avg_delta_t,
-        );
-        if CONFIG_PRINT_LOGS {
+        };
+        if self.log_config.print {
             println!("{}", format_log(&l));
         }
-        self.logs.push(l);
+        self.counters.count(&l.event);
+        if let Some(in_flight) = &mut self.in_flight {
+            match l.event {
+                ErrMsg::MsgEntSte => in_flight.transfer.statuses.push(word),
+                ErrMsg::MsgEntDat => in_flight.transfer.data.push(word.data()),
+                _ => {}
+            }
+        }
+        if self.control.check(&l) {
+            self.clock.freeze();
+        }
+        if let Some(stream) = &self.log_stream {
+            stream.send(l.clone());
+        }
+        if self.log_config.retain {
+            self.logs.push(l);
+        }
     }
 
-    pub fn log_merge(&self, log_list: &mut Vec<(u128, Mode, u32, u8, State, Word, ErrMsg, u128)>) {
+    pub fn log_merge(&self, log_list: &mut Vec<LogRecord>) {
         for l in &self.logs {
             log_list.push(l.clone());
         }
     }
 
-    pub fn set_state(&mut self, state: State) {
-        if state != self.state {
-            self.state = state;
-            self.log(WRD_EMPTY, ErrMsg::MsgStaChg(self.write_queue.len()));
+    pub fn save_logs(&self, log_file: &Path, log_file_bm: &Path) {
+        if self.log_stream.is_some() {
+            // already written by the stream
+            return;
+        }
+        if self.log_config.device_logs {
+            println!(
+                "{} writing {} logs to {} ",
+                self,
+                self.logs.len(),
+                log_file.to_str().unwrap()
+            );
+            let mut file = open_log(log_file).unwrap();
+            for l in &self.logs {
+                writeln!(file, "{}", format_log(&l)).unwrap();
+            }
+            println!("{} Done flushing logs", self);
+        }
+        // for bus monitor
+        if self.log_config.sys_logs && self.mode == Mode::BM {
+            println!(
+                "{} writing {} logs to {} ",
+                self,
+                self.logs.len(),
+                log_file_bm.to_str().unwrap()
+            );
+            let mut file = open_log(log_file_bm).unwrap();
+            for l in &self.logs {
+                writeln!(file, "{}", format_log_bm(&l)).unwrap();
+            }
+            println!("{} Done flushing logs", self);
         }
     }
 
-    pub fn act_bc2rt(&mut self, dest: u8, data: &Vec<u32>) {
-        self.set_state(State::BusyTrx);
-        self.write(Word::new_cmd(dest, data.len() as u8, TR::Receive));
-        for d in data {
-            self.write(Word::new_data(*d));
+    /// Applies the switching of its power line since the last call; false
+    /// while the device is unpowered (off, booting or failed).
+    pub fn sync_power(&mut self) -> bool {
+        self.power.finish_boot(self.clock.elapsed().as_nanos());
+        let power = self.power.power();
+        if power == self.power_seen {
+            return power == Power::On;
         }
-        self.set_state(State::AwtStsRcvB2R(dest));
-        self.delta_t_start = self.clock.elapsed().as_nanos();
-        // 12_000 is the max allowed RT write delays.
-        // put 20_000 to include the queue transmission time.
-        self.timeout = self.clock.elapsed().as_nanos()
-            + (RT_WORD_LOAD_TIME + self.write_delays + 50_000) * (data.len() as u128 + 2);
+        self.power_seen = power;
+        let event = match power {
+            Power::On => PowerEvent::Booted,
+            Power::Off => PowerEvent::Off,
+            Power::Failed => PowerEvent::Failed,
+            Power::Booting => {
+                let now = self.clock.elapsed().as_nanos();
+                PowerEvent::On(self.power.boot_until().unwrap_or(now).saturating_sub(now))
+            }
+        };
+        self.log(WRD_EMPTY, ErrMsg::MsgPower(event));
+        if power == Power::On {
+            self.set_state(State::Idle);
+        } else if self.state != State::Off {
+            // everything in progress is lost
+            self.write_queue.clear();
+            self.read_queue.clear();
+            self.retry = None;
+            self.recovery.pending = None;
+            self.in_flight = None;
+            self.last_msg = None;
+            self.bus_phase.fill(BusPhase::default());
+            self.tx_shutdown.fill(false);
+            self.bus_heard = None;
+            self.control_accepted = false;
+            self.reset_all_stateful();
+            self.set_state(State::Off);
+        }
+        power == Power::On
     }
-    pub fn act_rt2bc(&mut self, src: u8, dword_count: u8) {
-        self.set_state(State::BusyTrx);
-        self.write(Word::new_cmd(src, dword_count, TR::Transmit));
-        // expecting to recieve dword_count number of words
-        self.dword_count_expected = dword_count;
-        self.set_state(State::AwtStsTrxR2B(src));
-        self.delta_t_start = self.clock.elapsed().as_nanos();
-        self.timeout = self.clock.elapsed().as_nanos()
-            + (RT_WORD_LOAD_TIME + self.write_delays + 50_000) * (dword_count as u128 + 2);
+
+    /// A backup BC takes control of the bus once its status accepting it is
+    /// out, or after `takeover_after` ns of bus silence; true when it does.
+    pub fn check_takeover(&mut self, now: u128) -> bool {
+        if self.mode != Mode::RT || self.fake || !self.bc_capable {
+            return false;
+        }
+        if self.control_accepted && self.write_queue.is_empty() {
+            self.take_control(BusControl::Accepted);
+            return true;
+        }
+        if self.takeover_after == 0 {
+            return false;
+        }
+        // silence counts from the first check
+        let heard = *self.bus_heard.get_or_insert(now);
+        if now > heard + self.takeover_after {
+            self.take_control(BusControl::Takeover);
+            return true;
+        }
+        false
     }
-    pub fn act_rt2rt(&mut self, src: u8, dst: u8, dword_count: u8) {
-        self.set_state(State::BusyTrx);
-        self.write(Word::new_cmd(dst, dword_count, TR::Receive));
-        self.write(Word::new_cmd(src, dword_count, TR::Transmit));
-        // expecting to recieve dword_count number of words
-        self.set_state(State::AwtStsTrxR2R(src, dst));
-        self.delta_t_start = self.clock.elapsed().as_nanos();
-        self.timeout = self.clock.elapsed().as_nanos()
-            + (RT_WORD_LOAD_TIME + self.write_delays + 50_000) * (dword_count as u128 + 4);
+
+    /// When `check_takeover` can succeed next (`None`: not before a word).
+    pub fn takeover_deadline(&self) -> Option<u128> {
+        if self.mode != Mode::RT || self.fake || !self.bc_capable {
+            None
+        } else if self.control_accepted {
+            self.write_queue.is_empty().then_some(0)
+        } else if self.takeover_after == 0 {
+            None
+        } else {
+            Some(
+                self.bus_heard
+                    .map_or(0, |heard| heard + self.takeover_after + 1),
+            )
+        }
     }
-}
 
-impl fmt::Display for Device {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}{}-{}", self.mode, self.address, self.state)
+    fn take_control(&mut self, how: BusControl) {
+        self.control_accepted = false;
+        self.mode = Mode::BC;
+        self.log(WRD_EMPTY, ErrMsg::MsgBusCtrl(how));
+        self.reset_all_stateful();
     }
-}
 
-pub struct System {
-    pub n_devices: u32,
+    /// Hands the bus over (the RT offered it accepted); the BC goes on as an
+    /// RT that can take it back.
+    pub fn release_control(&mut self) {
+        self.mode = Mode::RT;
+        self.bc_capable = true;
+        self.retry = None;
+        self.recovery.pending = None;
+        self.in_flight = None;
+        self.last_msg = None;
+        self.bus_heard = None;
+        self.log(WRD_EMPTY, ErrMsg::MsgBusCtrl(BusControl::Released));
+    }
+
+    /// Makes the current state visible to `System::snapshot`.
+    pub fn publish(&mut self, now: u128) {
+        self.published_at = now;
+        *self.snapshot.lock().unwrap() = Some(DeviceSnapshot::of(self, now));
+    }
+
+    pub fn set_state(&mut self, state: State) {
+        if state != self.state {
+            self.state = state;
+            self.log(WRD_EMPTY, ErrMsg::MsgStaChg(self.write_queue.len()));
+        }
+    }
+
+    pub fn act(&mut self, msg: &Message) {
+        if let Some(rt) = self.failed_rt(msg) {
+            self.log(WRD_EMPTY, ErrMsg::MsgRecovery(RecoveryEvent::Skipped(rt)));
+            return;
+        }
+        match msg {
+            Message::BC2RT(dest, data) => self.act_bc2rt(*dest, data),
+            Message::RT2BC(src, dword_count) => self.act_rt2bc(*src, *dword_count),
+            Message::RT2RT(src, dst, dword_count) => self.act_rt2rt(*src, *dst, *dword_count),
+            Message::ModeCode(dest, mode_code, data) => {
+                self.act_mode_code(*dest, *mode_code, *data)
+            }
+        }
+    }
+    /// Sends a message recovered from as per `policy` rather than the
+    /// BC's `retry_policy`.
+    #[allow(unused)]
+    pub fn act_with_policy(&mut self, msg: &Message, policy: RetryPolicy) {
+        self.recovery.policy = Some(policy);
+        self.act(msg);
+    }
+    /// Sends a message injected from outside; its outcome is reported once
+    /// the BC is done with it (retries included).
+    pub fn act_injected(&mut self, injected: Injected) {
+        // on the default subaddress, not the last scheduled one
+        self.sub_address = SA_DEFAULT;
+        let skipped = self.failed_rt(&injected.msg).is_some();
+        self.act(&injected.msg);
+        if !skipped {
+            let now = self.clock.elapsed().as_nanos();
+            self.in_flight = Some(InFlight::new(injected.done, now));
+        }
+    }
+
+    // the current attempt of an injected message failed
+    fn note_failure(&mut self, cause: Failure) {
+        if let Some(in_flight) = &mut self.in_flight {
+            in_flight.failure = Some(cause);
+        }
+    }
+
+    /// Switches to the next redundant bus and schedules the last message to
+    /// be sent again there once the current (failed) one has been cleared.
+    pub fn retry_on_alternate_bus(&mut self) {
+        self.bus = (self.bus + 1) % self.n_buses;
+        self.retry = self.last_msg.clone();
+    }
+
+    /// Retry policy of the message in progress.
+    pub fn message_policy(&self) -> &RetryPolicy {
+        self.recovery.policy.as_ref().unwrap_or(&self.retry_policy)
+    }
+
+    /// RT the BC is waiting on for the current message.
+    pub fn awaited_rt(&self) -> Option<u8> {
+        match self.state {
+            State::AwtStsRcvB2R(rt)
+            | State::AwtStsTrxR2B(rt)
+            | State::AwtStsTrxR2R(rt, _)
+            | State::AwtStsRcvR2R(_, rt) => Some(rt),
+            // the data of the transmitter of the message in progress
+            State::AwtData => match self.last_msg {
+                Some(Message::RT2BC(rt, _))
+                | Some(Message::RT2RT(rt, _, _))
+                | Some(Message::ModeCode(rt, _, _)) => Some(rt),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // an RT marked failed the message involves
+    fn failed_rt(&self, msg: &Message) -> Option<u8> {
+        let rts = match msg {
+            Message::BC2RT(rt, _) | Message::RT2BC(rt, _) | Message::ModeCode(rt, _, _) => {
+                vec![*rt]
+            }
+            Message::RT2RT(src, dst, _) => vec![*src, *dst],
+        };
+        rts.into_iter().find(|rt| self.recovery.failed.contains(rt))
+    }
+
+    /// Decides what to do about the current message failing at `rt`: retry
+    /// it as per the policy, then go through the escalation steps, one more
+    /// each time the previous one fails too. Every decision is logged.
+    pub fn recover(&mut self, rt: u8, cause: Failure) {
+        let Some(msg) = self.last_msg.clone() else {
+            return;
+        };
+        let policy = self.message_policy().clone();
+        let next_step = match self.recovery.step {
+            Some(step) => step + 1,
+            None if self.retries < policy.retries_on(self.n_buses) => {
+                match policy.bus {
+                    RetryBus::Alternate => self.retry_on_alternate_bus(),
+                    RetryBus::Same => self.retry = Some(msg),
+                }
+                self.bc_next_at = self.clock.elapsed().as_nanos() + policy.delay;
+                let event = RecoveryEvent::Retry {
+                    rt,
+                    cause,
+                    attempt: self.retries + 1,
+                    bus: self.bus,
+                    delay: policy.delay,
+                };
+                self.log(WRD_EMPTY, ErrMsg::MsgRecovery(event));
+                return;
+            }
+            None => 0,
+        };
+        let Some(step) = policy.escalation.get(next_step).copied() else {
+            self.recovery.step = None;
+            self.log(
+                WRD_EMPTY,
+                ErrMsg::MsgRecovery(RecoveryEvent::GiveUp { rt, cause }),
+            );
+            return;
+        };
+        self.recovery.step = Some(next_step);
+        self.log(
+            WRD_EMPTY,
+            ErrMsg::MsgRecovery(RecoveryEvent::Escalate { rt, cause, step }),
+        );
+        match step {
+            Escalation::StatusPoll => {
+                self.recovery.pending = Some(Message::ModeCode(rt, 2, None));
+            }
+            Escalation::ResetRT => {
+                self.recovery.pending = Some(Message::ModeCode(rt, 8, None));
+            }
+            Escalation::MarkFailed => {
+                self.recovery.step = None;
+                self.recovery.failed.insert(rt);
+            }
+        }
+    }
+    /// Sends a mode command; `data` goes with the receive ones (17, 20, 21),
+    /// the response data word (16, 18, 19) lands in `memory`.
+    pub fn act_mode_code(&mut self, dest: u8, mode_code: u8, data: Option<u32>) {
+        self.last_msg = Some(Message::ModeCode(dest, mode_code, data));
+        self.set_state(State::BusyTrx);
+        let tr = match (mode_code, data) {
+            (17 | 20 | 21, _) | (_, Some(_)) => TR::Receive,
+            _ => TR::Transmit,
+        };
+        let cmd = Word::new_mode_cmd(dest, mode_code, tr);
+        self.write(cmd);
+        if let Some(data) = data {
+            self.write(Word::new_data(data));
+        }
+        if !cmd.expects_status() {
+            self.reset_all_stateful();
+            return;
+        }
+        let answers_data = cmd.n_data_words() == 1 && cmd.tr() == TR::Transmit;
+        if answers_data {
+            self.dword_count_expected = 1;
+            self.set_state(State::AwtStsTrxR2B(dest));
+        } else {
+            self.set_state(State::AwtStsRcvB2R(dest));
+        }
+        self.delta_t_start = self.clock.elapsed().as_nanos();
+        self.timeout = self.message_deadline(1, 1 + answers_data as u128);
+    }
+    // end of a message answered by `answered` words in `responses` responses:
+    // a word time (at the pace of the BC) per word still in the write queue,
+    // this message's and any queued before it, and per word answered, and
+    // `NO_RESPONSE_TIMEOUT` for each response to start
+    fn message_deadline(&self, responses: u128, answered: u128) -> u128 {
+        let words = self.write_queue.len() as u128 + answered;
+        let mut budget =
+            words * (RT_WORD_LOAD_TIME + self.write_delays) + responses * NO_RESPONSE_TIMEOUT;
+        if let Clock::Real(..) = self.clock {
+            budget += words * THREAD_ALLOWANCE;
+        }
+        // the queue starts once the word still on the bus has ended
+        let now = self.clock.elapsed().as_nanos();
+        now.max(self.bus_phase[self.bus as usize].last_end) + budget
+    }
+    // command word to the current subaddress
+    fn new_cmd(&self, addr: u8, dword_count: u8, tr: TR) -> Word {
+        let mut w = Word::new_cmd(addr, dword_count, tr);
+        w.set_sub_address(self.sub_address);
+        w.calculate_parity_bit();
+        w
+    }
+    /// Sends data to every RT at once; nobody answers.
+    pub fn act_broadcast_bc2rt(&mut self, data: &Vec<u32>) {
+        self.last_msg = Some(Message::BC2RT(BROADCAST_ADDRESS, data.clone()));
+        self.set_state(State::BusyTrx);
+        self.write(self.new_cmd(BROADCAST_ADDRESS, data.len() as u8, TR::Receive));
+        for d in data {
+            self.write(Word::new_data(*d));
+        }
+        self.reset_all_stateful();
+    }
+    /// `src` transmits to every other RT; only its status comes back, the BC
+    /// then listens to the data like the receivers.
+    pub fn act_rt2broadcast(&mut self, src: u8, dword_count: u8) {
+        self.last_msg = Some(Message::RT2RT(src, BROADCAST_ADDRESS, dword_count));
+        self.set_state(State::BusyTrx);
+        self.write(self.new_cmd(BROADCAST_ADDRESS, dword_count, TR::Receive));
+        self.write(self.new_cmd(src, dword_count, TR::Transmit));
+        self.dword_count_expected = dword_count;
+        self.set_state(State::AwtStsTrxR2B(src));
+        self.delta_t_start = self.clock.elapsed().as_nanos();
+        self.timeout = self.message_deadline(1, 1 + dword_count as u128);
+    }
+    pub fn act_bc2rt(&mut self, dest: u8, data: &Vec<u32>) {
+        if dest == BROADCAST_ADDRESS {
+            return self.act_broadcast_bc2rt(data);
+        }
+        self.last_msg = Some(Message::BC2RT(dest, data.clone()));
+        self.set_state(State::BusyTrx);
+        self.write(self.new_cmd(dest, data.len() as u8, TR::Receive));
+        for d in data {
+            self.write(Word::new_data(*d));
+        }
+        self.set_state(State::AwtStsRcvB2R(dest));
+        self.delta_t_start = self.clock.elapsed().as_nanos();
+        self.timeout = self.message_deadline(1, 1);
+    }
+    pub fn act_rt2bc(&mut self, src: u8, dword_count: u8) {
+        self.last_msg = Some(Message::RT2BC(src, dword_count));
+        self.set_state(State::BusyTrx);
+        self.write(self.new_cmd(src, dword_count, TR::Transmit));
+        // expecting to recieve dword_count number of words
+        self.dword_count_expected = dword_count;
+        self.set_state(State::AwtStsTrxR2B(src));
+        self.delta_t_start = self.clock.elapsed().as_nanos();
+        self.timeout = self.message_deadline(1, 1 + dword_count as u128);
+    }
+    pub fn act_rt2rt(&mut self, src: u8, dst: u8, dword_count: u8) {
+        if dst == BROADCAST_ADDRESS {
+            return self.act_rt2broadcast(src, dword_count);
+        }
+        self.last_msg = Some(Message::RT2RT(src, dst, dword_count));
+        self.set_state(State::BusyTrx);
+        self.write(self.new_cmd(dst, dword_count, TR::Receive));
+        self.write(self.new_cmd(src, dword_count, TR::Transmit));
+        // expecting to recieve dword_count number of words
+        self.set_state(State::AwtStsTrxR2R(src, dst));
+        self.delta_t_start = self.clock.elapsed().as_nanos();
+        // the status of the transmitter, its data, and the status of the
+        // receiver
+        self.timeout = self.message_deadline(2, 2 + dword_count as u128);
+    }
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}-{}", self.mode, self.address, self.state)
+    }
+}
+
+pub struct System {
+    pub n_devices: u32,
     pub max_devices: u32,
     pub transmitters: Vec<Sender<Word>>,
     pub receivers: Vec<Receiver<Word>>,
-    pub clock: Instant,
+    // transmitters and power of the devices, shared with them
+    pub ports: Ports,
+    pub clock: Clock,
     pub go: Arc<AtomicBool>,
     pub exit: Arc<AtomicBool>,
+    // pause, single steps and breakpoints
+    pub control: Arc<ExecControl>,
     pub handlers: Option<Vec<thread::JoinHandle<u32>>>,
     pub devices: Vec<Arc<Mutex<Device>>>,
-    pub logs: Vec<(u128, Mode, u32, u8, State, Word, ErrMsg, u128)>,
+    // latest snapshot of each device
+    pub snapshots: Vec<SnapshotSlot>,
+    pub logs: Vec<LogRecord>,
     pub home_dir: String,
     pub write_delays: u128,
+    // number of redundant buses (1: single bus, 2: bus A / bus B)
+    pub n_buses: u8,
+    // illegalization tables handed to the RTs
+    pub illegalization: Illegalization,
+    // reaction of the BC to the bus timing
+    pub timing_policy: TimingPolicy,
+    // recovery of the BC from failed messages
+    pub retry_policy: RetryPolicy,
+    // channel of the messages injected into the running BC
+    pub injector: (Injector, Receiver<Injected>),
+    // words go through the bit-level physical layer
+    pub phy: bool,
+    // injected faults between transmitters and receivers
+    pub faults: Option<Arc<Mutex<FaultInjector>>>,
+    // write timing of the devices, by id
+    pub timing_profiles: TimingProfiles,
+    // log files to write and extra sinks fed by `join`
+    pub log_config: LogConfig,
+    pub log_sinks: Vec<FilteredSink>,
+    // started by the first `run_d` when `log_config.stream` is on
+    pub log_stream: Option<LogStream>,
+    // fed the records like the sinks
+    pub metrics: Arc<Mutex<MetricsCollector>>,
+    // discrete-event engine (virtual time); `None` runs one thread per device
+    pub sim: Option<VirtualBus>,
 }
 
 impl System {
@@ -636,42 +1913,371 @@ impl System {
         return System::new_with_name(max_devices, write_delays, home_dir);
     }
     pub fn new_with_name(max_devices: u32, write_delays: u128, home_dir: String) -> Self {
-        let clock = Instant::now();
-
-        if CONFIG_SAVE_DEVICE_LOGS || CONFIG_SAVE_SYS_LOGS {
-            let _ = create_dir(PathBuf::from(&home_dir));
-        }
+        let clock = Clock::real();
 
         let mut sys_bus = System {
             n_devices: 0,
             max_devices: max_devices,
             transmitters: Vec::new(),
             receivers: Vec::new(),
+            ports: Arc::new(RwLock::new(Vec::new())),
             clock: clock,
             go: Arc::new(AtomicBool::new(false)),
             exit: Arc::new(AtomicBool::new(false)),
+            control: Arc::new(ExecControl::default()),
             handlers: Some(Vec::new()),
             home_dir: home_dir,
             write_delays: write_delays,
+            n_buses: 1,
+            illegalization: Illegalization::default(),
+            timing_policy: TimingPolicy::Record,
+            retry_policy: RetryPolicy::default(),
+            injector: {
+                let (tx, rx) = unbounded();
+                (Injector { tx }, rx)
+            },
+            phy: false,
+            faults: None,
+            timing_profiles: TimingProfiles::default(),
+            log_config: LogConfig::default(),
+            log_sinks: Vec::new(),
+            log_stream: None,
+            metrics: Arc::new(Mutex::new(MetricsCollector::default())),
             devices: Vec::new(),
+            snapshots: Vec::new(),
             logs: Vec::new(),
+            sim: None,
         };
         for _ in 0..sys_bus.max_devices {
-            let (s1, r1) = bounded(0);
-            // let (s1, r1) = unbounded();
-            sys_bus.transmitters.push(s1);
-            sys_bus.receivers.push(r1);
+            sys_bus.add_port();
         }
         return sys_bus;
     }
 
+    // a channel for one more device (off until one is plugged in)
+    fn add_port(&mut self) {
+        let (s1, r1) = bounded(0);
+        // let (s1, r1) = unbounded();
+        self.ports.write().unwrap().push(Port {
+            tx: s1.clone(),
+            power: Arc::new(PowerLine::default()),
+        });
+        self.transmitters.push(s1);
+        self.receivers.push(r1);
+        self.max_devices = self.max_devices.max(self.receivers.len() as u32);
+    }
+    /// Same as `new` but devices are driven by the discrete-event engine on a
+    /// simulated clock: `sleep_ms` advances virtual time as fast as the host
+    /// allows and the run is reproducible from `seed`.
+    #[allow(unused)]
+    pub fn new_virtual(max_devices: u32, write_delays: u128, seed: u64) -> Self {
+        let home_dir = Utc::now().format("%F-%H-%M-%S-%f").to_string();
+        System::new_virtual_with_name(max_devices, write_delays, home_dir, seed)
+    }
+    #[allow(unused)]
+    pub fn new_virtual_with_name(
+        max_devices: u32,
+        write_delays: u128,
+        home_dir: String,
+        seed: u64,
+    ) -> Self {
+        let mut sys_bus = System::new_with_name(max_devices, write_delays, home_dir);
+        let mut sim = VirtualBus::new();
+        sim.control = Arc::clone(&sys_bus.control);
+        sys_bus.clock = sim.clock();
+        sys_bus.timing_profiles.seed = Some(seed);
+        sys_bus.sim = Some(sim);
+        sys_bus
+    }
+
+    /// Wires `n_buses` redundant buses; to be called before `run_d`.
+    #[allow(unused)]
+    pub fn set_n_buses(&mut self, n_buses: u8) {
+        assert!((1..=MAX_BUSES).contains(&n_buses));
+        self.n_buses = n_buses;
+    }
+
+    /// Sets how the BC reacts to the bus timing; to be called before `run_d`.
+    #[allow(unused)]
+    pub fn set_timing_policy(&mut self, policy: TimingPolicy) {
+        self.timing_policy = policy;
+    }
+
+    /// Sets how the BC recovers from failed messages; to be called before
+    /// `run_d`.
+    #[allow(unused)]
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Receivers decode the Manchester bit stream of the words (partial
+    /// overlaps, sync and bit errors) instead of dropping colliding words;
+    /// to be called before `run_d`.
+    #[allow(unused)]
+    pub fn set_physical_layer(&mut self, on: bool) {
+        self.phy = on;
+    }
+
+    /// Degrades the bus with scripted faults; to be called before `run_d`.
+    #[allow(unused)]
+    pub fn set_faults(&mut self, faults: FaultInjector) {
+        self.faults = Some(Arc::new(Mutex::new(faults)));
+    }
+
+    /// Loads a fault script (JSON), see `FaultInjector::from_json`.
+    #[allow(unused)]
+    pub fn load_faults(&mut self, path: &Path) -> Result<(), String> {
+        self.set_faults(FaultInjector::load(path)?);
+        Ok(())
+    }
+
+    /// Gives device `id` (the next `run_d` gets id `n_devices`) its own
+    /// write timing instead of `write_delays`; to be called before `run_d`.
+    #[allow(unused)]
+    pub fn set_timing_profile(&mut self, id: u32, profile: TimingProfile) {
+        self.timing_profiles.devices.insert(id, profile);
+    }
+
+    /// Loads timing profiles (JSON), see `TimingProfiles`, over the ones set
+    /// so far; the seed of the file, if any, replaces the one of the system.
+    #[allow(unused)]
+    pub fn load_timing_profiles(&mut self, path: &Path) -> Result<(), String> {
+        self.timing_profiles.merge(TimingProfiles::load(path)?);
+        Ok(())
+    }
+
+    /// Chooses the log files to write; to be called before `run_d`.
+    #[allow(unused)]
+    pub fn set_log_config(&mut self, config: LogConfig) {
+        self.log_config = config;
+    }
+
+    /// Feeds the merged logs that pass `filter` to `sink`, on `join` or
+    /// while running when streaming.
+    #[allow(unused)]
+    pub fn add_log_sink(&mut self, filter: LogFilter, sink: Arc<Mutex<dyn LogSink>>) {
+        let sink = FilteredSink { filter, sink };
+        match &self.log_stream {
+            Some(stream) => {
+                if let Some(sink) = merged_only(sink) {
+                    stream.add_sink(sink);
+                }
+            }
+            None => self.log_sinks.push(sink),
+        }
+    }
+
+    /// The merged, time-ordered records that pass `filter`, as they are
+    /// written (with `log_config.stream`; otherwise on `join`). Records the
+    /// receiver has no room for are dropped.
+    #[allow(unused)]
+    pub fn subscribe_logs(&mut self, filter: LogFilter, capacity: usize) -> Receiver<LogRecord> {
+        let (tx, rx) = bounded(capacity);
+        self.add_log_sink(filter, Arc::new(Mutex::new(ChannelSink { tx })));
+        rx
+    }
+
+    /// State of every device as last published, read while the system runs:
+    /// at most `SNAPSHOT_INTERVAL` old in real time, current in virtual time.
+    #[allow(unused)]
+    pub fn snapshot(&self) -> Vec<DeviceSnapshot> {
+        self.snapshots
+            .iter()
+            .filter_map(|slot| slot.lock().unwrap().clone())
+            .collect()
+    }
+
+    /// Traffic, latency and error counts of the run, complete after `join`
+    /// (the records must be retained or streamed).
+    #[allow(unused)]
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.lock().unwrap().report()
+    }
+
+    fn metrics_sink(&self) -> FilteredSink {
+        FilteredSink {
+            filter: LogFilter::default(),
+            sink: self.metrics.clone(),
+        }
+    }
+
+    // `sys_bus.metrics.json`
+    fn save_metrics(&self) -> std::io::Result<()> {
+        let path = PathBuf::from(self.home_dir.clone()).join("sys_bus.metrics.json");
+        let mut file = File::create(path)?;
+        serde_json::to_writer_pretty(&mut file, &self.metrics())?;
+        writeln!(file)
+    }
+
+    /// Backpressure and ordering counters of the log stream, if any.
+    #[allow(unused)]
+    pub fn log_stream_stats(&self) -> Option<LogStreamStats> {
+        self.log_stream.as_ref().map(|stream| stream.stats())
+    }
+
+    // `sys_bus.log` and `sys_bus.flight.log`
+    fn sys_log_sinks(&self) -> Vec<FilteredSink> {
+        let home_dir = PathBuf::from(self.home_dir.clone());
+        let sys_log = TextSink::create(&home_dir.join("sys_bus.log")).unwrap();
+        let flight_log =
+            TextSink::with_format(&home_dir.join("sys_bus.flight.log"), format_log_flight).unwrap();
+        vec![
+            FilteredSink {
+                filter: LogFilter::default().modes([Mode::BC, Mode::RT]),
+                sink: Arc::new(Mutex::new(sys_log)),
+            },
+            FilteredSink {
+                filter: LogFilter::default().kinds(["MsgFlight"]),
+                sink: Arc::new(Mutex::new(flight_log)),
+            },
+        ]
+    }
+
+    // starts the stream if needed and gives `device` its end of it, along
+    // with its own log files
+    fn stream_logs(&mut self, device: &mut Device, log_file: &Path, log_file_bm: &Path) {
+        if self.log_stream.is_none() {
+            let mut sinks = match self.log_config.sys_logs {
+                true => self.sys_log_sinks(),
+                false => Vec::new(),
+            };
+            sinks.append(&mut self.log_sinks);
+            sinks.push(self.metrics_sink());
+            let sinks = sinks.into_iter().filter_map(merged_only).collect();
+            let config = &self.log_config;
+            let stream = LogStream::new(sinks, config.stream_capacity, config.reorder_window);
+            self.log_stream = Some(stream);
+        }
+        let stream = self.log_stream.as_ref().unwrap();
+        let own = LogFilter::default().devices([device.id]);
+        if self.log_config.device_logs {
+            stream.add_sink(FilteredSink {
+                filter: own.clone(),
+                sink: Arc::new(Mutex::new(TextSink::create(log_file).unwrap())),
+            });
+        }
+        if self.log_config.sys_logs && device.mode == Mode::BM {
+            let dat = TextSink::with_format(log_file_bm, format_log_bm).unwrap();
+            stream.add_sink(FilteredSink {
+                filter: own,
+                sink: Arc::new(Mutex::new(dat)),
+            });
+        }
+        device.log_stream = Some(stream.sender());
+    }
+
+    /// Loads the RT illegalization tables (JSON) applied by `run_d`.
+    #[allow(unused)]
+    pub fn load_illegalization(&mut self, path: &Path) -> Result<(), String> {
+        self.illegalization = Illegalization::load(path)?;
+        Ok(())
+    }
+
     pub fn go(&mut self) {
         self.go.store(true, Ordering::Relaxed);
     }
 
+    /// Freezes every device: device threads finish the word at hand and
+    /// wait, virtual time stops. The clock of the devices does not move
+    /// while the system is paused, and their locks are free.
     #[allow(unused)]
     pub fn pause(&mut self) {
-        self.go.store(false, Ordering::Relaxed);
+        self.control.pause();
+        self.clock.freeze();
+        self.wait_parked();
+    }
+
+    /// Undoes `pause` (or a breakpoint); also starts a system not yet `go`.
+    #[allow(unused)]
+    pub fn resume(&mut self) {
+        self.control.resume();
+        self.clock.thaw();
+        self.go();
+    }
+
+    #[allow(unused)]
+    pub fn is_paused(&self) -> bool {
+        self.control.is_paused()
+    }
+
+    // waits for the device threads to stop after a pause
+    fn wait_parked(&self) {
+        let n_threads = match &self.handlers {
+            Some(handlers) if self.sim.is_none() => handlers.len(),
+            _ => return,
+        };
+        let start = Instant::now();
+        while self.control.is_paused()
+            && self.control.parked() < n_threads
+            && start.elapsed() < PARK_TIMEOUT
+        {
+            thread::sleep(Duration::from_micros(100));
+        }
+    }
+
+    /// Freezes the system when a device logs a record matching `breakpoint`;
+    /// returns an id for `remove_breakpoint`.
+    #[allow(unused)]
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.control.add(breakpoint)
+    }
+
+    #[allow(unused)]
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        self.control.remove(id)
+    }
+
+    #[allow(unused)]
+    pub fn clear_breakpoints(&mut self) {
+        self.control.clear();
+    }
+
+    /// The breakpoint the system is frozen at, if any.
+    #[allow(unused)]
+    pub fn last_break(&self) -> Option<BreakHit> {
+        self.control.hit()
+    }
+
+    /// Resumes and runs for up to `ms` (virtual or real time), stopping
+    /// early at a breakpoint; returns the hit if the system is frozen at one.
+    #[allow(unused)]
+    pub fn run_until_break(&mut self, ms: u64) -> Option<BreakHit> {
+        self.resume();
+        match &mut self.sim {
+            Some(sim) => sim.run_for(ms as u128 * 1_000_000, true),
+            None => {
+                let start = Instant::now();
+                while !self.control.is_paused() && start.elapsed() < Duration::from_millis(ms) {
+                    thread::sleep(Duration::from_micros(100));
+                }
+                self.wait_parked();
+            }
+        }
+        self.control.hit()
+    }
+
+    /// Runs until the next word is put on the bus, and freezes there.
+    #[allow(unused)]
+    pub fn step_word(&mut self) -> Option<BreakHit> {
+        self.step(Breakpoint::Word)
+    }
+
+    /// Runs until the BC is done with the current message (or the next one
+    /// when it is between two), and freezes there.
+    #[allow(unused)]
+    pub fn step_message(&mut self) -> Option<BreakHit> {
+        self.step(Breakpoint::BcReady)
+    }
+
+    fn step(&mut self, breakpoint: Breakpoint) -> Option<BreakHit> {
+        self.control.step(Some(breakpoint));
+        let hit = self.run_until_break(STEP_LIMIT_MS);
+        if hit.is_none() {
+            // nothing happened, stay frozen all the same
+            self.control.step(None);
+            self.pause();
+        }
+        hit
     }
     pub fn stop(&mut self) {
         self.exit.store(true, Ordering::Relaxed);
@@ -684,6 +2290,14 @@ impl System {
         } else {
             panic!("tried to join but no threads exist");
         }
+        if let Some(sim) = &self.sim {
+            // no thread exit path in virtual time, flush device logs here
+            sim.flush_logs();
+        }
+
+        if let Some(stream) = &mut self.log_stream {
+            stream.close();
+        }
 
         // println!("Merging logs...");
         for device_mx in &self.devices {
@@ -693,36 +2307,31 @@ impl System {
             }
         }
 
-        self.logs.sort_by_key(|k| k.0);
-        if CONFIG_SAVE_SYS_LOGS {
-            let log_file = PathBuf::from(self.home_dir.clone()).join("sys_bus.log");
-            let mut file = OpenOptions::new()
-                .write(true)
-                .append(true)
-                .create(true)
-                .open(log_file)
-                .unwrap();
-            for l in &self.logs {
-                let _ = writeln!(file, "{}", format_log(&l));
-            }
-            let log_file = PathBuf::from(self.home_dir.clone()).join("sys_bus.flight.log");
-            let mut file = OpenOptions::new()
-                .write(true)
-                .append(true)
-                .create(true)
-                .open(log_file)
-                .unwrap();
-            for l in &self.logs {
-                match &l.6 {
-                    ErrMsg::MsgFlight(msg) => {
-                        let _ = writeln!(file, "{} {}", l.0, msg);
-                    }
-                    _ => {}
+        self.logs.sort_by_key(|k| k.time);
+        if self.log_stream.is_none() {
+            let mut sinks = match self.log_config.sys_logs {
+                true => self.sys_log_sinks(),
+                false => Vec::new(),
+            };
+            sinks.extend(self.log_sinks.iter().cloned());
+            sinks.push(self.metrics_sink());
+            for sink in &sinks {
+                if let Err(e) = sink.write_all(&self.logs) {
+                    eprintln!("failed to write logs: {}", e);
                 }
             }
         }
+        if self.log_config.sys_logs {
+            if let Err(e) = self.save_metrics() {
+                eprintln!("failed to write metrics: {}", e);
+            }
+        }
     }
     pub fn sleep_ms(&mut self, ms: u64) {
+        if let Some(sim) = &mut self.sim {
+            sim.run_for(ms as u128 * 1_000_000, self.go.load(Ordering::Relaxed));
+            return;
+        }
         thread::sleep(Duration::from_millis(ms));
     }
     pub fn sleep_ms_progress(&mut self, mut ms: u64) {
@@ -739,26 +2348,21 @@ impl System {
             if next > ms {
                 next = ms
             }
-            thread::sleep(Duration::from_millis(next));
+            self.sleep_ms(next);
             ms = ms - next;
             // pbs.set_message(format!("{}", ms));
             pbs.inc(next);
         }
     }
-    pub fn run_d(
-        &mut self,
-        addr: u8,
-        mode: Mode,
-        handler_emitter: Arc<Mutex<EventHandlerEmitter>>,
-        fake: bool,
-    ) {
-        let transmitters = self.transmitters.clone();
-        let receiver = self.receivers[self.n_devices as usize].clone();
+    fn new_device(&mut self, addr: u8, mode: Mode, fake: bool) -> Device {
+        if self.n_devices as usize >= self.receivers.len() {
+            self.add_port();
+        }
         let mut w_delay = self.write_delays;
         if fake {
             w_delay = ATK_DEFAULT_DELAYS;
         }
-        let device_obj = Device {
+        Device {
             fake: fake,
             atk_type: AttackType::Benign,
             ccmd: 0,
@@ -772,67 +2376,231 @@ impl System {
             id: self.n_devices,
             dword_count: 0,
             dword_count_expected: 0,
-            clock: self.clock,
-            transmitters: transmitters,
+            clock: self.clock.clone(),
+            ports: Arc::clone(&self.ports),
             write_queue: VecDeque::new(),
             read_queue: Vec::new(),
-            receiver: receiver,
+            receiver: self.receivers[self.n_devices as usize].clone(),
             delta_t_avg: 0,
             delta_t_count: 0,
             delta_t_start: 0,
             write_delays: w_delay,
             timeout: 0,
             timeout_times: 0,
+            bc_next_at: 0,
             time_write_ready: 0,
-        };
-        let device_name = format!("{}", device_obj);
-        let go = Arc::clone(&self.go);
-        let exit = Arc::clone(&self.exit);
-        let log_file = PathBuf::from(self.home_dir.clone()).join(format!("{}.log", device_obj));
-        let log_file_bm = PathBuf::from(self.home_dir.clone()).join(format!("{}.dat", device_obj));
-        self.n_devices += 1;
-        let device_mtx = Arc::new(Mutex::new(device_obj));
-        let device_mtx_thread_local = device_mtx.clone();
-        let device_handler_emitter = Arc::clone(&handler_emitter);
-        self.devices.push(device_mtx.clone());
-        let h = thread::Builder::new()
-            .name(format!("{}", device_name).to_string())
-            .spawn(move || {
-                let spin_sleeper = spin_sleep::SpinSleeper::new(1000);
-                // read_time, valid message flag, word
-                let mut prev_word = (0, false, WRD_EMPTY);
-                // lock the device object - release only after thread shutdown:
-                let mut device = device_mtx_thread_local.lock().unwrap();
-                // warmup offset
-                let mut bc_step = 0;
+            n_buses: self.n_buses,
+            bus: 0,
+            last_msg: None,
+            sub_address: SA_DEFAULT,
+            retry: None,
+            retries: 0,
+            retry_policy: self.retry_policy.clone(),
+            recovery: Recovery::default(),
+            injected: self.injector.1.clone(),
+            in_flight: None,
+            bus_phase: vec![BusPhase::default(); self.n_buses as usize],
+            timing_policy: self.timing_policy,
+            phy_rx: match self.phy {
+                true => Some(vec![None; self.n_buses as usize]),
+                false => None,
+            },
+            faults: self.faults.clone(),
+            log_config: self.log_config,
+            log_stream: None,
+            timing_profile: self.timing_profiles.timing(self.n_devices),
+            counters: DeviceCounters::default(),
+            snapshot: Arc::new(Mutex::new(None)),
+            published_at: 0,
+            control: Arc::clone(&self.control),
+            power: Arc::clone(&self.ports.read().unwrap()[self.n_devices as usize].power),
+            power_seen: Power::On,
+            last_status: Word::new_status(addr),
+            last_cmd: WRD_EMPTY,
+            bit_word: 0,
+            vector_word: 0,
+            service_request: false,
+            busy: false,
+            subsystem_flag: false,
+            terminal_flag: false,
+            terminal_flag_inhibit: false,
+            tx_shutdown: vec![false; self.n_buses as usize],
+            bc_capable: false,
+            takeover_after: 0,
+            bus_heard: None,
+            control_accepted: false,
+            sync_time: 0,
+            sync_word: 0,
+            illegal_cmds: self.illegalization.rules(addr),
+            in_illegal: false,
+        }
+    }
+    #[allow(unused)]
+    pub fn run_d(
+        &mut self,
+        addr: u8,
+        mode: Mode,
+        handler_emitter: Arc<Mutex<EventHandlerEmitter>>,
+        fake: bool,
+    ) {
+        let device_obj = self.new_device(addr, mode, fake);
+        self.start(device_obj, handler_emitter);
+    }
 
-                loop {
+    /// Adds the device of `spec`, to a running system too (hot-plug); returns
+    /// its id.
+    #[allow(unused)]
+    pub fn plug(&mut self, spec: DeviceSpec) -> u32 {
+        if let Some(profile) = spec.timing {
+            self.set_timing_profile(self.n_devices, profile);
+        }
+        let mut device_obj = self.new_device(spec.address, spec.mode, spec.fake);
+        if let Some(write_delays) = spec.write_delays {
+            device_obj.write_delays = write_delays;
+        }
+        if let Some(takeover_after) = spec.takeover_after {
+            device_obj.bc_capable = true;
+            device_obj.takeover_after = takeover_after;
+        }
+        let id = device_obj.id;
+        self.start(device_obj, spec.emitter);
+        id
+    }
+
+    /// Power of device `id`.
+    #[allow(unused)]
+    pub fn power(&self, id: u32) -> Option<Power> {
+        let ports = self.ports.read().unwrap();
+        Some(ports.get(id as usize)?.power.power())
+    }
+
+    /// Switches device `id` off: it drops what it was doing and is silent.
+    #[allow(unused)]
+    pub fn power_off(&mut self, id: u32) -> Result<(), String> {
+        self.switch_power(id, |line| line.set(Power::Off))
+    }
+
+    /// Switches device `id` on; it stays silent for `boot_delay` ns, then
+    /// starts afresh.
+    #[allow(unused)]
+    pub fn power_on(&mut self, id: u32, boot_delay: u128) -> Result<(), String> {
+        let until = self.clock.elapsed().as_nanos() + boot_delay;
+        self.switch_power(id, |line| line.boot(until))
+    }
+
+    /// Device `id` breaks down for good.
+    #[allow(unused)]
+    pub fn fail(&mut self, id: u32) -> Result<(), String> {
+        self.switch_power(id, |line| line.set(Power::Failed))
+    }
+
+    /// Where other threads inject one-shot messages into the running BC.
+    #[allow(unused)]
+    pub fn injector(&self) -> Injector {
+        self.injector.0.clone()
+    }
+
+    /// Has the BC send `msg` once, at its next gap between scheduled
+    /// messages (see `MessageHandle` for the outcome).
+    #[allow(unused)]
+    pub fn inject(&mut self, msg: Message) -> MessageHandle {
+        let handle = self.injector.0.inject(msg);
+        if let Some(sim) = &mut self.sim {
+            for (id, device) in self.devices.iter().enumerate() {
+                if device.lock().unwrap().mode == Mode::BC {
+                    sim.wake(id);
+                }
+            }
+        }
+        handle
+    }
+
+    // a device thread applies the switch on its next iteration, in virtual
+    // time it is applied right away
+    fn switch_power(&mut self, id: u32, switch: impl FnOnce(&PowerLine)) -> Result<(), String> {
+        let line = match self.devices.get(id as usize) {
+            Some(_) => Arc::clone(&self.ports.read().unwrap()[id as usize].power),
+            None => return Err(format!("no device {}", id)),
+        };
+        if line.power() == Power::Failed {
+            return Err(format!("device {} has failed", id));
+        }
+        switch(&line);
+        if let Some(sim) = &mut self.sim {
+            let mut device = self.devices[id as usize].lock().unwrap();
+            device.sync_power();
+            device.publish(sim.now());
+            drop(device);
+            sim.wake(id as usize);
+        }
+        Ok(())
+    }
+
+    fn start(&mut self, mut device_obj: Device, handler_emitter: Arc<Mutex<EventHandlerEmitter>>) {
+        let device_name = format!("{}", device_obj);
+        let go = Arc::clone(&self.go);
+        let exit = Arc::clone(&self.exit);
+        let control = Arc::clone(&self.control);
+        let log_file = PathBuf::from(self.home_dir.clone()).join(format!("{}.log", device_obj));
+        let log_file_bm = PathBuf::from(self.home_dir.clone()).join(format!("{}.dat", device_obj));
+        if self.log_config.stream {
+            self.stream_logs(&mut device_obj, &log_file, &log_file_bm);
+        }
+        device_obj.power.set(Power::On);
+        if self.go.load(Ordering::Relaxed) {
+            device_obj.log(WRD_EMPTY, ErrMsg::MsgPower(PowerEvent::Plugged));
+        }
+        device_obj.publish(0);
+        self.snapshots.push(Arc::clone(&device_obj.snapshot));
+        self.n_devices += 1;
+        let device_mtx = Arc::new(Mutex::new(device_obj));
+        let device_mtx_thread_local = device_mtx.clone();
+        let device_handler_emitter = Arc::clone(&handler_emitter);
+        self.devices.push(device_mtx.clone());
+        if let Some(sim) = &mut self.sim {
+            sim.add_device(device_mtx, handler_emitter, log_file, log_file_bm);
+            return;
+        }
+        let h = thread::Builder::new()
+            .name(format!("{}", device_name).to_string())
+            .spawn(move || {
+                let spin_sleeper = spin_sleep::SpinSleeper::new(1000);
+                // lock the device object - release only while parked and after thread shutdown:
+                let mut device = device_mtx_thread_local.lock().unwrap();
+                // per bus: read_time, valid message flag, word
+                let mut prev_words = vec![(0, false, WRD_EMPTY); device.n_buses as usize];
+                // warmup offset
+                let mut bc_step = 0;
+                // words taken while parked, read before the channel
+                let receiver = device.receiver.clone();
+                let mut held = VecDeque::new();
+
+                loop {
+                    if control.is_paused() {
+                        let now = device.clock.elapsed().as_nanos();
+                        device.publish(now);
+                        // the device can be looked at while parked; the words
+                        // of a device still finishing its write are taken so
+                        // that the pause loses none
+                        drop(device);
+                        control.park(&exit, || held.extend(receiver.try_iter()));
+                        device = device_mtx_thread_local.lock().unwrap();
+                    }
+                    if !device.sync_power() {
+                        // what was being received is lost
+                        prev_words.iter_mut().for_each(|p| p.1 = false);
+                    }
                     if !go.load(Ordering::Relaxed) || device.state == State::Off {
                         spin_sleeper.sleep_ns(1_000_000);
                     }
                     if device.state != State::Off {
                         let mut current = device.clock.elapsed().as_nanos();
+                        if go.load(Ordering::Relaxed) {
+                            device.check_takeover(current);
+                        }
                         if device.mode == Mode::BC {
-                            let mut timeout = device.timeout;
-                            // 10 timeout for warming up
-                            if bc_step <= BC_WARMUP_STEPS {
-                                // if it is for warming up, we add additional margin for timeout
-                                // but we can't just skip since certain operation fails forever
-                                // such as BC2RT where RT is a BM
-                                timeout += 20_000_000;
-                            }
-                            if device.state == State::Idle {
-                                device.log(WRD_EMPTY, ErrMsg::MsgBCReady);
-                                let mut local_emitter = device_handler_emitter.lock().unwrap();
-                                device.timeout = 0;
-                                local_emitter.handler.on_bc_ready(&mut device);
-                                bc_step += 1;
-                            } else if timeout > 0 && current > timeout {
-                                device.timeout_times += 1;
-                                let mut local_emitter = device_handler_emitter.lock().unwrap();
-                                local_emitter.handler.on_bc_timeout(&mut device);
-                                device.reset_all_stateful();
-                                device.timeout = 0;
+                            let mut local_emitter = device_handler_emitter.lock().unwrap();
+                            if local_emitter.bc_tick(&mut device, &mut bc_step, current) {
                                 spin_sleeper.sleep_ns(100_0000);
                             }
                         }
@@ -841,14 +2609,21 @@ impl System {
                         if current > device.time_write_ready {
                             if let Some(entry) = device.write_queue.pop_front() {
                                 let wq = device.write_queue.len();
-                                spin_sleeper.sleep_ns(device.write_delays as u64);
-                                device.log(entry.1, ErrMsg::MsgWrt(wq));
-                                for (i, s) in device.transmitters.iter().enumerate() {
-                                    if (i as u32) != device.id {
+                                let delay = device.write_delay(wq);
+                                spin_sleeper.sleep_ns(delay as u64);
+                                device.transmitted(entry.1, wq);
+                                let ports = device.ports.read().unwrap().clone();
+                                for (i, port) in ports.iter().enumerate() {
+                                    // unpowered devices do not read
+                                    if (i as u32) != device.id && port.power.listening() {
+                                        let w = match device.deliver(i as u32, entry.1) {
+                                            Some(w) => w,
+                                            None => continue,
+                                        };
                                         // let _e = s.try_send(entry.1);
                                         // let _e = s.send(entry.1);
                                         let _e =
-                                            s.send_timeout(entry.1, Duration::from_millis(100));
+                                            port.tx.send_timeout(w, Duration::from_millis(100));
                                         if _e.is_err() {
                                             break;
                                         }
@@ -859,130 +2634,27 @@ impl System {
                             }
                         }
                         // update current after potential blocking operation
-                        let res = device.read();
+                        let res = match held.pop_front() {
+                            Some(w) => Ok(w),
+                            None => device.read(),
+                        };
                         current = device.clock.elapsed().as_nanos();
-                        let diff =
-                            (current as i128) - (prev_word.0 as i128) - (RT_WORD_LOAD_TIME as i128);
-                        if prev_word.1 && diff > 0 {
-                            // message in the cache is valid & after word_time . processe the word.
-                            let mut w = prev_word.2;
+                        if let Ok(w) = res {
                             let mut local_emitter = device_handler_emitter.lock().unwrap();
-                            let new_atk_type = local_emitter.handler.get_attk_type();
-                            if new_atk_type != device.atk_type {
-                                // new handler
-                                device.reset_all_stateful();
-                                device.atk_type = new_atk_type;
-                            }
-
-                            if device.mode == Mode::BM {
-                                device.log(w, ErrMsg::MsgBMLog);
-                            } else {
-                                if w.sync() == 1 {
-                                    if w.instrumentation_bit() == 1 {
-                                        local_emitter.handler.on_cmd(&mut device, &mut w)
-                                    } else {
-                                        // status word
-                                        local_emitter.handler.on_sts(&mut device, &mut w);
-                                    }
-                                } else {
-                                    // data word
-                                    local_emitter.handler.on_dat(&mut device, &mut w);
-                                }
-                            }
-
-                            // clear cache
-                            prev_word = (0, false, WRD_EMPTY);
-                        }
-                        if !res.is_err() {
-                            // update current after blocking
-                            if prev_word.0 == 0 {
-                                // empty cache, do replacement
-                                prev_word = (current, true, res.unwrap());
-                            } else {
-                                // collision
-                                if diff < 0 {
-                                    let mut w = res.unwrap();
-                                    // if w.address() == device.address {
-                                    let mut local_emitter = device_handler_emitter.lock().unwrap();
-                                    let new_atk_type = local_emitter.handler.get_attk_type();
-                                    if new_atk_type != device.atk_type {
-                                        // new handler
-                                        device.reset_all_stateful();
-                                        device.atk_type = new_atk_type;
-                                    }
-                                    if prev_word.1 {
-                                        // if previous word is a valid message then file parity error
-                                        // if not, the error was already filed.
-                                        // log previous word recieve time
-                                        // if device.state != State::Idle {
-                                        local_emitter.handler.on_err_parity(
-                                            &mut device,
-                                            &mut prev_word.2,
-                                            prev_word.0 as i128,
-                                            diff,
-                                        );
-                                        // log current word recieve time
-                                        local_emitter.handler.on_err_parity(
-                                            &mut device,
-                                            &mut w,
-                                            current as i128,
-                                            diff,
-                                        );
-                                        // log the previous word (corrupted)
-                                        if device.mode == Mode::BM {
-                                            w.set_parity_bit(1);
-                                            device.log(w, ErrMsg::MsgBMLog);
-                                        }
-                                        // }
-                                    }
-                                    // }
-                                    device.reset_all_stateful();
-                                    prev_word = (0, false, WRD_EMPTY);
-                                }
-                            }
+                            local_emitter.receive(&mut device, &mut prev_words, w, current);
+                        } else if prev_words.iter().any(|p| p.1) {
+                            let mut local_emitter = device_handler_emitter.lock().unwrap();
+                            local_emitter.process_due(&mut device, &mut prev_words, current);
                         }
                     }
+                    let now = device.clock.elapsed().as_nanos();
+                    if now >= device.published_at + SNAPSHOT_INTERVAL {
+                        device.publish(now);
+                    }
                     if exit.load(Ordering::Relaxed) {
                         //exiting
-                        if CONFIG_SAVE_DEVICE_LOGS {
-                            println!(
-                                "{} writing {} logs to {} ",
-                                device,
-                                device.logs.len(),
-                                log_file.to_str().unwrap()
-                            );
-                            let mut file = OpenOptions::new()
-                                .write(true)
-                                .append(true)
-                                .create(true)
-                                .open(log_file)
-                                .unwrap();
-                            let device_des = device.to_string();
-                            for l in &device.logs {
-                                writeln!(file, "{}", format_log(&l)).unwrap();
-                            }
-                            println!("{} Done flushing logs", device_des);
-                        }
-                        // for bus monitor
-                        if CONFIG_SAVE_SYS_LOGS && device.mode == Mode::BM {
-                            println!(
-                                "{} writing {} logs to {} ",
-                                device,
-                                device.logs.len(),
-                                log_file_bm.to_str().unwrap()
-                            );
-                            let mut file = OpenOptions::new()
-                                .write(true)
-                                .append(true)
-                                .create(true)
-                                .open(log_file_bm)
-                                .unwrap();
-                            let device_des = device.to_string();
-                            for l in &device.logs {
-                                writeln!(file, "{}", format_log_bm(&l)).unwrap();
-                            }
-                            println!("{} Done flushing logs", device_des);
-                        }
+                        device.publish(now);
+                        device.save_logs(&log_file, &log_file_bm);
                         break;
                     }
                 }
@@ -1057,10 +2729,264 @@ pub struct EventHandlerEmitter {
     pub handler: Box<dyn EventHandler>,
 }
 
-impl EventHandlerEmitter {}
+/// Restricts a handler to a single bus of a redundant system: it only hears
+/// words of that bus and all its writes go there.
+pub struct BusBound {
+    pub bus: u8,
+    pub inner: Box<dyn EventHandler>,
+}
+
+impl EventHandler for BusBound {
+    fn on_wrd_rec(&mut self, d: &mut Device, w: &mut Word) {
+        self.inner.on_wrd_rec(d, w);
+    }
+    fn on_err_parity(&mut self, d: &mut Device, w: &mut Word, recv_time: i128, lag: i128) {
+        self.inner.on_err_parity(d, w, recv_time, lag);
+    }
+    fn on_err_phy(&mut self, d: &mut Device, w: &mut Word, err: PhyError, recv_time: i128) {
+        self.inner.on_err_phy(d, w, err, recv_time);
+    }
+    fn on_cmd(&mut self, d: &mut Device, w: &mut Word) {
+        self.inner.on_cmd(d, w);
+    }
+    fn on_cmd_rcv(&mut self, d: &mut Device, w: &mut Word) {
+        self.inner.on_cmd_rcv(d, w);
+    }
+    fn on_cmd_trx(&mut self, d: &mut Device, w: &mut Word) {
+        self.inner.on_cmd_trx(d, w);
+    }
+    fn on_cmd_mcx(&mut self, d: &mut Device, w: &mut Word) {
+        self.inner.on_cmd_mcx(d, w);
+    }
+    fn on_dat(&mut self, d: &mut Device, w: &mut Word) {
+        self.inner.on_dat(d, w);
+    }
+    fn on_sts(&mut self, d: &mut Device, w: &mut Word) {
+        self.inner.on_sts(d, w);
+    }
+    fn on_bc_ready(&mut self, d: &mut Device) {
+        self.inner.on_bc_ready(d);
+    }
+    fn on_bc_timeout(&mut self, d: &mut Device) {
+        self.inner.on_bc_timeout(d);
+    }
+    fn on_memory_ready(&mut self, d: &mut Device) {
+        self.inner.on_memory_ready(d);
+    }
+    fn on_data_write(&mut self, d: &mut Device, dword_count: u8) {
+        self.inner.on_data_write(d, dword_count);
+    }
+    fn verify(&mut self, system: &System) -> bool {
+        self.inner.verify(system)
+    }
+    fn get_attk_type(&self) -> AttackType {
+        self.inner.get_attk_type()
+    }
+    fn bus(&self) -> Option<u8> {
+        Some(self.bus)
+    }
+}
+
+impl EventHandlerEmitter {
+    fn sync_attk_type(&mut self, d: &mut Device) {
+        let new_atk_type = self.handler.get_attk_type();
+        if new_atk_type != d.atk_type {
+            // new handler
+            d.reset_all_stateful();
+            d.atk_type = new_atk_type;
+        }
+    }
+
+    /// One BC scheduling step. Returns true if the current message timed out.
+    pub fn bc_tick(&mut self, d: &mut Device, bc_step: &mut u128, current: u128) -> bool {
+        let mut timeout = d.timeout;
+        // 10 timeout for warming up
+        if *bc_step <= BC_WARMUP_STEPS {
+            // if it is for warming up, we add additional margin for timeout
+            // but we can't just skip since certain operation fails forever
+            // such as BC2RT where RT is a BM
+            timeout += 20_000_000;
+        }
+        if d.state == State::Idle {
+            if d.retry.is_none() {
+                if let Some(in_flight) = d.in_flight.take() {
+                    in_flight.finish(current);
+                }
+            }
+            let recovering = d.retry.is_some() || d.recovery.pending.is_some();
+            if current < d.bc_next_at && (recovering || d.injected.is_empty()) {
+                // the handler waits for its next slot (a gap injected
+                // messages can use)
+                return false;
+            }
+            d.log(WRD_EMPTY, ErrMsg::MsgBCReady);
+            d.timeout = 0;
+            if let Some(msg) = d.retry.take() {
+                d.log(WRD_EMPTY, ErrMsg::MsgBCRetry(d.bus));
+                d.retries += 1;
+                if let Some(in_flight) = &mut d.in_flight {
+                    in_flight.failure = None;
+                    in_flight.transfer.retries = d.retries;
+                }
+                d.act(&msg);
+            } else if let Some(msg) = d.recovery.pending.take() {
+                // an escalation step (the step is kept until it is through)
+                d.act(&msg);
+            } else if let Ok(injected) = d.injected.try_recv() {
+                d.retries = 0;
+                d.recovery.step = None;
+                d.recovery.policy = None;
+                d.act_injected(injected);
+            } else {
+                d.retries = 0;
+                d.recovery.step = None;
+                d.recovery.policy = None;
+                self.handler.on_bc_ready(d);
+            }
+            *bc_step += 1;
+        } else if (timeout > 0 && current > timeout) || d.check_no_response(current) {
+            d.timeout_times += 1;
+            d.note_failure(Failure::Timeout);
+            self.handler.on_bc_timeout(d);
+            d.reset_all_stateful();
+            d.timeout = 0;
+            return true;
+        }
+        false
+    }
+
+    /// Dispatches a word that has been on the bus for a full word time since
+    /// `start`.
+    pub fn process_word(&mut self, d: &mut Device, mut w: Word, start: u128) {
+        self.sync_attk_type(d);
+        if let Some(bus) = self.handler.bus() {
+            d.bus = bus;
+        }
+        let status = d.observe(&w, start);
+        if d.mode == Mode::BM {
+            d.log(w, ErrMsg::MsgBMLog);
+        } else {
+            if w.sync() == SYNC_CMD_STS {
+                if status {
+                    self.handler.on_sts(d, &mut w);
+                } else {
+                    self.handler.on_cmd(d, &mut w)
+                }
+            } else {
+                // data word
+                self.handler.on_dat(d, &mut w);
+            }
+        }
+    }
+
+    /// Processes the cached words (read_time, valid message flag, word), one
+    /// per bus, once `RT_WORD_LOAD_TIME` has passed since they were received.
+    pub fn process_due(
+        &mut self,
+        d: &mut Device,
+        prev_words: &mut [(u128, bool, Word)],
+        current: u128,
+    ) {
+        for (bus, prev_word) in prev_words.iter_mut().enumerate() {
+            let diff = (current as i128) - (prev_word.0 as i128) - (RT_WORD_LOAD_TIME as i128);
+            if prev_word.1 && diff > 0 {
+                let (start, _, w) = *prev_word;
+                // clear cache
+                *prev_word = (0, false, WRD_EMPTY);
+                // message in the cache is valid & after word_time . processe the word.
+                match d.phy_rx.as_mut().and_then(|rx| rx[bus].take()) {
+                    Some(wave) => match wave.decode(&w) {
+                        Ok(w) => self.process_word(d, w, start),
+                        Err(err) => self.phy_error(d, w, err, start),
+                    },
+                    // (only words damaged on their way can fail the check)
+                    None if w.fault() != FaultLabel::Clean => match phy::check(&w) {
+                        Ok(()) => self.process_word(d, w, start),
+                        Err(err) => self.phy_error(d, w, err, start),
+                    },
+                    None => self.process_word(d, w, start),
+                }
+            }
+        }
+    }
+
+    fn phy_error(&mut self, d: &mut Device, mut w: Word, err: PhyError, start: u128) {
+        self.sync_attk_type(d);
+        self.handler.on_err_phy(d, &mut w, err, start as i128);
+        if d.mode == Mode::BM {
+            // fails the parity check
+            w.set_parity_bit(w.parity_bit() ^ 1);
+            d.log(w, ErrMsg::MsgBMLog);
+        }
+        d.reset_all_stateful();
+    }
+
+    /// Handles a word read from the bus at `current`; a word arriving while
+    /// the cached one of the same bus is still loading is a collision and
+    /// corrupts both.
+    pub fn receive(
+        &mut self,
+        d: &mut Device,
+        prev_words: &mut [(u128, bool, Word)],
+        mut w: Word,
+        current: u128,
+    ) {
+        let bus = w.bus() as usize;
+        if bus >= prev_words.len() || self.handler.bus().is_some_and(|b| b as usize != bus) {
+            // not attached to this bus
+            return;
+        }
+        let diff = (current as i128) - (prev_words[bus].0 as i128) - (RT_WORD_LOAD_TIME as i128);
+        self.process_due(d, prev_words, current);
+        d.bus_phase[bus].start(current);
+        d.bus_heard = Some(current);
+        let prev_word = &mut prev_words[bus];
+        if prev_word.0 == 0 {
+            // empty cache, do replacement
+            *prev_word = (current, true, w);
+            if let Some(rx) = d.phy_rx.as_mut() {
+                rx[bus] = Some(Waveform::encode(&w));
+            }
+        } else if diff < 0 && d.phy_rx.is_some() {
+            // the receiver stays locked on the first word, which is garbled
+            // where they overlap; the sync of a later one is never seen
+            let offset = current - prev_word.0;
+            if let Some(wave) = d.phy_rx.as_mut().and_then(|rx| rx[bus].as_mut()) {
+                wave.superimpose(&Waveform::encode(&w), offset);
+            }
+            if offset >= phy::HALF_BIT_TIME {
+                d.log(w, ErrMsg::MsgEntErrSync);
+            }
+        } else if diff < 0 {
+            // collision
+            // if w.address() == device.address {
+            self.sync_attk_type(d);
+            if prev_word.1 {
+                // if previous word is a valid message then file parity error
+                // if not, the error was already filed.
+                // log previous word recieve time
+                // if device.state != State::Idle {
+                self.handler
+                    .on_err_parity(d, &mut prev_word.2, prev_word.0 as i128, diff);
+                // log current word recieve time
+                self.handler.on_err_parity(d, &mut w, current as i128, diff);
+                // log the previous word (corrupted)
+                if d.mode == Mode::BM {
+                    // fails the parity check
+                    w.set_parity_bit(w.parity_bit() ^ 1);
+                    d.log(w, ErrMsg::MsgBMLog);
+                }
+                // }
+            }
+            // }
+            d.reset_all_stateful();
+            *prev_word = (0, false, WRD_EMPTY);
+        }
+    }
+}
 
 #[allow(unused)]
-#[derive(Clone, Debug, Copy, PartialEq)]
+#[derive(Clone, Debug, Copy, PartialEq, Serialize)]
 pub enum AttackType {
     Benign = 0,
     AtkCollisionAttackAgainstTheBus = 1,
@@ -1096,38 +3022,22 @@ impl From<i32> for AttackType {
 }
 
 pub fn eval_sys(w_delays: u128, n_devices: u8, proto: Proto, proto_rotate: bool) -> System {
-    // let n_devices = 3;
-    // let w_delays = w_delays;
-    let mut sys_bus = System::new(n_devices as u32, w_delays);
-    for m in 0..n_devices {
-        // let (s1, r1) = bounded(64);
-        // s_vec.lock().unwrap().push(s1);
-        if m == 0 {
-            sys_bus.run_d(
-                m as u8,
-                Mode::BC,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultBCEventHandler {
-                        total_device: n_devices,
-                        target: 0,
-                        data: vec![1, 2, 3],
-                        proto: proto,
-                        proto_rotate: proto_rotate,
-                    }),
-                })),
-                false,
-            );
-        } else {
-            sys_bus.run_d(
-                m as u8,
-                Mode::RT,
-                Arc::new(Mutex::new(EventHandlerEmitter {
-                    handler: Box::new(DefaultEventHandler {}),
-                })),
-                false,
-            );
-        }
-    }
+    let bc = DeviceSpec::new(
+        0,
+        Mode::BC,
+        Box::new(DefaultBCEventHandler {
+            total_device: n_devices,
+            target: 0,
+            data: vec![1, 2, 3],
+            proto: proto,
+            proto_rotate: proto_rotate,
+        }),
+    );
+    let mut sys_bus = SystemBuilder::new(w_delays)
+        .device(bc)
+        .rts(1..n_devices)
+        .build()
+        .unwrap();
     sys_bus.go();
     sys_bus.sleep_ms(200);
     sys_bus.stop();
@@ -1139,6 +3049,8 @@ pub fn eval_sys(w_delays: u128, n_devices: u8, proto: Proto, proto_rotate: bool)
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use log::LogQuery;
+    use timing::Delay;
 
     #[test]
     fn test_delta_t() {
@@ -1194,4 +3106,1260 @@ mod tests {
         sys_bus.join();
         assert!(sys_bus.devices[0].lock().unwrap().timeout_times > 2);
     }
+
+    #[test]
+    fn test_word_layout() {
+        let mut w = Word::new_cmd(5, 3, TR::Transmit);
+        w.set_sub_address(0b10110);
+        assert_eq!(w.sync(), SYNC_CMD_STS);
+        assert_eq!(w.address(), 5);
+        assert_eq!(w.tr(), TR::Transmit);
+        assert_eq!(w.sub_address(), 0b10110);
+        assert_eq!(w.dword_count(), 3);
+        assert_eq!(w.all() >> 20, 0);
+        // the sub-address shares its bits with the status flags
+        assert_eq!(w.instrumentation_bit(), 0);
+        assert_eq!(w.reserved_bits(), 0b101);
+        assert!(!w.is_mode_cmd());
+        let mc = Word::new_mode_cmd(5, 17, TR::Receive);
+        assert!(mc.is_mode_cmd());
+        assert_eq!(mc.mode_code(), 17);
+        assert_eq!(mc.n_data_words(), 1);
+        assert_eq!(Word::new_data(0xffff).sync(), SYNC_DATA);
+        // odd parity over the 16 information bits
+        for w in [
+            mc,
+            Word::new_status(7),
+            Word::new_data(0),
+            Word::new_data(0xa5),
+        ] {
+            assert_eq!((w.data().count_ones() + w.parity_bit() as u32) % 2, 1);
+        }
+    }
+
+    #[test]
+    fn test_bus_phase_status_or_command() {
+        let mut phase = BusPhase::default();
+        let mut see = |w: Word| {
+            let status = phase.is_status(&w, 0);
+            phase.advance(&w, status, 0);
+            status
+        };
+        // rt2bc: status then data
+        assert!(!see(Word::new_cmd(3, 2, TR::Transmit)));
+        assert!(see(Word::new_status(3)));
+        see(Word::new_data(1));
+        see(Word::new_data(2));
+        // the next command to the same RT is not mistaken for its status
+        assert!(!see(Word::new_cmd(3, 1, TR::Receive)));
+        see(Word::new_data(1));
+        assert!(see(Word::new_status(3)));
+        // rt2rt: receiver 4 answers after the data of transmitter 3
+        assert!(!see(Word::new_cmd(4, 1, TR::Receive)));
+        assert!(!see(Word::new_cmd(3, 1, TR::Transmit)));
+        assert!(see(Word::new_status(3)));
+        see(Word::new_data(1));
+        assert!(!see(Word::new_status(3)));
+        // (not expected any more: a command to RT@3)
+        let mut phase = BusPhase::default();
+        phase.advance(&Word::new_cmd(4, 1, TR::Receive), false, 0);
+        phase.advance(&Word::new_cmd(3, 1, TR::Transmit), false, 0);
+        phase.advance(&Word::new_status(3), true, 0);
+        phase.advance(&Word::new_data(1), false, 0);
+        assert!(phase.is_status(&Word::new_status(4), 0));
+        // a late status is not a response any more
+        assert!(!phase.is_status(&Word::new_status(4), STATUS_WINDOW + 1));
+        // broadcast: no status
+        let mut phase = BusPhase::default();
+        phase.advance(&Word::new_cmd(BROADCAST_ADDRESS, 0, TR::Receive), false, 0);
+        assert!(!phase.is_status(&Word::new_status(BROADCAST_ADDRESS), 0));
+    }
+
+    // BC sending a fixed list of (bus, message)
+    struct ScriptBC {
+        script: VecDeque<(u8, Message)>,
+    }
+
+    impl EventHandler for ScriptBC {
+        fn on_bc_ready(&mut self, d: &mut Device) {
+            if let Some((bus, msg)) = self.script.pop_front() {
+                d.bus = bus;
+                d.act(&msg);
+            }
+        }
+    }
+
+    // runs a script against RT@1 (set up by `setup`) to RT@n_rts
+    fn eval_script(
+        n_buses: u8,
+        n_rts: u8,
+        script: Vec<(u8, Message)>,
+        setup: impl FnOnce(&mut Device),
+    ) -> System {
+        let mut sys_bus = System::new_virtual(n_rts as u32 + 1, 4_000, 0);
+        sys_bus.set_n_buses(n_buses);
+        eval_script_on(sys_bus, n_rts, script, |sys_bus| {
+            setup(&mut sys_bus.devices[1].lock().unwrap())
+        })
+    }
+
+    // same with any system, `setup` runs once the BC and RTs are added
+
+    fn eval_script_on(
+        mut sys_bus: System,
+        n_rts: u8,
+        script: Vec<(u8, Message)>,
+        setup: impl FnOnce(&mut System),
+    ) -> System {
+        let bc: Box<dyn EventHandler> = Box::new(ScriptBC {
+            script: script.into(),
+        });
+        sys_bus.run_d(
+            0,
+            Mode::BC,
+            Arc::new(Mutex::new(EventHandlerEmitter { handler: bc })),
+            false,
+        );
+        for m in 1..=n_rts {
+            sys_bus.run_d(
+                m,
+                Mode::RT,
+                Arc::new(Mutex::new(EventHandlerEmitter {
+                    handler: Box::new(DefaultEventHandler {}),
+                })),
+                false,
+            );
+        }
+        setup(&mut sys_bus);
+        sys_bus.go();
+        // (long enough for timeouts during the warm up)
+        sys_bus.sleep_ms(200);
+        sys_bus.stop();
+        sys_bus.join();
+        sys_bus
+    }
+
+    fn bc_words(sys_bus: &System, msg: ErrMsg) -> Vec<Word> {
+        let bc = sys_bus.devices[0].lock().unwrap();
+        bc.logs
+            .iter()
+            .filter(|l| l.event == msg)
+            .map(|l| l.word)
+            .collect()
+    }
+
+    #[test]
+    fn test_mode_code_data_responses() {
+        let sys_bus = eval_script(
+            1,
+            1,
+            vec![
+                (0, Message::ModeCode(1, 16, None)),
+                (0, Message::BC2RT(1, vec![7, 8])),
+                (0, Message::ModeCode(1, 18, None)),
+                (0, Message::ModeCode(1, 19, None)),
+            ],
+            |rt| {
+                rt.vector_word = 0x1234;
+                rt.bit_word = 0x0f0f;
+            },
+        );
+        let data: Vec<u32> = bc_words(&sys_bus, ErrMsg::MsgEntDat)
+            .iter()
+            .map(|w| w.data())
+            .collect();
+        assert_eq!(
+            data,
+            vec![0x1234, Word::new_cmd(1, 2, TR::Receive).data(), 0x0f0f]
+        );
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgEntSte).len(), 4);
+        assert_eq!(sys_bus.devices[0].lock().unwrap().timeout_times, 0);
+    }
+
+    #[test]
+    fn test_mode_code_terminal_flag_and_sync() {
+        let sys_bus = eval_script(
+            1,
+            1,
+            vec![
+                (0, Message::ModeCode(1, 2, None)),
+                (0, Message::ModeCode(1, 6, None)),
+                (0, Message::ModeCode(1, 2, None)),
+                (0, Message::ModeCode(1, 7, None)),
+                (0, Message::ModeCode(1, 17, Some(0xbeef))),
+            ],
+            |rt| rt.terminal_flag = true,
+        );
+        let flags: Vec<u8> = bc_words(&sys_bus, ErrMsg::MsgEntSte)
+            .iter()
+            .map(|w| w.terminal_flag_bit())
+            .collect();
+        // "transmit status word" repeats the last status: the initial one, then
+        // the one of mode code 6 (flag inhibited)
+        assert_eq!(flags, vec![0, 0, 0, 1, 1]);
+        let rt = sys_bus.devices[1].lock().unwrap();
+        assert_eq!(rt.sync_word, 0xbeef);
+        assert!(rt.sync_time > 0);
+        assert_eq!(rt.state, State::Idle);
+    }
+
+    #[test]
+    fn test_status_flags() {
+        let sys_bus = eval_script(
+            1,
+            1,
+            vec![
+                (0, Message::ModeCode(1, 16, None)),
+                (0, Message::BC2RT(1, vec![1])),
+            ],
+            |rt| {
+                rt.set_flag(StatusFlag::ServiceRequest, true);
+                rt.set_flag(StatusFlag::SubsystemFlag, true);
+            },
+        );
+        let sts = bc_words(&sys_bus, ErrMsg::MsgEntSte);
+        // the service request is cleared once the vector word is sent
+        let sr: Vec<u8> = sts.iter().map(|w| w.service_request_bit()).collect();
+        assert_eq!(sr, vec![1, 0]);
+        assert!(sts.iter().all(|w| w.subsystem_flag_bit() == 1));
+        for w in sts {
+            let mut p = w;
+            p.calculate_parity_bit();
+            assert_eq!(p.parity_bit(), w.parity_bit());
+        }
+    }
+
+    #[test]
+    fn test_status_busy() {
+        let sys_bus = eval_script(
+            1,
+            1,
+            vec![(0, Message::RT2BC(1, 2)), (0, Message::BC2RT(1, vec![5]))],
+            |rt| rt.set_flag(StatusFlag::Busy, true),
+        );
+        let busy: Vec<u8> = bc_words(&sys_bus, ErrMsg::MsgEntSte)
+            .iter()
+            .map(|w| w.busy_bit())
+            .collect();
+        assert_eq!(busy, vec![1, 1]);
+        // no data is moved out of a busy RT, and the BC does not wait for it
+        assert!(bc_words(&sys_bus, ErrMsg::MsgEntDat).is_empty());
+        assert_eq!(sys_bus.devices[0].lock().unwrap().timeout_times, 0);
+    }
+
+    #[test]
+    fn test_message_error_illegal_mode_code() {
+        let sys_bus = eval_script(
+            1,
+            1,
+            vec![
+                (0, Message::ModeCode(1, 9, None)),
+                (0, Message::ModeCode(1, 2, None)),
+                (0, Message::ModeCode(1, 1, None)),
+            ],
+            |_| {},
+        );
+        let me: Vec<u8> = bc_words(&sys_bus, ErrMsg::MsgEntSte)
+            .iter()
+            .map(|w| w.message_errorbit())
+            .collect();
+        // kept for "transmit status word", cleared by the next valid command
+        assert_eq!(me, vec![1, 1, 0]);
+        assert!(!Word::new_mode_cmd(BROADCAST_ADDRESS, 2, TR::Transmit).is_legal_mode_cmd());
+        assert!(!Word::new_mode_cmd(1, 17, TR::Transmit).is_legal_mode_cmd());
+        assert!(Word::new_mode_cmd(BROADCAST_ADDRESS, 17, TR::Receive).is_legal_mode_cmd());
+    }
+
+    #[test]
+    fn test_message_error_word_count_and_parity() {
+        let mut sys_bus = System::new_virtual(2, 4_000, 0);
+        let mut d = sys_bus.new_device(1, Mode::RT, false);
+        let mut h = DefaultEventHandler {};
+        // two data words announced, a new command after the first one
+        h.on_cmd(&mut d, &mut Word::new_cmd(1, 2, TR::Receive));
+        h.on_dat(&mut d, &mut Word::new_data(7));
+        h.on_cmd(&mut d, &mut Word::new_cmd(1, 1, TR::Receive));
+        assert_eq!(d.last_status.message_errorbit(), 1);
+        // a complete message clears it
+        h.on_dat(&mut d, &mut Word::new_data(8));
+        assert_eq!(d.last_status.message_errorbit(), 0);
+        // a garbled data word, the status is suppressed
+        let n_written = d.write_queue.len();
+        h.on_cmd(&mut d, &mut Word::new_cmd(1, 1, TR::Receive));
+        h.on_err_parity(&mut d, &mut Word::new_data(9), 0, 0);
+        assert_eq!(d.last_status.message_errorbit(), 1);
+        assert_eq!(d.write_queue.len(), n_written);
+    }
+
+    #[test]
+    fn test_illegal_commands() {
+        let sys_bus = eval_script(
+            1,
+            1,
+            vec![
+                (0, Message::RT2BC(1, 2)),
+                (0, Message::BC2RT(1, vec![1, 2])),
+                (0, Message::ModeCode(1, 19, None)),
+                (0, Message::BC2RT(1, vec![3])),
+            ],
+            |rt| {
+                rt.illegal_cmds = Illegalization::from_json(
+                    r#"{"1": [{"tr": "Transmit", "sub_address": 1}, {"word_count": 2}, {"mode_code": 19}]}"#,
+                )
+                .unwrap()
+                .rules(1)
+            },
+        );
+        let me: Vec<u8> = bc_words(&sys_bus, ErrMsg::MsgEntSte)
+            .iter()
+            .map(|w| w.message_errorbit())
+            .collect();
+        assert_eq!(me, vec![1, 1, 1, 0]);
+        // no data is sent for the illegal transmit commands
+        assert!(bc_words(&sys_bus, ErrMsg::MsgEntDat).is_empty());
+        assert_eq!(sys_bus.devices[0].lock().unwrap().timeout_times, 0);
+        assert_eq!(rt_logs(&sys_bus, 1, ErrMsg::MsgEntCmdIlg).len(), 3);
+    }
+
+    fn timing_script() -> Vec<(u8, Message)> {
+        vec![
+            (0, Message::BC2RT(1, vec![1, 2])),
+            (0, Message::RT2BC(1, 2)),
+            (0, Message::RT2RT(1, 2, 1)),
+        ]
+    }
+
+    #[test]
+    fn test_timing_within_spec() {
+        let sys_bus = eval_script(1, 2, timing_script(), |_| {});
+        let bc = sys_bus.devices[0].lock().unwrap();
+        assert_eq!(bc.timing_violations(), vec![]);
+        assert_eq!(bc.timeout_times, 0);
+    }
+
+    #[test]
+    fn test_timing_short_gaps() {
+        let sys_bus = eval_script_on(System::new_virtual(3, 1_000, 0), 2, timing_script(), |_| {});
+        let violations = sys_bus.devices[0].lock().unwrap().timing_violations();
+        assert!(violations
+            .iter()
+            .any(|v| matches!(v, TimingViolation::ResponseTime { rt: 1, gap } if *gap < RESPONSE_TIME_MIN)));
+        assert!(violations
+            .iter()
+            .any(|v| matches!(v, TimingViolation::IntermessageGap { .. })));
+    }
+
+    #[test]
+    fn test_timing_no_response_recorded() {
+        // RT@1 answers after 20µs
+        let sys_bus = eval_script(1, 1, vec![(0, Message::RT2BC(1, 1))], |rt| {
+            rt.write_delays = 20_000
+        });
+        let bc = sys_bus.devices[0].lock().unwrap();
+        let violations = bc.timing_violations();
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0], TimingViolation::NoResponse { rt: 1 });
+        assert!(matches!(
+            violations[1],
+            TimingViolation::ResponseTime { rt: 1, gap } if gap > NO_RESPONSE_TIMEOUT
+        ));
+        // the late status is still taken
+        assert_eq!(bc.timeout_times, 0);
+        drop(bc);
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgEntDat).len(), 1);
+    }
+
+    #[test]
+    fn test_timing_no_response_enforced() {
+        let mut sys_bus = System::new_virtual(2, 4_000, 0);
+        sys_bus.set_timing_policy(TimingPolicy::Enforce);
+        let sys_bus = eval_script_on(sys_bus, 1, vec![(0, Message::RT2BC(1, 1))], |sys_bus| {
+            sys_bus.devices[1].lock().unwrap().write_delays = 20_000
+        });
+        let bc = sys_bus.devices[0].lock().unwrap();
+        assert_eq!(
+            bc.timing_violations()[0],
+            TimingViolation::NoResponse { rt: 1 }
+        );
+        assert_eq!(bc.timeout_times, 1);
+        assert!(bc
+            .logs
+            .iter()
+            .any(|l| matches!(l.event, ErrMsg::MsgBCTimeout(_))));
+        drop(bc);
+        assert!(bc_words(&sys_bus, ErrMsg::MsgEntDat).is_empty());
+    }
+
+    fn eval_jittered(seed: u64, response: Delay) -> System {
+        let mut sys_bus = System::new_virtual(2, 4_000, seed);
+        sys_bus.set_timing_profile(
+            1,
+            TimingProfile {
+                response,
+                ..TimingProfile::default()
+            },
+        );
+        let script = (0..20).map(|_| (0, Message::RT2BC(1, 2))).collect();
+        eval_script_on(sys_bus, 1, script, |_| {})
+    }
+
+    #[test]
+    fn test_timing_profiles() {
+        let jitter = Delay::Uniform {
+            min: 5_000,
+            max: 11_000,
+        };
+        let sys_bus = eval_jittered(1, jitter.clone());
+        assert_eq!(
+            sys_bus.devices[0].lock().unwrap().timing_violations(),
+            vec![]
+        );
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgEntDat).len(), 40);
+        // the draws are reproducible from the seed only
+        let times = |sys_bus: &System| -> Vec<u128> {
+            let rt = sys_bus.devices[1].lock().unwrap();
+            rt.logs.iter().map(|l| l.time).collect()
+        };
+        assert_eq!(times(&eval_jittered(1, jitter.clone())), times(&sys_bus));
+        assert_ne!(times(&eval_jittered(2, jitter)), times(&sys_bus));
+        // a slow RT
+        let sys_bus = eval_jittered(
+            1,
+            Delay::Normal {
+                mean: 20_000.0,
+                std_dev: 1_000.0,
+            },
+        );
+        let violations = sys_bus.devices[0].lock().unwrap().timing_violations();
+        assert!(violations.contains(&TimingViolation::NoResponse { rt: 1 }));
+    }
+
+    // with a BM, whose records stay out of the merged logs
+    fn eval_logged(
+        home_dir: &Path,
+        config: LogConfig,
+    ) -> (System, Receiver<LogRecord>, Receiver<LogRecord>) {
+        let _ = std::fs::remove_dir_all(home_dir);
+        let home_dir = home_dir.to_str().unwrap().to_owned();
+        let mut sys_bus = System::new_virtual_with_name(4, 4_000, home_dir, 0);
+        sys_bus.set_log_config(config);
+        let commands = sys_bus.subscribe_logs(LogFilter::default().kinds(["MsgEntCmdRcv"]), 1000);
+        let all = sys_bus.subscribe_logs(LogFilter::default(), 100_000);
+        let sys_bus = eval_script_on(sys_bus, 2, timing_script(), |sys_bus| {
+            sys_bus.run_d(
+                3,
+                Mode::BM,
+                Arc::new(Mutex::new(EventHandlerEmitter {
+                    handler: Box::new(DefaultEventHandler {}),
+                })),
+                false,
+            );
+        });
+        (sys_bus, commands, all)
+    }
+
+    #[test]
+    fn test_log_stream() {
+        let read_lines = |path: PathBuf| -> Vec<String> {
+            let mut lines: Vec<String> = std::fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .map(|l| l.to_owned())
+                .collect();
+            lines.sort();
+            lines
+        };
+        let dir = std::env::temp_dir().join("sv1dur_test_log_stream");
+        let (sys_bus, commands, all) = eval_logged(&dir.join("joined"), LogConfig::default());
+        assert!(sys_bus.log_stream_stats().is_none());
+        let joined = read_lines(dir.join("joined").join("sys_bus.log"));
+        assert_eq!(joined.len(), sys_bus.logs.len());
+        let n_commands = commands.try_iter().count();
+        assert!(n_commands > 0);
+        let n_bm = sys_bus.devices[3].lock().unwrap().logs.len();
+        assert!(n_bm > 0);
+        let joined_all: Vec<String> = all.try_iter().map(|l| format_log(&l)).collect();
+        assert_eq!(joined_all.len(), joined.len());
+        let bm_lines = read_lines(dir.join("joined").join("BM3-Idle.dat"));
+        let metrics = sys_bus.metrics();
+        assert!(metrics.messages > 0);
+        let report = std::fs::read_to_string(dir.join("joined").join("sys_bus.metrics.json"));
+        assert_eq!(
+            report.unwrap(),
+            serde_json::to_string_pretty(&metrics).unwrap() + "\n"
+        );
+
+        let config = LogConfig {
+            stream: true,
+            stream_capacity: 8,
+            retain: false,
+            ..LogConfig::default()
+        };
+        let (sys_bus, commands, all) = eval_logged(&dir.join("streamed"), config);
+        // same records, without keeping them in memory
+        assert_eq!(read_lines(dir.join("streamed").join("sys_bus.log")), joined);
+        let streamed_all: Vec<String> = all.try_iter().map(|l| format_log(&l)).collect();
+        assert_eq!(streamed_all, joined_all);
+        assert_eq!(
+            read_lines(dir.join("streamed").join("BM3-Idle.dat")),
+            bm_lines
+        );
+        assert!(sys_bus.logs.is_empty());
+        assert!(sys_bus.devices[0].lock().unwrap().logs.is_empty());
+        let received: Vec<LogRecord> = commands.try_iter().collect();
+        assert_eq!(received.len(), n_commands);
+        assert!(received.windows(2).all(|w| w[0].time <= w[1].time));
+        let stats = sys_bus.log_stream_stats().unwrap();
+        // the BM records are streamed to its own files only
+        assert_eq!(stats.records as usize, joined.len() + n_bm);
+        assert_eq!((stats.late, stats.errors), (0, 0));
+        assert!(stats.max_queue <= 8);
+        assert_eq!(sys_bus.metrics(), metrics);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    // answers the first command to RT@1 with `word` (after its write delay)
+    struct Jammer {
+        word: Option<Word>,
+    }
+
+    impl EventHandler for Jammer {
+        fn on_cmd(&mut self, d: &mut Device, w: &mut Word) {
+            if w.address() == 1 {
+                if let Some(word) = self.word.take() {
+                    d.write(word);
+                }
+            }
+        }
+    }
+
+    fn eval_jammed(phy: bool, word: Word, delay: u128) -> System {
+        let mut sys_bus = System::new_virtual(3, 4_000, 0);
+        sys_bus.set_physical_layer(phy);
+        eval_script_on(sys_bus, 1, vec![(0, Message::RT2BC(1, 1))], |sys_bus| {
+            sys_bus.run_d(
+                2,
+                Mode::RT,
+                Arc::new(Mutex::new(EventHandlerEmitter {
+                    handler: Box::new(Jammer { word: Some(word) }),
+                })),
+                true,
+            );
+            sys_bus.devices[2].lock().unwrap().write_delays = delay;
+        })
+    }
+
+    #[test]
+    fn test_physical_layer_collisions() {
+        // jamming the status word, at once: the sync is gone
+        let sys_bus = eval_jammed(true, Word::new_data(0), 4_000);
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgEntErrSync).len(), 1);
+        assert!(bc_words(&sys_bus, ErrMsg::MsgEntSte).is_empty());
+        // 15 bit times later: the status is garbled from there on
+        let sys_bus = eval_jammed(true, Word::new_data(0), 4_000 + 15 * phy::BIT_TIME);
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgEntErrMan(15)).len(), 1);
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgEntErrSync).len(), 1);
+        // a spoofed copy of the status goes through unnoticed
+        let sys_bus = eval_jammed(true, Word::new_status(1), 4_000);
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgEntDat).len(), 1);
+        assert_eq!(sys_bus.devices[0].lock().unwrap().timeout_times, 0);
+        // whole-word collisions without the physical layer
+        let sys_bus = eval_jammed(false, Word::new_status(1), 4_000);
+        assert!(bc_words(&sys_bus, ErrMsg::MsgEntDat).is_empty());
+        assert!(sys_bus.devices[0]
+            .lock()
+            .unwrap()
+            .logs
+            .iter()
+            .any(|l| matches!(l.event, ErrMsg::MsgEntErrPty(..))));
+    }
+
+    #[test]
+    fn test_fault_injection() {
+        // RT@1 is cut off for the first 10ms
+        let mut sys_bus = System::new_virtual(2, 4_000, 0);
+        sys_bus.set_faults(
+            FaultInjector::from_json(
+                r#"{"events": [{"start": 0, "end": 10000000, "fault": {"StubDisconnect": {"device": 1}}}]}"#,
+            )
+            .unwrap(),
+        );
+        let script = vec![(0, Message::RT2BC(1, 1)), (0, Message::RT2BC(1, 1))];
+        let sys_bus = eval_script_on(sys_bus, 1, script, |_| {});
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgFaultDrop(1)).len(), 1);
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgEntDat).len(), 1);
+        assert_eq!(sys_bus.devices[0].lock().unwrap().timeout_times, 1);
+
+        // reflections garble every word reaching the BC, and only the BC
+        let mut sys_bus = System::new_virtual(2, 4_000, 0);
+        sys_bus.set_faults(
+            FaultInjector::from_json(
+                r#"{"events": [{"start": 0, "end": null, "fault": {"Reflection": {"receivers": [0], "p": 1.0}}}]}"#,
+            )
+            .unwrap(),
+        );
+        let sys_bus = eval_script_on(sys_bus, 1, vec![(0, Message::BC2RT(1, vec![7]))], |_| {});
+        assert!(bc_words(&sys_bus, ErrMsg::MsgEntSte).is_empty());
+        let bc = sys_bus.devices[0].lock().unwrap();
+        let garbled: Vec<&Word> = bc
+            .logs
+            .iter()
+            .filter(|l| matches!(l.event, ErrMsg::MsgEntErrPty(..) | ErrMsg::MsgEntErrSync))
+            .map(|l| &l.word)
+            .collect();
+        assert_eq!(garbled.len(), 1);
+        assert_eq!(garbled[0].fault(), FaultLabel::Reflection);
+        drop(bc);
+        assert_eq!(rt_logs(&sys_bus, 1, ErrMsg::MsgEntDat).len(), 1);
+    }
+
+    #[test]
+    fn test_mode_code_transmitter_shutdown() {
+        let sys_bus = eval_script(
+            2,
+            1,
+            vec![
+                // shut down bus B from bus A, B is then silent
+                (0, Message::ModeCode(1, 4, None)),
+                (1, Message::RT2BC(1, 1)),
+                // override it from bus A
+                (0, Message::ModeCode(1, 5, None)),
+                (1, Message::RT2BC(1, 1)),
+                // selected transmitter shutdown of bus A, then reset
+                (1, Message::ModeCode(1, 20, Some(0))),
+                (0, Message::RT2BC(1, 1)),
+                (1, Message::ModeCode(1, 8, None)),
+                (0, Message::RT2BC(1, 1)),
+            ],
+            |_| {},
+        );
+        // both silent transfers succeed when retried on the other bus
+        let bc = sys_bus.devices[0].lock().unwrap();
+        assert_eq!(bc.timeout_times, 2);
+        drop(bc);
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgBCRetry(0)).len(), 1);
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgBCRetry(1)).len(), 1);
+        let rt = sys_bus.devices[1].lock().unwrap();
+        assert_eq!(rt.tx_shutdown, vec![false, false]);
+        assert_eq!(rt.state, State::Idle);
+    }
+
+    #[test]
+    fn test_mode_code_shutdown_single_bus() {
+        let sys_bus = eval_script(1, 1, vec![(0, Message::ModeCode(1, 4, None))], |_| {});
+        assert_eq!(sys_bus.devices[1].lock().unwrap().state, State::Off);
+    }
+
+    fn rt_logs(sys_bus: &System, rt: usize, msg: ErrMsg) -> Vec<Word> {
+        let rt = sys_bus.devices[rt].lock().unwrap();
+        rt.logs
+            .iter()
+            .filter(|l| l.event == msg)
+            .map(|l| l.word)
+            .collect()
+    }
+
+    #[test]
+    fn test_broadcast_bc2rt() {
+        let sys_bus = eval_script(
+            1,
+            2,
+            vec![
+                (0, Message::BC2RT(BROADCAST_ADDRESS, vec![4, 5])),
+                (0, Message::ModeCode(2, 18, None)),
+                (0, Message::ModeCode(1, 2, None)),
+                (0, Message::RT2BC(1, 1)),
+                (0, Message::ModeCode(1, 2, None)),
+            ],
+            |_| {},
+        );
+        for rt in 1..=2 {
+            assert_eq!(rt_logs(&sys_bus, rt, ErrMsg::MsgEntDat).len(), 2);
+        }
+        let sts = bc_words(&sys_bus, ErrMsg::MsgEntSte);
+        let brdcst: Vec<u8> = sts.iter().map(|w| w.brdcst_received_bit()).collect();
+        // kept until the next message to the RT
+        assert_eq!(brdcst, vec![1, 1, 0, 0]);
+        // the last command of RT@2 is the broadcast
+        let data = bc_words(&sys_bus, ErrMsg::MsgEntDat);
+        assert_eq!(
+            data[0].data(),
+            Word::new_cmd(BROADCAST_ADDRESS, 2, TR::Receive).data()
+        );
+        assert_eq!(sys_bus.devices[0].lock().unwrap().timeout_times, 0);
+    }
+
+    #[test]
+    fn test_rt2broadcast() {
+        let sys_bus = eval_script(
+            1,
+            3,
+            vec![
+                (0, Message::RT2RT(1, BROADCAST_ADDRESS, 3)),
+                (0, Message::RT2BC(3, 1)),
+            ],
+            |_| {},
+        );
+        // only the transmitter and RT@3 (asked afterwards) talk
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgEntSte).len(), 2);
+        let rt2 = sys_bus.devices[2].lock().unwrap();
+        assert!(!rt2
+            .logs
+            .iter()
+            .any(|l| matches!(l.event, ErrMsg::MsgWrt(_))));
+        drop(rt2);
+        for rt in 2..=3 {
+            assert_eq!(rt_logs(&sys_bus, rt, ErrMsg::MsgEntDat).len(), 3);
+        }
+        // the BC listens to the data too
+        assert_eq!(bc_words(&sys_bus, ErrMsg::MsgEntDat).len(), 3 + 1);
+        assert_eq!(sys_bus.devices[0].lock().unwrap().timeout_times, 0);
+    }
+
+    // BC polling RT@1 on a dual-redundant bus; a BM sits at address 2.
+    fn eval_dual_bus(rt_bus: Option<u8>) -> System {
+        let rt_handler: Box<dyn EventHandler> = match rt_bus {
+            Some(bus) => Box::new(BusBound {
+                bus,
+                inner: Box::new(DefaultEventHandler {}),
+            }),
+            None => Box::new(DefaultEventHandler {}),
+        };
+        eval_dual_bus_rt(rt_handler)
+    }
+
+    fn eval_dual_bus_rt(rt_handler: Box<dyn EventHandler>) -> System {
+        let mut sys_bus = System::new_virtual(3, 4_000, 0);
+        sys_bus.set_n_buses(2);
+        let handlers: Vec<(Mode, Box<dyn EventHandler>)> = vec![
+            (
+                Mode::BC,
+                Box::new(DefaultBCEventHandler {
+                    total_device: 2,
+                    target: 0,
+                    data: vec![1, 2, 3],
+                    proto: Proto::RT2BC,
+                    proto_rotate: false,
+                }),
+            ),
+            (Mode::RT, rt_handler),
+            (Mode::BM, Box::new(DefaultEventHandler {})),
+        ];
+        for (m, (mode, handler)) in handlers.into_iter().enumerate() {
+            sys_bus.run_d(
+                m as u8,
+                mode,
+                Arc::new(Mutex::new(EventHandlerEmitter { handler })),
+                false,
+            );
+        }
+        sys_bus.go();
+        sys_bus.sleep_ms(100);
+        sys_bus.stop();
+        sys_bus.join();
+        sys_bus
+    }
+
+    fn bm_buses(sys_bus: &System) -> Vec<u8> {
+        let bm = sys_bus.devices[2].lock().unwrap();
+        bm.logs
+            .iter()
+            .filter(|l| l.event == ErrMsg::MsgBMLog)
+            .map(|l| l.word.bus())
+            .collect()
+    }
+
+    #[test]
+    fn test_dual_bus_primary() {
+        let sys_bus = eval_dual_bus(None);
+        let buses = bm_buses(&sys_bus);
+        assert!(buses.len() > 100);
+        // everything stays on bus A, the RT answering on the bus it was asked on
+        assert!(buses.iter().all(|b| *b == 0));
+        assert_eq!(sys_bus.devices[0].lock().unwrap().timeout_times, 0);
+    }
+
+    #[test]
+    fn test_dual_bus_retry_on_alternate_bus() {
+        let sys_bus = eval_dual_bus(Some(1));
+        let bc = sys_bus.devices[0].lock().unwrap();
+        // the RT (bound to bus B) answers the retry, and everything after
+        assert_eq!(bc.timeout_times, 1);
+        assert!(bc.logs.iter().any(|l| l.event == ErrMsg::MsgBCRetry(1)));
+        assert_eq!(bc.bus, 1);
+        drop(bc);
+        let buses = bm_buses(&sys_bus);
+        assert!(buses.contains(&0));
+        assert!(buses.contains(&1));
+    }
+
+    // sends its status but none of the data on bus A
+    struct SilentDataOnBusA {}
+
+    impl EventHandler for SilentDataOnBusA {
+        fn on_data_write(&mut self, d: &mut Device, dword_count: u8) {
+            if d.bus != 0 {
+                self.default_on_data_write(d, dword_count);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dual_bus_retry_after_data_timeout() {
+        let sys_bus = eval_dual_bus_rt(Box::new(SilentDataOnBusA {}));
+        // the status came, the data did not: retried on bus B
+        assert_eq!(
+            recovery_events(&sys_bus)[0],
+            RecoveryEvent::Retry {
+                rt: 1,
+                cause: Failure::Timeout,
+                attempt: 1,
+                bus: 1,
+                delay: 0,
+            }
+        );
+        let bc = sys_bus.devices[0].lock().unwrap();
+        assert_eq!(bc.timeout_times, 1);
+        assert_eq!(bc.bus, 1);
+        drop(bc);
+        assert!(bc_words(&sys_bus, ErrMsg::MsgEntDat).len() > 3);
+    }
+
+    fn recovery_events(sys_bus: &System) -> Vec<RecoveryEvent> {
+        let bc = sys_bus.devices[0].lock().unwrap();
+        bc.logs
+            .iter()
+            .filter_map(|l| match &l.event {
+                ErrMsg::MsgRecovery(e) => Some(e.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_retry_policy_escalation() {
+        let mut sys_bus = System::new_virtual(2, 4_000, 0);
+        let policy = RetryPolicy::new(2, RetryBus::Same)
+            .delay(100_000)
+            .escalate(Escalation::StatusPoll)
+            .escalate(Escalation::ResetRT)
+            .escalate(Escalation::MarkFailed);
+        sys_bus.set_retry_policy(policy);
+        // nobody at 5
+        let script = vec![
+            (0, Message::RT2BC(5, 2)),
+            (0, Message::BC2RT(5, vec![1])),
+            (0, Message::BC2RT(1, vec![1])),
+        ];
+        let sys_bus = eval_script_on(sys_bus, 1, script, |_| {});
+        let retry = |attempt| RecoveryEvent::Retry {
+            rt: 5,
+            cause: Failure::Timeout,
+            attempt,
+            bus: 0,
+            delay: 100_000,
+        };
+        let escalate = |step| RecoveryEvent::Escalate {
+            rt: 5,
+            cause: Failure::Timeout,
+            step,
+        };
+        assert_eq!(
+            recovery_events(&sys_bus),
+            vec![
+                retry(1),
+                retry(2),
+                escalate(Escalation::StatusPoll),
+                escalate(Escalation::ResetRT),
+                escalate(Escalation::MarkFailed),
+                RecoveryEvent::Skipped(5),
+            ]
+        );
+        let bc = sys_bus.devices[0].lock().unwrap();
+        let times = |e: ErrMsg| -> Vec<u128> {
+            bc.logs
+                .iter()
+                .filter(|l| l.event == e)
+                .map(|l| l.time)
+                .collect()
+        };
+        let (decided, retried) = (
+            times(ErrMsg::MsgRecovery(retry(1))),
+            times(ErrMsg::MsgBCRetry(0)),
+        );
+        assert_eq!(retried.len(), 2);
+        assert!(retried[0] >= decided[0] + 100_000);
+        // the status poll and the reset, then RT 1 is still served
+        let commands: Vec<Word> = bc
+            .logs
+            .iter()
+            .filter(|l| l.is_bc_command())
+            .map(|l| l.word)
+            .collect();
+        let mode_codes: Vec<u8> = commands
+            .iter()
+            .filter(|w| w.is_mode_cmd() && w.address() == 5)
+            .map(|w| w.mode_code())
+            .collect();
+        assert_eq!(mode_codes, vec![2, 8]);
+        assert_eq!(commands.last().unwrap().address(), 1);
+        assert!(bc.recovery.failed.contains(&5));
+        drop(bc);
+        let metrics = sys_bus.metrics();
+        assert_eq!((metrics.retries, metrics.escalations), (2, 3));
+    }
+
+    #[test]
+    fn test_retry_on_message_error() {
+        let mut sys_bus = System::new_virtual(2, 4_000, 0);
+        sys_bus.set_retry_policy(RetryPolicy::new(1, RetryBus::Same).on_message_error(true));
+        let script = vec![
+            (0, Message::BC2RT(1, vec![1, 2])),
+            (0, Message::BC2RT(1, vec![3])),
+        ];
+        let sys_bus = eval_script_on(sys_bus, 1, script, |sys_bus| {
+            sys_bus.devices[1].lock().unwrap().illegal_cmds =
+                Illegalization::from_json(r#"{"1": [{"word_count": 2}]}"#)
+                    .unwrap()
+                    .rules(1)
+        });
+        let me: Vec<u8> = bc_words(&sys_bus, ErrMsg::MsgEntSte)
+            .iter()
+            .map(|w| w.message_errorbit())
+            .collect();
+        assert_eq!(me, vec![1, 1, 0]);
+        assert_eq!(
+            recovery_events(&sys_bus),
+            vec![
+                RecoveryEvent::Retry {
+                    rt: 1,
+                    cause: Failure::MessageError,
+                    attempt: 1,
+                    bus: 0,
+                    delay: 0,
+                },
+                RecoveryEvent::GiveUp {
+                    rt: 1,
+                    cause: Failure::MessageError,
+                },
+            ]
+        );
+    }
+
+    // the BC sends data to RTs 1 and 2 in turn
+    fn bc2rt_system(builder: SystemBuilder) -> System {
+        let bc = Box::new(DefaultBCEventHandler {
+            total_device: 3,
+            target: 0,
+            data: vec![1, 2, 3],
+            proto: Proto::BC2RT,
+            proto_rotate: false,
+        });
+        builder.test_devices(bc).build().unwrap()
+    }
+
+    // polls the snapshots of a running BC2RT system
+    fn eval_snapshots(builder: SystemBuilder) {
+        let mut sys_bus = bc2rt_system(builder);
+        let idle = sys_bus.snapshot();
+        assert_eq!(idle.len(), 3);
+        assert!(idle.iter().all(|s| s.counters == DeviceCounters::default()));
+        sys_bus.go();
+        sys_bus.sleep_ms(100);
+        // the device threads hold their locks, the snapshots are still there
+        let first = sys_bus.snapshot();
+        assert!(first[0].counters.words_written > 0);
+        assert_eq!(first[1].mode, Mode::RT);
+        sys_bus.sleep_ms(50);
+        let second = sys_bus.snapshot();
+        assert!(second[0].time > first[0].time);
+        assert!(second[0].counters.words_written > first[0].counters.words_written);
+        sys_bus.stop();
+        sys_bus.join();
+        for (snapshot, device) in sys_bus.snapshot().iter().zip(&sys_bus.devices) {
+            let device = device.lock().unwrap();
+            assert_eq!(snapshot.counters.logs as usize, device.logs.len());
+            assert_eq!(snapshot.state, device.state);
+        }
+    }
+
+    #[test]
+    fn test_snapshot() {
+        eval_snapshots(SystemBuilder::new(4_000).virtual_time(0));
+        eval_snapshots(SystemBuilder::new(4_000));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut sys_bus = bc2rt_system(SystemBuilder::new(4_000).virtual_time(0));
+        let id = sys_bus.add_breakpoint(Breakpoint::CommandTo(2));
+        let hit = sys_bus.run_until_break(50).unwrap();
+        assert_eq!(hit.id, Some(id));
+        assert!(hit.record.is_bc_command() && hit.record.word.address() == 2);
+        // frozen: no time passes, nothing is logged
+        let n_logs = sys_bus.devices[0].lock().unwrap().logs.len();
+        let now = sys_bus.clock.elapsed();
+        sys_bus.sleep_ms(10);
+        assert_eq!(sys_bus.clock.elapsed(), now);
+        assert_eq!(sys_bus.devices[0].lock().unwrap().logs.len(), n_logs);
+        assert_eq!(sys_bus.snapshot()[0].counters.logs as usize, n_logs);
+
+        // the data words of the command, one at a time
+        sys_bus.remove_breakpoint(id);
+        for _ in 0..3 {
+            let hit = sys_bus.step_word().unwrap();
+            assert_eq!(hit.record.mode, Mode::BC);
+            assert_eq!(hit.record.word.sync(), SYNC_DATA);
+        }
+        // then the status of RT2
+        let hit = sys_bus.step_word().unwrap();
+        assert_eq!(hit.record.device, 2);
+        assert_eq!(hit.record.word.all(), Word::new_status(2).all());
+        let hit = sys_bus.step_message().unwrap();
+        assert_eq!(hit.record.event, ErrMsg::MsgBCReady);
+        assert!(sys_bus.is_paused());
+
+        sys_bus.add_breakpoint(Breakpoint::EnterState(State::AwtData));
+        let hit = sys_bus.run_until_break(50).unwrap();
+        assert_eq!(hit.record.mode, Mode::RT);
+        assert_eq!(hit.record.state, State::AwtData);
+        sys_bus.clear_breakpoints();
+        assert!(sys_bus.run_until_break(10).is_none());
+        sys_bus.stop();
+        sys_bus.join();
+    }
+
+    #[test]
+    fn test_pause() {
+        // a BM (device 0) logs every word it takes
+        let bm = DeviceSpec::new(3, Mode::BM, Box::new(DefaultEventHandler {}));
+        let mut sys_bus = bc2rt_system(SystemBuilder::new(4_000).device(bm));
+        sys_bus.go();
+        sys_bus.sleep_ms(50);
+        sys_bus.pause();
+        let frozen = sys_bus.snapshot();
+        // the clock of the devices stops too
+        let now = sys_bus.clock.elapsed();
+        sys_bus.sleep_ms(50);
+        assert_eq!(sys_bus.clock.elapsed(), now);
+        let counters = |s: &Vec<DeviceSnapshot>| s.iter().map(|s| s.counters).collect::<Vec<_>>();
+        assert_eq!(counters(&sys_bus.snapshot()), counters(&frozen));
+        // the device threads let go of their devices
+        assert!(sys_bus.devices.iter().all(|d| d.try_lock().is_ok()));
+        // a word still being written by a device that has yet to park is
+        // taken all the same
+        let late = Word::new_data(0xbeef);
+        let sent = sys_bus.transmitters[0].send_timeout(late, Duration::from_millis(100));
+        assert!(sent.is_ok());
+        sys_bus.resume();
+        sys_bus.sleep_ms(50);
+        sys_bus.stop();
+        sys_bus.join();
+        let words = |s: &Vec<DeviceSnapshot>| s[1].counters.words_written;
+        assert!(words(&sys_bus.snapshot()) > words(&frozen));
+        let bm = sys_bus.devices[0].lock().unwrap();
+        assert!(bm.logs.iter().any(|l| l.word.data() == 0xbeef));
+    }
+
+    #[test]
+    fn test_power() {
+        // the BC polls RTs 1 to 3, RT3 is plugged in later
+        let bc = Box::new(DefaultBCEventHandler {
+            total_device: 4,
+            target: 0,
+            data: vec![1, 2],
+            proto: Proto::BC2RT,
+            proto_rotate: false,
+        });
+        let mut sys_bus = SystemBuilder::test_bus(bc).build().unwrap();
+        let powered = |sys_bus: &System, id: usize, e: PowerEvent| {
+            let d = sys_bus.devices[id].lock().unwrap();
+            d.logs
+                .iter()
+                .any(|l| l.event == ErrMsg::MsgPower(e.clone()))
+        };
+        sys_bus.go();
+        sys_bus.sleep_ms(200);
+        assert_eq!(sys_bus.power(2), Some(Power::On));
+
+        sys_bus.power_off(2).unwrap();
+        assert!(powered(&sys_bus, 2, PowerEvent::Off));
+        let before = sys_bus.snapshot();
+        sys_bus.sleep_ms(20);
+        let off = sys_bus.snapshot();
+        assert_eq!(off[2].power, Power::Off);
+        assert_eq!(off[2].state, State::Off);
+        assert_eq!(off[2].counters.logs, before[2].counters.logs);
+        assert!(off[0].counters.timeouts > before[0].counters.timeouts);
+
+        // silent while booting, then back
+        sys_bus.power_on(2, 5_000_000).unwrap();
+        sys_bus.sleep_ms(2);
+        assert_eq!(sys_bus.power(2), Some(Power::Booting));
+        assert_eq!(
+            sys_bus.snapshot()[2].counters.words_written,
+            off[2].counters.words_written
+        );
+        sys_bus.sleep_ms(10);
+        assert_eq!(sys_bus.power(2), Some(Power::On));
+        assert!(powered(&sys_bus, 2, PowerEvent::Booted));
+        assert!(sys_bus.snapshot()[2].counters.words_written > off[2].counters.words_written);
+
+        sys_bus.fail(1).unwrap();
+        assert!(powered(&sys_bus, 1, PowerEvent::Failed));
+        assert!(sys_bus.power_on(1, 0).is_err());
+        assert!(sys_bus.power_off(7).is_err());
+        assert_eq!(sys_bus.power(7), None);
+
+        let id = sys_bus.plug(DeviceSpec::new(
+            3,
+            Mode::RT,
+            Box::new(DefaultEventHandler {}),
+        ));
+        assert_eq!(id, 3);
+        assert!(powered(&sys_bus, 3, PowerEvent::Plugged));
+        sys_bus.sleep_ms(10);
+        let plugged = &sys_bus.snapshot()[3];
+        assert!(plugged.counters.commands > 0 && plugged.counters.words_written > 0);
+        sys_bus.stop();
+        sys_bus.join();
+    }
+
+    #[test]
+    fn test_dynamic_bus_control() {
+        let mut sys_bus = System::new_virtual(3, 4_000, 0);
+        let script = |script: Vec<Message>| {
+            let bc: Box<dyn EventHandler> = Box::new(ScriptBC {
+                script: script.into_iter().map(|m| (0, m)).collect(),
+            });
+            Arc::new(Mutex::new(EventHandlerEmitter { handler: bc }))
+        };
+        // RT2 refuses, RT1 takes control, sends its message and hands it back
+        let bc = script(vec![
+            Message::ModeCode(2, 0, None),
+            Message::ModeCode(1, 0, None),
+            Message::BC2RT(2, vec![9]),
+        ]);
+        sys_bus.run_d(0, Mode::BC, bc, false);
+        let backup = script(vec![
+            Message::BC2RT(2, vec![7]),
+            Message::ModeCode(0, 0, None),
+        ]);
+        sys_bus.run_d(1, Mode::RT, backup, false);
+        sys_bus.devices[1].lock().unwrap().bc_capable = true;
+        let rt = Arc::new(Mutex::new(EventHandlerEmitter {
+            handler: Box::new(DefaultEventHandler {}),
+        }));
+        sys_bus.run_d(2, Mode::RT, rt, false);
+        sys_bus.go();
+        sys_bus.sleep_ms(200);
+        sys_bus.stop();
+        sys_bus.join();
+
+        let accepted: Vec<u8> = bc_words(&sys_bus, ErrMsg::MsgEntSte)
+            .iter()
+            .map(|w| w.dynamic_bus_control_accpt_bit())
+            .collect();
+        assert_eq!(accepted[..2], [0, 1]);
+        let control = |id: usize| -> Vec<BusControl> {
+            let d = sys_bus.devices[id].lock().unwrap();
+            d.logs
+                .iter()
+                .filter_map(|l| match l.event {
+                    ErrMsg::MsgBusCtrl(c) => Some(c),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(control(0), vec![BusControl::Released, BusControl::Accepted]);
+        assert_eq!(control(1), vec![BusControl::Accepted, BusControl::Released]);
+        assert_eq!(control(2), vec![]);
+        let rt = sys_bus.devices[2].lock().unwrap();
+        let data: Vec<u32> = rt
+            .logs
+            .iter()
+            .filter(|l| l.event == ErrMsg::MsgEntDat)
+            .map(|l| l.word.data())
+            .collect();
+        assert_eq!(data, vec![7, 9]);
+        drop(rt);
+        assert_eq!(sys_bus.devices[0].lock().unwrap().mode, Mode::BC);
+        assert_eq!(sys_bus.devices[1].lock().unwrap().mode, Mode::RT);
+    }
+
+    #[test]
+    fn test_backup_bc_takeover() {
+        let bc = |proto| {
+            Box::new(DefaultBCEventHandler {
+                total_device: 3,
+                target: 0,
+                data: vec![1, 2],
+                proto,
+                proto_rotate: false,
+            })
+        };
+        let mut sys_bus = SystemBuilder::test_bus(bc(Proto::BC2RT))
+            .device(DeviceSpec::new(3, Mode::RT, bc(Proto::RT2BC)).backup(2_000_000))
+            .build()
+            .unwrap();
+        sys_bus.go();
+        // the bus is never silent for long while the BC runs
+        sys_bus.sleep_ms(100);
+        let running = sys_bus.snapshot();
+        assert_eq!(running[3].mode, Mode::RT);
+
+        sys_bus.power_off(0).unwrap();
+        sys_bus.sleep_ms(50);
+        let taken = sys_bus.snapshot();
+        assert_eq!(taken[3].mode, Mode::BC);
+        let backup = sys_bus.devices[3].lock().unwrap();
+        let takeover = backup
+            .logs
+            .iter()
+            .find(|l| l.event == ErrMsg::MsgBusCtrl(BusControl::Takeover))
+            .unwrap()
+            .time;
+        let off = sys_bus.devices[0].lock().unwrap().logs.last().unwrap().time;
+        assert!(takeover > off + 2_000_000);
+        drop(backup);
+        // RT1 and RT2 now serve the backup
+        for rt in 1..3 {
+            assert!(taken[rt].counters.words_written > running[rt].counters.words_written);
+        }
+        sys_bus.stop();
+        sys_bus.join();
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut sys_bus = bc2rt_system(SystemBuilder::new(4_000).virtual_time(0));
+        sys_bus.go();
+        sys_bus.sleep_ms(100);
+        sys_bus.stop();
+        sys_bus.join();
+        let m = sys_bus.metrics();
+        let bc = sys_bus.devices[0].lock().unwrap();
+        assert_eq!(m.timeouts, bc.timeout_times as u64);
+        drop(bc);
+        assert_eq!(m.messages as usize, sys_bus.logs.messages().count());
+        // the BC writes to RT1 and RT2 in turn, three data words each
+        let sa = SA_DEFAULT;
+        for rt in 1..3 {
+            let traffic = m.traffic[&rt][&sa];
+            assert_eq!(traffic.words, 3 * traffic.messages);
+        }
+        let n_messages: u64 = m
+            .traffic
+            .values()
+            .flat_map(|sa| sa.values())
+            .map(|t| t.messages)
+            .sum();
+        assert_eq!(n_messages, m.messages);
+        let latency = &m.latency["BC2RT"];
+        assert!(latency.count > 0 && latency.min > 4 * RT_WORD_LOAD_TIME);
+        assert_eq!(latency.buckets.values().sum::<u64>(), latency.count);
+        let utilization = m.bus_utilization[&'A'];
+        assert!(utilization > 0.0 && utilization < 100.0);
+        assert!(m.attack_words.is_empty());
+    }
 }
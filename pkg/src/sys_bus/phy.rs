@@ -0,0 +1,149 @@
+use crate::sys_bus::{Word, RT_WORD_LOAD_TIME, SYNC_CMD_STS, SYNC_DATA};
+
+// 1 Mbit/s: a word is 3 bit times of sync, 16 bits and the parity bit
+pub const BIT_TIME: u128 = RT_WORD_LOAD_TIME / WORD_BITS as u128;
+pub const HALF_BIT_TIME: u128 = BIT_TIME / 2;
+pub const WORD_BITS: usize = 20;
+pub const WORD_HALF_BITS: usize = 2 * WORD_BITS;
+// bits 0 to 19 of a `Word` go on the wire, the rest is bookkeeping
+const WIRE_MASK: u32 = (1 << WORD_BITS) - 1;
+
+/// What a receiver could not decode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PhyError {
+    // neither the command/status nor the data sync waveform
+    Sync,
+    // no mid-bit transition in the given bit time
+    Manchester(u8),
+    // decoded, but the odd parity does not hold
+    Parity,
+}
+
+/// Line levels of a word, one per half bit time: +1 / -1 driven high / low,
+/// 0 when the drivers cancel out. Several transmitters add up.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Waveform {
+    pub levels: Vec<i8>,
+}
+
+impl Waveform {
+    /// Manchester II encoding (a one is high then low) after the sync, which
+    /// changes level once in the middle of its 3 bit times.
+    pub fn encode(w: &Word) -> Waveform {
+        let s = |i: u8| if (w.sync() >> i) & 1 == 1 { 1 } else { -1 };
+        let mut levels = Vec::with_capacity(WORD_HALF_BITS);
+        levels.extend([s(0), s(0), s(1), s(2), s(2), s(2)]);
+        for b in 3..WORD_BITS {
+            let one = (w.0 >> b) & 1 == 1;
+            levels.extend(if one { [1, -1] } else { [-1, 1] });
+        }
+        Waveform { levels }
+    }
+
+    /// Adds a word that started `offset` ns after this one; only the part
+    /// overlapping this word is kept.
+    pub fn superimpose(&mut self, other: &Waveform, offset: u128) {
+        let skip = (offset / HALF_BIT_TIME) as usize;
+        for (l, o) in self.levels.iter_mut().skip(skip).zip(other.levels.iter()) {
+            *l = (*l + *o).signum();
+        }
+    }
+
+    /// Inverts bit time `bit` (0 to 2 being the sync).
+    #[allow(unused)]
+    pub fn flip_bit(&mut self, bit: usize) {
+        for l in self.levels.iter_mut().skip(2 * bit).take(2) {
+            *l = -*l;
+        }
+    }
+
+    /// Decodes the wire bits; `template` provides the bookkeeping bits (bus,
+    /// attack type) of the result.
+    pub fn decode(&self, template: &Word) -> Result<Word, PhyError> {
+        let l = &self.levels;
+        let sync = match l[..6] {
+            [1, 1, 1, -1, -1, -1] => SYNC_CMD_STS,
+            [-1, -1, -1, 1, 1, 1] => SYNC_DATA,
+            _ => return Err(PhyError::Sync),
+        };
+        let mut raw = sync as u32;
+        for b in 3..WORD_BITS {
+            match (l[2 * b], l[2 * b + 1]) {
+                (1, -1) => raw |= 1 << b,
+                (-1, 1) => {}
+                _ => return Err(PhyError::Manchester(b as u8)),
+            }
+        }
+        let mut w = Word((template.0 & !WIRE_MASK) | raw);
+        let parity = w.parity_bit();
+        w.calculate_parity_bit();
+        if w.parity_bit() != parity {
+            return Err(PhyError::Parity);
+        }
+        Ok(w)
+    }
+}
+
+/// Checks a word received without the physical layer the way the decoder
+/// would (a garbled sync or a wrong parity).
+pub fn check(w: &Word) -> Result<(), PhyError> {
+    if w.sync() != SYNC_CMD_STS && w.sync() != SYNC_DATA {
+        return Err(PhyError::Sync);
+    }
+    let mut p = *w;
+    p.calculate_parity_bit();
+    match p.parity_bit() == w.parity_bit() {
+        true => Ok(()),
+        false => Err(PhyError::Parity),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::TR;
+
+    #[test]
+    fn test_manchester_round_trip() {
+        let mut cmd = Word::new_cmd(5, 3, TR::Transmit);
+        cmd.set_bus(1);
+        for w in [cmd, Word::new_status(7), Word::new_data(0xa5a5)] {
+            let wave = Waveform::encode(&w);
+            assert_eq!(wave.levels.len(), WORD_HALF_BITS);
+            let d = wave.decode(&w).unwrap();
+            assert_eq!(d.0, w.0);
+        }
+        assert_eq!(BIT_TIME, 1_000);
+    }
+
+    #[test]
+    fn test_manchester_errors() {
+        let w = Word::new_data(0x1234);
+        let mut wave = Waveform::encode(&w);
+        wave.flip_bit(1);
+        assert_eq!(wave.decode(&w).err(), Some(PhyError::Sync));
+        // a single flipped bit is a parity error, two go unnoticed
+        let mut wave = Waveform::encode(&w);
+        wave.flip_bit(4);
+        assert_eq!(wave.decode(&w).err(), Some(PhyError::Parity));
+        wave.flip_bit(5);
+        assert!(wave.decode(&w).is_ok());
+        let mut wave = Waveform::encode(&w);
+        wave.levels[9] = -wave.levels[9];
+        assert_eq!(wave.decode(&w).err(), Some(PhyError::Manchester(4)));
+    }
+
+    #[test]
+    fn test_superimpose() {
+        let a = Word::new_data(0);
+        let mut wave = Waveform::encode(&a);
+        // a word starting in the last bit time only hits the parity bit
+        wave.superimpose(&Waveform::encode(&Word::new_status(3)), 19 * BIT_TIME);
+        assert_eq!(wave.decode(&a).err(), Some(PhyError::Manchester(19)));
+        // the same word sent twice at once is still readable
+        let mut wave = Waveform::encode(&a);
+        wave.superimpose(&Waveform::encode(&a), 0);
+        assert!(wave.decode(&a).is_ok());
+    }
+}
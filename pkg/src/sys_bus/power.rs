@@ -0,0 +1,123 @@
+use crate::sys_bus::Word;
+use crossbeam_channel::Sender;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Supply of a device.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Power {
+    // also a slot no device is plugged in yet
+    Off = 0,
+    On = 1,
+    // powered, silent until the end of its boot delay
+    Booting = 2,
+    // for good
+    Failed = 3,
+}
+
+impl From<u8> for Power {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Power::On,
+            2 => Power::Booting,
+            3 => Power::Failed,
+            _ => Power::Off,
+        }
+    }
+}
+
+/// Power events logged by a device (`ErrMsg::MsgPower`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PowerEvent {
+    // added to a running system
+    Plugged,
+    Off,
+    // switched on, with its boot delay (ns)
+    On(u128),
+    Booted,
+    Failed,
+}
+
+/// Power of a device, switched by the system and read without locking the
+/// device (by the device itself and by those writing to it).
+#[derive(Debug, Default)]
+pub struct PowerLine {
+    power: AtomicU8,
+    // end of the boot (device clock, ns)
+    boot_until: AtomicU64,
+}
+
+impl PowerLine {
+    pub fn power(&self) -> Power {
+        self.power.load(Ordering::SeqCst).into()
+    }
+
+    /// Words sent to the device now are received.
+    pub fn listening(&self) -> bool {
+        self.power() == Power::On
+    }
+
+    pub fn set(&self, power: Power) {
+        self.power.store(power as u8, Ordering::SeqCst);
+    }
+
+    pub fn boot(&self, until: u128) {
+        self.boot_until.store(until as u64, Ordering::SeqCst);
+        self.set(Power::Booting);
+    }
+
+    /// End of the boot in progress.
+    pub fn boot_until(&self) -> Option<u128> {
+        match self.power() {
+            Power::Booting => Some(self.boot_until.load(Ordering::SeqCst) as u128),
+            _ => None,
+        }
+    }
+
+    /// Turns a boot over by `now` into `On` (unless switched meanwhile).
+    pub fn finish_boot(&self, now: u128) {
+        if self.boot_until().is_some_and(|until| now >= until) {
+            let _ = self.power.compare_exchange(
+                Power::Booting as u8,
+                Power::On as u8,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            );
+        }
+    }
+}
+
+/// Where a device writes to another one.
+#[derive(Clone, Debug)]
+pub struct Port {
+    pub tx: Sender<Word>,
+    pub power: Arc<PowerLine>,
+}
+
+/// Ports of all the devices by id, shared so that devices plugged in later
+/// are reached by the running ones.
+pub type Ports = Arc<RwLock<Vec<Port>>>;
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_power_line() {
+        let line = PowerLine::default();
+        assert_eq!(line.power(), Power::Off);
+        assert!(!line.listening());
+        line.boot(1_000);
+        assert_eq!(line.boot_until(), Some(1_000));
+        line.finish_boot(999);
+        assert_eq!(line.power(), Power::Booting);
+        line.finish_boot(1_000);
+        assert!(line.listening());
+        assert_eq!(line.boot_until(), None);
+        line.set(Power::Failed);
+        line.finish_boot(2_000);
+        assert_eq!(line.power(), Power::Failed);
+    }
+}
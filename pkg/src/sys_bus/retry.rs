@@ -0,0 +1,154 @@
+use crate::sys_bus::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Bus a failed message is sent again on.
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RetryBus {
+    Same,
+    // the next redundant bus (no retry at all on a single bus)
+    Alternate,
+}
+
+/// Step taken about an RT once the retries of a message are used up.
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Escalation {
+    // transmit status word (mode code 2)
+    StatusPoll,
+    // reset remote terminal (mode code 8)
+    ResetRT,
+    // the BC stops sending messages involving it
+    MarkFailed,
+}
+
+/// Why a message failed.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Failure {
+    // no response (or not all of it) in time
+    Timeout,
+    // status word with the message error bit
+    MessageError,
+}
+
+/// How a BC recovers from a failed message. The default retries a timed
+/// out message once on the other bus of a dual bus, and gives up.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub retries: u8,
+    pub bus: RetryBus,
+    // wait before each retry (ns)
+    #[serde(default)]
+    pub delay: u128,
+    // a status with the message error bit fails the message too
+    #[serde(default)]
+    pub on_message_error: bool,
+    // steps tried in turn, each one as long as the previous one fails
+    #[serde(default)]
+    pub escalation: Vec<Escalation>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(1, RetryBus::Alternate)
+    }
+}
+
+#[allow(unused)]
+impl RetryPolicy {
+    pub fn new(retries: u8, bus: RetryBus) -> Self {
+        RetryPolicy {
+            retries,
+            bus,
+            delay: 0,
+            on_message_error: false,
+            escalation: Vec::new(),
+        }
+    }
+
+    pub fn delay(mut self, delay: u128) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn on_message_error(mut self, on: bool) -> Self {
+        self.on_message_error = on;
+        self
+    }
+
+    pub fn escalate(mut self, step: Escalation) -> Self {
+        self.escalation.push(step);
+        self
+    }
+
+    /// Retries allowed with `n_buses` redundant buses.
+    pub fn retries_on(&self, n_buses: u8) -> u8 {
+        match self.bus {
+            RetryBus::Alternate if n_buses < 2 => 0,
+            _ => self.retries,
+        }
+    }
+}
+
+/// Decisions of a BC about failed messages (`ErrMsg::MsgRecovery`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RecoveryEvent {
+    // the message is sent again (attempt from 1) on the bus after the delay
+    Retry {
+        rt: u8,
+        cause: Failure,
+        attempt: u8,
+        bus: u8,
+        delay: u128,
+    },
+    // the retries (or the previous step) failed
+    Escalate {
+        rt: u8,
+        cause: Failure,
+        step: Escalation,
+    },
+    // nothing left to try
+    GiveUp {
+        rt: u8,
+        cause: Failure,
+    },
+    // a message not sent as it involves an RT marked failed
+    Skipped(u8),
+}
+
+/// Recovery state of a BC.
+#[derive(Clone, Debug, Default)]
+pub struct Recovery {
+    // policy of the message in progress, when not the BC's one
+    pub policy: Option<RetryPolicy>,
+    // escalation step in progress (`None`: a regular message)
+    pub step: Option<usize>,
+    // escalation message to send as soon as the BC is ready
+    pub pending: Option<Message>,
+    // RTs marked failed
+    pub failed: BTreeSet<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::default();
+        assert_eq!((policy.retries_on(1), policy.retries_on(2)), (0, 1));
+        let policy = RetryPolicy::new(3, RetryBus::Same)
+            .delay(50_000)
+            .escalate(Escalation::StatusPoll)
+            .escalate(Escalation::MarkFailed);
+        assert_eq!(policy.retries_on(1), 3);
+        let parsed: RetryPolicy = serde_json::from_str(
+            r#"{"retries": 3, "bus": "Same", "delay": 50000,
+                "escalation": ["StatusPoll", "MarkFailed"]}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed, policy);
+    }
+}
@@ -0,0 +1,425 @@
+use crate::sys_bus::control::ExecControl;
+use crate::sys_bus::power::PowerLine;
+use crate::sys_bus::{
+    Clock, Device, EventHandlerEmitter, Mode, State, Word, BC_WARMUP_STEPS, RT_WORD_LOAD_TIME,
+    WRD_EMPTY,
+};
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BinaryHeap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// an idle BC polls its handler at the same pace as the threaded loop
+// (one 5µs `recv_timeout` per iteration)
+pub const BC_IDLE_POLL: u128 = 5_000;
+// pause after a BC timeout (same as the threaded loop)
+pub const BC_TIMEOUT_PAUSE: u128 = 1_000_000;
+
+#[derive(Clone, Copy, Debug)]
+enum Ev {
+    // one iteration of the device loop
+    Poll,
+    // a word put on the bus by another device
+    Arrive(Word),
+    // end of the write delay: (word, write queue size when popped)
+    Sent(Word, usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Scheduled {
+    time: u128,
+    seq: u64,
+    node: usize,
+    ev: Ev,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        self.time == other.time && self.seq == other.seq
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        // reversed: BinaryHeap is a max-heap, we pop the earliest event first
+        other
+            .time
+            .cmp(&self.time)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+struct Node {
+    device: Arc<Mutex<Device>>,
+    // read without locking the device
+    power: Arc<PowerLine>,
+    emitter: Arc<Mutex<EventHandlerEmitter>>,
+    // per bus: read_time, valid message flag, word
+    prev_words: Vec<(u128, bool, Word)>,
+    bc_step: u128,
+    // the device is sleeping (write delay or timeout pause) until then
+    busy_until: u128,
+    // the only poll that is still valid for this node
+    next_poll: Option<u128>,
+    log_file: PathBuf,
+    log_file_bm: PathBuf,
+}
+
+/// Discrete-event engine driving the devices of a `System` on a simulated
+/// nanosecond clock. It replays the per-device loop of `System::run_d`
+/// (same handlers, write delays and `RT_WORD_LOAD_TIME` collision window)
+/// but only at the instants where something can happen.
+pub struct VirtualBus {
+    now: Arc<AtomicU64>,
+    seq: u64,
+    queue: BinaryHeap<Scheduled>,
+    nodes: Vec<Node>,
+    // stops the engine at a pause or a breakpoint
+    pub control: Arc<ExecControl>,
+}
+
+impl VirtualBus {
+    pub fn new() -> Self {
+        VirtualBus {
+            now: Arc::new(AtomicU64::new(0)),
+            seq: 0,
+            queue: BinaryHeap::new(),
+            nodes: Vec::new(),
+            control: Arc::new(ExecControl::default()),
+        }
+    }
+
+    pub fn clock(&self) -> Clock {
+        Clock::Virtual(Arc::clone(&self.now))
+    }
+
+    pub fn now(&self) -> u128 {
+        self.now.load(Ordering::Relaxed) as u128
+    }
+
+    fn set_now(&self, time: u128) {
+        // handlers may have consumed time (`Clock::sleep_ns`), never go back
+        self.now.fetch_max(time as u64, Ordering::Relaxed);
+    }
+
+    fn push(&mut self, time: u128, node: usize, ev: Ev) {
+        self.seq += 1;
+        self.queue.push(Scheduled {
+            time,
+            seq: self.seq,
+            node,
+            ev,
+        });
+    }
+
+    fn poll_at(&mut self, node: usize, time: u128) {
+        if let Some(t) = self.nodes[node].next_poll {
+            if t <= time {
+                return;
+            }
+        }
+        self.nodes[node].next_poll = Some(time);
+        self.push(time, node, Ev::Poll);
+    }
+
+    pub fn add_device(
+        &mut self,
+        device: Arc<Mutex<Device>>,
+        emitter: Arc<Mutex<EventHandlerEmitter>>,
+        log_file: PathBuf,
+        log_file_bm: PathBuf,
+    ) {
+        let (n_buses, power) = {
+            let d = device.lock().unwrap();
+            (d.n_buses as usize, Arc::clone(&d.power))
+        };
+        self.nodes.push(Node {
+            device,
+            power,
+            emitter,
+            prev_words: vec![(0, false, WRD_EMPTY); n_buses],
+            bc_step: 0,
+            busy_until: 0,
+            next_poll: None,
+            log_file,
+            log_file_bm,
+        });
+        let now = self.now();
+        self.poll_at(self.nodes.len() - 1, now);
+    }
+
+    /// Polls node `i` now, after a change from outside (power switch).
+    pub fn wake(&mut self, i: usize) {
+        let now = self.now();
+        self.poll_at(i, now);
+    }
+
+    /// Advances the simulated clock by `ns`, processing every due event.
+    /// When `go` is false the clock moves but devices are frozen; a pause
+    /// (or a breakpoint hit) stops the clock at the event that caused it.
+    pub fn run_for(&mut self, ns: u128, go: bool) {
+        let end = self.now() + ns;
+        if !go {
+            self.set_now(end);
+            return;
+        }
+        loop {
+            if self.control.is_paused() {
+                break;
+            }
+            match self.queue.peek() {
+                Some(top) if top.time <= end => {}
+                _ => break,
+            }
+            let e = self.queue.pop().unwrap();
+            self.set_now(e.time);
+            match e.ev {
+                Ev::Poll => {
+                    if self.nodes[e.node].next_poll != Some(e.time) {
+                        // superseded by an earlier poll
+                        continue;
+                    }
+                    self.nodes[e.node].next_poll = None;
+                    self.poll(e.node);
+                }
+                Ev::Arrive(w) => self.arrive(e.node, w, e.time),
+                Ev::Sent(w, wq) => self.sent(e.node, w, wq),
+            }
+        }
+        if !self.control.is_paused() {
+            self.set_now(end);
+        }
+        let now = self.now();
+        for node in &self.nodes {
+            node.device.lock().unwrap().publish(now);
+        }
+    }
+
+    fn poll(&mut self, i: usize) {
+        let node_device = Arc::clone(&self.nodes[i].device);
+        let node_emitter = Arc::clone(&self.nodes[i].emitter);
+        let mut device = node_device.lock().unwrap();
+        let mut bc_ready = false;
+        if !device.sync_power() {
+            self.nodes[i]
+                .prev_words
+                .iter_mut()
+                .for_each(|p| p.1 = false);
+        }
+        if device.state != State::Off && self.nodes[i].busy_until <= self.now() {
+            let current = self.now();
+            device.check_takeover(current);
+            if device.mode == Mode::BC {
+                bc_ready = device.state == State::Idle;
+                let mut emitter = node_emitter.lock().unwrap();
+                if emitter.bc_tick(&mut device, &mut self.nodes[i].bc_step, current) {
+                    self.nodes[i].busy_until = self.now() + BC_TIMEOUT_PAUSE;
+                }
+            }
+            let current = self.now();
+            if self.nodes[i].busy_until <= current && current > device.time_write_ready {
+                if let Some(entry) = device.write_queue.pop_front() {
+                    let wq = device.write_queue.len();
+                    let sent_at = current + device.write_delay(wq);
+                    self.nodes[i].busy_until = sent_at;
+                    self.push(sent_at, i, Ev::Sent(entry.1, wq));
+                }
+            }
+            if self.nodes[i].busy_until <= current && self.nodes[i].prev_words.iter().any(|p| p.1) {
+                let mut emitter = node_emitter.lock().unwrap();
+                emitter.process_due(&mut device, &mut self.nodes[i].prev_words, current);
+            }
+        }
+        self.reschedule(i, &device, bc_ready);
+    }
+
+    fn arrive(&mut self, i: usize, w: Word, time: u128) {
+        if self.nodes[i].busy_until > time {
+            // a sleeping device reads the word once it wakes up
+            let busy_until = self.nodes[i].busy_until;
+            self.push(busy_until, i, Ev::Arrive(w));
+            return;
+        }
+        let node_device = Arc::clone(&self.nodes[i].device);
+        let node_emitter = Arc::clone(&self.nodes[i].emitter);
+        let mut device = node_device.lock().unwrap();
+        if device.state != State::Off {
+            let current = self.now();
+            let mut emitter = node_emitter.lock().unwrap();
+            emitter.receive(&mut device, &mut self.nodes[i].prev_words, w, current);
+        }
+        self.reschedule(i, &device, false);
+    }
+
+    fn sent(&mut self, i: usize, w: Word, wq: usize) {
+        let node_device = Arc::clone(&self.nodes[i].device);
+        let mut device = node_device.lock().unwrap();
+        let current = self.now();
+        device.transmitted(w, wq);
+        for j in 0..self.nodes.len() {
+            // unpowered devices do not read
+            if j != i && self.nodes[j].power.listening() {
+                if let Some(w) = device.deliver(j as u32, w) {
+                    self.push(current, j, Ev::Arrive(w));
+                }
+            }
+        }
+        device.time_write_ready = current + RT_WORD_LOAD_TIME;
+        self.nodes[i].busy_until = current;
+        self.reschedule(i, &device, false);
+    }
+
+    /// Schedules the next loop iteration of node `i` at the earliest time
+    /// its state can change on its own (incoming words schedule themselves).
+    fn reschedule(&mut self, i: usize, device: &Device, bc_ready: bool) {
+        let now = self.now();
+        if device.state == State::Off {
+            if let Some(until) = device.power.boot_until() {
+                self.poll_at(i, now.max(until));
+            }
+            return;
+        }
+        if self.nodes[i].busy_until > now {
+            let busy_until = self.nodes[i].busy_until;
+            self.poll_at(i, busy_until);
+            return;
+        }
+        let mut next: Option<u128> = None;
+        let mut consider = |t: u128| {
+            next = Some(match next {
+                Some(n) if n <= t => n,
+                _ => t,
+            });
+        };
+        for prev_word in self.nodes[i].prev_words.iter().filter(|p| p.1) {
+            consider(prev_word.0 + RT_WORD_LOAD_TIME + 1);
+        }
+        if !device.write_queue.is_empty() {
+            consider(now.max(device.time_write_ready + 1));
+        }
+        if let Some(deadline) = device.takeover_deadline() {
+            consider(now.max(deadline));
+        }
+        if device.mode == Mode::BC {
+            if device.state == State::Idle {
+                if device.bc_next_at > now {
+                    consider(device.bc_next_at);
+                } else if bc_ready {
+                    // the handler had nothing to send
+                    consider(now + BC_IDLE_POLL);
+                } else {
+                    consider(now);
+                }
+            } else {
+                let mut timeout = device.timeout;
+                if self.nodes[i].bc_step <= BC_WARMUP_STEPS {
+                    timeout += 20_000_000;
+                }
+                if timeout > 0 {
+                    consider(now.max(timeout + 1));
+                }
+                if let Some(deadline) = device.no_response_deadline() {
+                    consider(now.max(deadline + 1));
+                }
+            }
+        }
+        if let Some(t) = next {
+            self.poll_at(i, t);
+        }
+    }
+
+    pub fn flush_logs(&self) {
+        for n in &self.nodes {
+            let device = n.device.lock().unwrap();
+            device.save_logs(&n.log_file, &n.log_file_bm);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::{format_log, DefaultBCEventHandler, DefaultEventHandler, Proto, System};
+    use std::time::Instant;
+
+    fn new_virtual(w_delays: u128, n_devices: u8, proto: Proto, seed: u64) -> System {
+        let mut sys_bus = System::new_virtual(n_devices as u32, w_delays, seed);
+        for m in 0..n_devices {
+            if m == 0 {
+                sys_bus.run_d(
+                    m,
+                    Mode::BC,
+                    Arc::new(Mutex::new(EventHandlerEmitter {
+                        handler: Box::new(DefaultBCEventHandler {
+                            total_device: n_devices,
+                            target: 0,
+                            data: vec![1, 2, 3],
+                            proto,
+                            proto_rotate: true,
+                        }),
+                    })),
+                    false,
+                );
+            } else {
+                sys_bus.run_d(
+                    m,
+                    Mode::RT,
+                    Arc::new(Mutex::new(EventHandlerEmitter {
+                        handler: Box::new(DefaultEventHandler {}),
+                    })),
+                    false,
+                );
+            }
+        }
+        sys_bus
+    }
+
+    fn eval_virtual(w_delays: u128, n_devices: u8, proto: Proto, ms: u64, seed: u64) -> System {
+        let mut sys_bus = new_virtual(w_delays, n_devices, proto, seed);
+        sys_bus.go();
+        sys_bus.sleep_ms(ms);
+        sys_bus.stop();
+        sys_bus.join();
+        sys_bus
+    }
+
+    #[test]
+    fn test_virtual_delta_t() {
+        let system = eval_virtual(8_000, 3, Proto::RT2RT, 200, 0);
+        let bc = system.devices[0].lock().unwrap();
+        assert!(bc.delta_t_count > 0);
+        assert!(bc.delta_t_avg / bc.delta_t_count > 0);
+        assert!(bc.logs.len() > 1000);
+        assert_eq!(bc.timeout_times, 0);
+    }
+
+    #[test]
+    fn test_virtual_reproducible() {
+        let a = eval_virtual(4_000, 4, Proto::BC2RT, 50, 7);
+        let b = eval_virtual(4_000, 4, Proto::BC2RT, 50, 7);
+        assert_eq!(a.logs.len(), b.logs.len());
+        for (la, lb) in a.logs.iter().zip(b.logs.iter()) {
+            assert_eq!(format_log(la), format_log(lb));
+        }
+    }
+
+    #[test]
+    fn test_virtual_faster_than_real_time() {
+        let mut sys_bus = new_virtual(4_000, 3, Proto::RT2BC, 0);
+        sys_bus.go();
+        let started = Instant::now();
+        sys_bus.sleep_ms(2_000);
+        assert!(started.elapsed().as_millis() < 2_000);
+        assert!(sys_bus.clock.elapsed().as_millis() >= 2_000);
+    }
+}
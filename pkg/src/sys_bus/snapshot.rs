@@ -0,0 +1,137 @@
+use crate::sys_bus::power::Power;
+use crate::sys_bus::{AttackType, Device, ErrMsg, Mode, State};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+// a running device publishes its snapshot at least this often (ns, device
+// clock)
+pub const SNAPSHOT_INTERVAL: u128 = 1_000_000;
+
+/// Events a device logged so far, counted even when its logs are not
+/// retained.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct DeviceCounters {
+    pub logs: u64,
+    pub words_written: u64,
+    // commands of any kind received (including illegal ones)
+    pub commands: u64,
+    pub data: u64,
+    pub statuses: u64,
+    // parity, Manchester and sync errors
+    pub word_errors: u64,
+    pub timeouts: u64,
+    pub retries: u64,
+    pub timing_violations: u64,
+}
+
+impl DeviceCounters {
+    pub fn count(&mut self, e: &ErrMsg) {
+        use ErrMsg::*;
+        self.logs += 1;
+        let counter = match e {
+            MsgWrt(_) => &mut self.words_written,
+            MsgEntCmd | MsgEntCmdRcv | MsgEntCmdTrx | MsgEntCmdMcx | MsgEntCmdIlg => {
+                &mut self.commands
+            }
+            MsgEntDat => &mut self.data,
+            MsgEntSte => &mut self.statuses,
+            MsgEntErrPty(..) | MsgEntErrMan(_) | MsgEntErrSync => &mut self.word_errors,
+            MsgBCTimeout(_) => &mut self.timeouts,
+            MsgBCRetry(_) => &mut self.retries,
+            MsgTiming(_) => &mut self.timing_violations,
+            _ => return,
+        };
+        *counter += 1;
+    }
+}
+
+/// State of a device at a point of its run, see `System::snapshot`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DeviceSnapshot {
+    // device clock when taken (ns)
+    pub time: u128,
+    pub id: u32,
+    pub address: u8,
+    pub mode: Mode,
+    pub fake: bool,
+    pub state: State,
+    pub power: Power,
+    pub atk_type: AttackType,
+    // words waiting to be written, words received but not handled yet
+    pub write_queue: usize,
+    pub read_queue: usize,
+    // words sent to the device and not read yet
+    pub rx_pending: usize,
+    pub timeout_times: u128,
+    // average response delay seen by a BC (0 before the first one)
+    pub delta_t_avg: u128,
+    pub delta_t_count: u128,
+    pub counters: DeviceCounters,
+}
+
+impl DeviceSnapshot {
+    pub fn of(d: &Device, time: u128) -> Self {
+        DeviceSnapshot {
+            time,
+            id: d.id,
+            address: d.address,
+            mode: d.mode,
+            fake: d.fake,
+            state: d.state,
+            power: d.power.power(),
+            atk_type: d.atk_type,
+            write_queue: d.write_queue.len(),
+            read_queue: d.read_queue.len(),
+            rx_pending: d.receiver.len(),
+            timeout_times: d.timeout_times,
+            delta_t_avg: match d.delta_t_count {
+                0 => 0,
+                n => d.delta_t_avg / n,
+            },
+            delta_t_count: d.delta_t_count,
+            counters: d.counters,
+        }
+    }
+}
+
+/// Latest snapshot of a device, shared between the device (writer) and the
+/// system (reader) so that reading never waits on the device itself.
+pub type SnapshotSlot = Arc<Mutex<Option<DeviceSnapshot>>>;
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::TimingViolation;
+
+    #[test]
+    fn test_device_counters() {
+        let mut counters = DeviceCounters::default();
+        for e in [
+            ErrMsg::MsgWrt(2),
+            ErrMsg::MsgWrt(1),
+            ErrMsg::MsgEntCmdRcv,
+            ErrMsg::MsgEntCmdIlg,
+            ErrMsg::MsgEntDat,
+            ErrMsg::MsgEntErrPty(0, 0),
+            ErrMsg::MsgEntErrSync,
+            ErrMsg::MsgBCTimeout(1),
+            ErrMsg::MsgTiming(TimingViolation::NoResponse { rt: 1 }),
+            ErrMsg::MsgStaChg(0),
+        ] {
+            counters.count(&e);
+        }
+        let expected = DeviceCounters {
+            logs: 10,
+            words_written: 2,
+            commands: 2,
+            data: 1,
+            statuses: 0,
+            word_errors: 2,
+            timeouts: 1,
+            retries: 0,
+            timing_violations: 1,
+        };
+        assert_eq!(counters, expected);
+    }
+}
@@ -0,0 +1,310 @@
+use crate::sys_bus::log::{FilteredSink, LogRecord};
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender, TrySendError};
+use std::cmp::{Ordering as CmpOrdering, Reverse};
+use std::collections::BinaryHeap;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// the writer flushes its sinks at least this often while records come in
+const FLUSH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Counters of a log stream, see `System::log_stream_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LogStreamStats {
+    // records sent by the devices
+    pub records: u64,
+    // sends that found the channel full and waited for the writer
+    pub blocked: u64,
+    pub blocked_ns: u64,
+    // highest number of records waiting in the channel
+    pub max_queue: u64,
+    // records older than the reorder window, written out of order
+    pub late: u64,
+    // sink write errors
+    pub errors: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    records: AtomicU64,
+    blocked: AtomicU64,
+    blocked_ns: AtomicU64,
+    max_queue: AtomicU64,
+    late: AtomicU64,
+    errors: AtomicU64,
+}
+
+#[derive(Debug)]
+enum LogMsg {
+    Record(LogRecord),
+    Close,
+}
+
+/// The end of the stream held by each device.
+#[derive(Clone, Debug)]
+pub struct LogSender {
+    tx: Sender<LogMsg>,
+    counters: Arc<Counters>,
+}
+
+impl LogSender {
+    /// Waits for the writer when the channel is full (backpressure).
+    pub fn send(&self, l: LogRecord) {
+        let c = &self.counters;
+        c.records.fetch_add(1, Ordering::Relaxed);
+        match self.tx.try_send(LogMsg::Record(l)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(msg)) => {
+                let start = Instant::now();
+                c.blocked.fetch_add(1, Ordering::Relaxed);
+                let _ = self.tx.send(msg);
+                let waited = start.elapsed().as_nanos() as u64;
+                c.blocked_ns.fetch_add(waited, Ordering::Relaxed);
+            }
+            Err(TrySendError::Disconnected(_)) => return,
+        }
+        c.max_queue
+            .fetch_max(self.tx.len() as u64, Ordering::Relaxed);
+    }
+}
+
+// a record waiting in the reorder window, ordered by time then arrival
+struct Pending {
+    time: u128,
+    seq: u64,
+    record: LogRecord,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        (self.time, self.seq) == (other.time, other.seq)
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        (self.time, self.seq).cmp(&(other.time, other.seq))
+    }
+}
+
+/// Records of all the devices, merged in time order by a background writer
+/// that feeds the sinks while the system runs. A record is written once a
+/// record `reorder_window` ns younger has arrived (or on `close`).
+pub struct LogStream {
+    sender: LogSender,
+    sinks: Arc<Mutex<Vec<FilteredSink>>>,
+    writer: Option<thread::JoinHandle<()>>,
+}
+
+impl LogStream {
+    pub fn new(sinks: Vec<FilteredSink>, capacity: usize, reorder_window: u128) -> Self {
+        let (tx, rx) = bounded(capacity);
+        let counters = Arc::new(Counters::default());
+        let sinks = Arc::new(Mutex::new(sinks));
+        let writer = Writer {
+            rx,
+            sinks: Arc::clone(&sinks),
+            counters: Arc::clone(&counters),
+            reorder_window,
+            pending: BinaryHeap::new(),
+            seq: 0,
+            newest: 0,
+            written: 0,
+        };
+        let handle = thread::Builder::new()
+            .name("log writer".to_owned())
+            .spawn(move || writer.run())
+            .unwrap();
+        LogStream {
+            sender: LogSender { tx, counters },
+            sinks,
+            writer: Some(handle),
+        }
+    }
+
+    pub fn sender(&self) -> LogSender {
+        self.sender.clone()
+    }
+
+    /// Sink for the records to come.
+    pub fn add_sink(&self, sink: FilteredSink) {
+        self.sinks.lock().unwrap().push(sink);
+    }
+
+    pub fn stats(&self) -> LogStreamStats {
+        let c = &self.sender.counters;
+        LogStreamStats {
+            records: c.records.load(Ordering::Relaxed),
+            blocked: c.blocked.load(Ordering::Relaxed),
+            blocked_ns: c.blocked_ns.load(Ordering::Relaxed),
+            max_queue: c.max_queue.load(Ordering::Relaxed),
+            late: c.late.load(Ordering::Relaxed),
+            errors: c.errors.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Writes out what is left and waits for the writer; the devices must
+    /// have stopped logging.
+    pub fn close(&mut self) {
+        if let Some(handle) = self.writer.take() {
+            let _ = self.sender.tx.send(LogMsg::Close);
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for LogStream {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+struct Writer {
+    rx: Receiver<LogMsg>,
+    sinks: Arc<Mutex<Vec<FilteredSink>>>,
+    counters: Arc<Counters>,
+    reorder_window: u128,
+    pending: BinaryHeap<Reverse<Pending>>,
+    seq: u64,
+    // newest record time seen, and time of the last record written
+    newest: u128,
+    written: u128,
+}
+
+impl Writer {
+    fn run(mut self) {
+        let mut last_flush = Instant::now();
+        loop {
+            match self.rx.recv_timeout(FLUSH_INTERVAL) {
+                Ok(LogMsg::Record(l)) => {
+                    if l.time < self.written {
+                        self.counters.late.fetch_add(1, Ordering::Relaxed);
+                    }
+                    self.newest = self.newest.max(l.time);
+                    self.seq += 1;
+                    self.pending.push(Reverse(Pending {
+                        time: l.time,
+                        seq: self.seq,
+                        record: l,
+                    }));
+                    let due = self.newest.saturating_sub(self.reorder_window);
+                    self.write_until(Some(due));
+                }
+                Ok(LogMsg::Close) | Err(RecvTimeoutError::Disconnected) => {
+                    self.write_until(None);
+                    self.flush();
+                    return;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            if last_flush.elapsed() >= FLUSH_INTERVAL {
+                self.flush();
+                last_flush = Instant::now();
+            }
+        }
+    }
+
+    // writes the pending records up to `due` (all of them for none)
+    fn write_until(&mut self, due: Option<u128>) {
+        let sinks = Arc::clone(&self.sinks);
+        let sinks = sinks.lock().unwrap();
+        while let Some(Reverse(p)) = self.pending.peek() {
+            if due.is_some_and(|due| p.time > due) {
+                break;
+            }
+            let Reverse(p) = self.pending.pop().unwrap();
+            self.written = self.written.max(p.time);
+            for sink in sinks.iter() {
+                self.count_error(sink.write(&p.record));
+            }
+        }
+    }
+
+    fn flush(&self) {
+        for sink in self.sinks.lock().unwrap().iter() {
+            self.count_error(sink.sink.lock().unwrap().flush());
+        }
+    }
+
+    fn count_error(&self, res: io::Result<()>) {
+        if res.is_err() {
+            self.counters.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::sys_bus::log::{LogFilter, MemorySink};
+    use crate::sys_bus::{ErrMsg, Mode, State, WRD_EMPTY};
+
+    fn record(time: u128, device: u32) -> LogRecord {
+        LogRecord {
+            time,
+            mode: Mode::RT,
+            device,
+            address: device as u8,
+            state: State::Idle,
+            word: WRD_EMPTY,
+            event: ErrMsg::MsgEmpt,
+            avg_delta_t: 0,
+        }
+    }
+
+    #[test]
+    fn test_log_stream_order() {
+        let memory = Arc::new(Mutex::new(MemorySink::default()));
+        let sink = FilteredSink {
+            filter: LogFilter::default(),
+            sink: memory.clone(),
+        };
+        let mut stream = LogStream::new(vec![sink], 2, 100);
+        let tx = stream.sender();
+        // two devices slightly out of step, then one far behind
+        for (t, d) in [(10, 1), (5, 2), (60, 1), (50, 2), (300, 1), (20, 2)] {
+            tx.send(record(t, d));
+        }
+        stream.close();
+        let times: Vec<u128> = memory.lock().unwrap().logs.iter().map(|l| l.time).collect();
+        assert_eq!(times, vec![5, 10, 50, 60, 20, 300]);
+        let stats = stream.stats();
+        assert_eq!(stats.records, 6);
+        assert_eq!(stats.late, 1);
+        assert!(stats.max_queue <= 2);
+    }
+
+    #[test]
+    fn test_log_stream_incremental() {
+        let memory = Arc::new(Mutex::new(MemorySink::default()));
+        let stream = LogStream::new(Vec::new(), 16, 0);
+        stream.add_sink(FilteredSink {
+            filter: LogFilter::default().devices([1]),
+            sink: memory.clone(),
+        });
+        let tx = stream.sender();
+        for t in 0..10 {
+            tx.send(record(t, (t % 2) as u32));
+        }
+        // written while the stream is still open
+        let start = Instant::now();
+        while memory.lock().unwrap().logs.len() < 4 && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(memory.lock().unwrap().logs.len() >= 4);
+        drop(stream);
+        assert_eq!(memory.lock().unwrap().logs.len(), 5);
+    }
+}
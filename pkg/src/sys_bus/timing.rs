@@ -0,0 +1,258 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Distribution of a delay (ns).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum Delay {
+    Fixed(u128),
+    // inclusive bounds
+    Uniform { min: u128, max: u128 },
+    // negative draws are clamped to 0
+    Normal { mean: f64, std_dev: f64 },
+    // one of the delays observed in a capture, see `Delay::load_empirical`
+    Empirical(Vec<u128>),
+}
+
+impl Default for Delay {
+    fn default() -> Self {
+        Delay::Fixed(0)
+    }
+}
+
+impl Delay {
+    pub fn sample(&self, rng: &mut StdRng) -> u128 {
+        match self {
+            Delay::Fixed(ns) => *ns,
+            Delay::Uniform { min, max } => rng.gen_range(*min..=*max),
+            Delay::Normal { mean, std_dev } => match Normal::new(*mean, *std_dev) {
+                Ok(normal) => normal.sample(rng).max(0.0) as u128,
+                Err(_) => mean.max(0.0) as u128,
+            },
+            Delay::Empirical(samples) if samples.is_empty() => 0,
+            Delay::Empirical(samples) => samples[rng.gen_range(0..samples.len())],
+        }
+    }
+
+    /// Refuses a distribution that cannot be sampled.
+    pub fn check(&self) -> Result<(), String> {
+        match self {
+            Delay::Uniform { min, max } if min > max => {
+                Err(format!("Uniform delay with min {} > max {}", min, max))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the delays (ns) of a capture: one per line, or the first column
+    /// of a CSV. Lines that do not start with a number (headers, comments)
+    /// are skipped.
+    #[allow(unused)]
+    pub fn load_empirical(path: &Path) -> Result<Self, String> {
+        let s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let samples: Vec<u128> = s
+            .lines()
+            .filter_map(|l| l.split(',').next()?.trim().parse().ok())
+            .collect();
+        match samples.is_empty() {
+            true => Err(format!("{}: no delays found", path.display())),
+            false => Ok(Delay::Empirical(samples)),
+        }
+    }
+}
+
+/// How fast a device writes on the bus.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TimingProfile {
+    // before the first word of a transmission (the status of an RT, the
+    // command of a BC)
+    pub response: Delay,
+    // before each following word of the same transmission
+    #[serde(default)]
+    pub inter_word_gap: Delay,
+    // handling of the received command, added to `response`
+    #[serde(default)]
+    pub processing: Delay,
+}
+
+impl TimingProfile {
+    /// Same delay before every word, as `write_delays` does.
+    #[allow(unused)]
+    pub fn fixed(delay: u128) -> Self {
+        TimingProfile {
+            response: Delay::Fixed(delay),
+            inter_word_gap: Delay::Fixed(delay),
+            processing: Delay::Fixed(0),
+        }
+    }
+}
+
+/// Profiles by device id (order in which devices were added to the system),
+/// e.g. `{"seed": 3, "devices": {"1": {"response": {"Uniform": {"min": 4000,
+/// "max": 9000}}, "inter_word_gap": {"Fixed": 0}}}}`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TimingProfiles {
+    // `None`: 0
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub devices: HashMap<u32, TimingProfile>,
+}
+
+impl TimingProfiles {
+    pub fn from_json(s: &str) -> Result<Self, String> {
+        let profiles: TimingProfiles = serde_json::from_str(s).map_err(|e| e.to_string())?;
+        for (id, p) in profiles.devices.iter() {
+            for delay in [&p.response, &p.inter_word_gap, &p.processing] {
+                delay.check().map_err(|e| format!("device {}: {}", id, e))?;
+            }
+        }
+        Ok(profiles)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        TimingProfiles::from_json(&s)
+    }
+
+    /// Takes the profiles of `other` over those of the same devices, and its
+    /// seed if it has one.
+    pub fn merge(&mut self, other: TimingProfiles) {
+        self.seed = other.seed.or(self.seed);
+        self.devices.extend(other.devices);
+    }
+
+    /// Timing of device `id`, if it has a profile.
+    pub fn timing(&self, id: u32) -> Option<Timing> {
+        let profile = self.devices.get(&id)?.clone();
+        let seed = self.seed.unwrap_or(0);
+        Some(Timing::new(profile, seed.wrapping_add(id as u64)))
+    }
+}
+
+/// A profile with its own random stream.
+#[derive(Clone, Debug)]
+pub struct Timing {
+    pub profile: TimingProfile,
+    rng: StdRng,
+    // the next word continues the current transmission
+    burst: bool,
+}
+
+impl Timing {
+    pub fn new(profile: TimingProfile, seed: u64) -> Self {
+        Timing {
+            profile,
+            rng: StdRng::seed_from_u64(seed),
+            burst: false,
+        }
+    }
+
+    /// Delay before writing the word just taken from the write queue;
+    /// `remaining` words are still queued behind it.
+    pub fn next_delay(&mut self, remaining: usize) -> u128 {
+        let delay = match self.burst {
+            true => self.profile.inter_word_gap.sample(&mut self.rng),
+            false => {
+                self.profile.processing.sample(&mut self.rng)
+                    + self.profile.response.sample(&mut self.rng)
+            }
+        };
+        self.burst = remaining > 0;
+        delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_delay_sample() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(Delay::Fixed(4_000).sample(&mut rng), 4_000);
+        let uniform = Delay::Uniform {
+            min: 4_000,
+            max: 6_000,
+        };
+        let draws: Vec<u128> = (0..1000).map(|_| uniform.sample(&mut rng)).collect();
+        assert!(draws.iter().all(|d| (4_000..=6_000).contains(d)));
+        assert!(draws.iter().any(|d| *d < 4_500) && draws.iter().any(|d| *d > 5_500));
+        let normal = Delay::Normal {
+            mean: 8_000.0,
+            std_dev: 500.0,
+        };
+        let mean = (0..1000).map(|_| normal.sample(&mut rng)).sum::<u128>() / 1000;
+        assert!((7_900..8_100).contains(&mean), "{}", mean);
+        let empirical = Delay::Empirical(vec![5_000, 7_000]);
+        assert!((0..100).all(|_| [5_000, 7_000].contains(&empirical.sample(&mut rng))));
+        // same seed, same draws
+        let mut a = StdRng::seed_from_u64(9);
+        let mut b = StdRng::seed_from_u64(9);
+        assert!((0..100).all(|_| normal.sample(&mut a) == normal.sample(&mut b)));
+    }
+
+    #[test]
+    fn test_timing_bursts() {
+        let profile = TimingProfile {
+            response: Delay::Fixed(6_000),
+            inter_word_gap: Delay::Fixed(100),
+            processing: Delay::Fixed(1_000),
+        };
+        let mut timing = Timing::new(profile, 0);
+        // status and two data words, then a status alone
+        let delays: Vec<u128> = [2, 1, 0, 0].iter().map(|r| timing.next_delay(*r)).collect();
+        assert_eq!(delays, vec![7_000, 100, 100, 7_000]);
+    }
+
+    #[test]
+    fn test_timing_profiles_load() {
+        let profiles = TimingProfiles::from_json(
+            r#"{"seed": 3, "devices": {
+                "1": {"response": {"Uniform": {"min": 4000, "max": 9000}}},
+                "2": {"response": {"Normal": {"mean": 8000.0, "std_dev": 300.0}},
+                      "inter_word_gap": {"Fixed": 0}, "processing": {"Empirical": [10, 20]}}
+            }}"#,
+        )
+        .unwrap();
+        assert_eq!(profiles.devices[&1].inter_word_gap, Delay::Fixed(0));
+        assert!(profiles.timing(0).is_none());
+        let d1 = profiles.timing(1).unwrap().next_delay(0);
+        assert_eq!(profiles.timing(1).unwrap().next_delay(0), d1);
+        assert!(TimingProfiles::from_json(r#"{"devices": {"1": {"delay": 1}}}"#).is_err());
+        let inverted =
+            r#"{"devices": {"1": {"response": {"Uniform": {"min": 9000, "max": 4000}}}}}"#;
+        assert!(TimingProfiles::from_json(inverted).is_err());
+
+        // loaded over earlier profiles: the file's devices and seed win
+        let mut merged = TimingProfiles {
+            seed: Some(7),
+            devices: HashMap::from([
+                (1, TimingProfile::fixed(1_000)),
+                (4, TimingProfile::fixed(2_000)),
+            ]),
+        };
+        merged.merge(profiles.clone());
+        assert_eq!(merged.seed, Some(3));
+        assert_eq!(merged.devices[&1], profiles.devices[&1]);
+        assert_eq!(merged.devices[&4], TimingProfile::fixed(2_000));
+        merged.merge(TimingProfiles::from_json(r#"{"devices": {}}"#).unwrap());
+        assert_eq!(merged.seed, Some(3));
+
+        let path = std::env::temp_dir().join("sv1dur_test_capture.csv");
+        fs::write(&path, "response_ns,rt\n5100,1\n# noise\n6200,2\n").unwrap();
+        assert_eq!(
+            Delay::load_empirical(&path),
+            Ok(Delay::Empirical(vec![5_100, 6_200]))
+        );
+        fs::write(&path, "response_ns\n").unwrap();
+        assert!(Delay::load_empirical(&path).is_err());
+        let _ = fs::remove_file(&path);
+    }
+}
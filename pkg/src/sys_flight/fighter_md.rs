@@ -1,14 +1,19 @@
 use crate::attacks::AttackController;
+use crate::sys_bus::builder::{DeviceSpec, SystemBuilder};
+use crate::sys_bus::log::LogConfig;
 use crate::sys_bus::{
-    AttackType, DefaultEventHandler, Device, ErrMsg, EventHandler, EventHandlerEmitter, Mode,
-    System, Word, TR, WRD_EMPTY,
+    AttackType, DefaultEventHandler, Device, ErrMsg, EventHandler, EventHandlerEmitter, Mode, Word,
+    SA_DEFAULT, TR, WRD_EMPTY,
 };
 use bitfield::bitfield;
 use num_format::{Locale, ToFormattedString};
 use priority_queue::DoublePriorityQueue;
 use rand::Rng;
 use rusqlite::{Connection, Result};
+use serde::{Deserialize, Serialize};
 use std::collections::LinkedList;
+use std::fs;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
@@ -33,7 +38,7 @@ impl SplitInt {
 }
 
 #[allow(unused)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum Address {
     BusControl,
@@ -227,7 +232,7 @@ impl From<u8> for Address {
 }
 
 #[allow(unused)]
-#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum MsgPri {
     Immediate,
     VeryHigh,
@@ -261,10 +266,12 @@ impl MsgPri {
 #[derive(Hash, PartialEq, Eq, Copy, Clone)]
 pub struct Event {
     source: Address,
-    // source sub address?
     destination: Address,
-    // destination sub address?
+    // subaddress of the commands
+    sub_address: u8,
     priority: MsgPri,
+    // delay between two sends of a repeating event (ns)
+    period: u128,
     repeating: bool,
     word_count: u8,
 }
@@ -275,26 +282,108 @@ pub enum SystemState {
     Active,
 }
 
-#[derive(Clone)]
-pub struct FighterBCScheduler {
-    // to be used for BC as its event handler
-    pub priority_list: DoublePriorityQueue<Event, u128>,
-    timeout: u128,
-    landing_gear_state: SystemState,
-    landing_gear_events: Vec<Event>,
-    radar_state: SystemState,
-    radar_events: Vec<Event>,
-    rover_state: SystemState,
-    rover_events: Vec<Event>,
-    current_event: Option<Event>,
+/// Systems whose messages are only scheduled while they are active.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Subsystem {
+    LandingGear,
+    Radar,
+    Rover,
 }
 
-impl FighterBCScheduler {
-    pub fn new() -> Self {
+/// A message of a fighter BC schedule.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleEntry {
+    pub source: Address,
+    pub destination: Address,
+    // `SA_DEFAULT` when not given
+    #[serde(default)]
+    pub sub_address: Option<u8>,
+    pub word_count: u8,
+    // how often a repeating message is sent: the delay of a priority, or a
+    // period (ns)
+    #[serde(default)]
+    pub priority: Option<MsgPri>,
+    #[serde(default)]
+    pub period: Option<u128>,
+    pub repeating: bool,
+    // only scheduled while this system is active
+    #[serde(default)]
+    pub enabled_by: Option<Subsystem>,
+}
+
+impl ScheduleEntry {
+    fn new(source: Address, destination: Address, priority: MsgPri, word_count: u8) -> Self {
+        ScheduleEntry {
+            source,
+            destination,
+            sub_address: None,
+            word_count,
+            priority: Some(priority),
+            period: None,
+            repeating: true,
+            enabled_by: None,
+        }
+    }
+
+    fn enabled_by(mut self, system: Subsystem) -> Self {
+        self.enabled_by = Some(system);
+        self
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let what = format!("{:?} to {:?}", self.source, self.destination);
+        if self.source == self.destination {
+            return Err(format!("{}: same source and destination", what));
+        }
+        if let Some(sa) = self.sub_address {
+            // 0 and 31 are for mode codes
+            if !(1..=30).contains(&sa) {
+                return Err(format!("{}: bad subaddress {}", what, sa));
+            }
+        }
+        if !(1..=32).contains(&self.word_count) {
+            return Err(format!("{}: bad word count {}", what, self.word_count));
+        }
+        match (self.priority, self.period) {
+            (Some(_), Some(_)) => Err(format!("{}: both a priority and a period", what)),
+            (None, None) if self.repeating => Err(format!("{}: no priority or period", what)),
+            _ => Ok(()),
+        }
+    }
+
+    fn event(&self) -> Event {
+        let priority = self.priority.unwrap_or(MsgPri::Immediate);
+        Event {
+            source: self.source,
+            destination: self.destination,
+            sub_address: self.sub_address.unwrap_or(SA_DEFAULT),
+            priority,
+            period: self.period.unwrap_or(priority.delay() as u128),
+            repeating: self.repeating,
+            word_count: self.word_count,
+        }
+    }
+}
+
+/// Messages of a `FighterBCScheduler` and the systems active from the
+/// start, e.g. `{"active": ["LandingGear"], "messages": [{"source": "Fuel",
+/// "destination": "FlightControls", "sub_address": 3, "word_count": 4,
+/// "period": 500000000, "repeating": true}]}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FighterSchedule {
+    #[serde(default)]
+    pub active: Vec<Subsystem>,
+    pub messages: Vec<ScheduleEntry>,
+}
+
+impl Default for FighterSchedule {
+    // F-18 schedule
+    fn default() -> Self {
         use Address::*;
         use MsgPri::*;
-        let fighter_schedule: Vec<Event> = vec![
-            //F-18 schedule
+        let messages = vec![
             // With feedback
             // Event {source: FlightControls, destination: Trim,  priority: Low,  repeating: true,
             //     word_count: 2, //one float32 should carry sufficient data
@@ -302,23 +391,12 @@ impl FighterBCScheduler {
             // Event {source: Trim,   destination: FlightControls,    priority: Low,  repeating: true,
             //     word_count: 2,//one float32 should carry sufficient data
             // },
-            Event {
-                source: FlightControls,
-                destination: Flaps,
-                priority: Low,
-                repeating: true,
-                word_count: 2, //1,
-            },
+            ScheduleEntry::new(FlightControls, Flaps, Low, 2), //1,
             // Event {source: Flaps,  destination: FlightControls,    priority: Low,  repeating: true,
             //     word_count: 1,
             // },
-            Event {
-                source: FlightControls,
-                destination: Engine,
-                priority: VeryHigh,
-                repeating: true,
-                word_count: 2, //16, //We'll estimate a float32 for each of the engines (up to four engines) and 2 words per float32
-            },
+            //16, //We'll estimate a float32 for each of the engines (up to four engines) and 2 words per float32
+            ScheduleEntry::new(FlightControls, Engine, VeryHigh, 2),
             // Event {source: Engine, destination: FlightControls,    priority: High, repeating: true,
             //     word_count: 16, //Temperature, speed,
             // },
@@ -334,28 +412,10 @@ impl FighterBCScheduler {
             // Event {source: Weapons,    destination: BusControl,    priority: Medium,   repeating: true,
             //     word_count: 0, //Check for an SR and then activate the "service request" message.
             // },
-            Event {
-                source: FlightControls,
-                destination: Rudder,
-                priority: VeryHigh,
-                repeating: true,
-                word_count: 4, //2,//float32 for degree
-            },
-            Event {
-                source: Rudder,
-                destination: FlightControls,
-                priority: VeryHigh,
-                repeating: true,
-                word_count: 2, //2,//float32 for degree
-            },
+            ScheduleEntry::new(FlightControls, Rudder, VeryHigh, 4), //2,//float32 for degree
+            ScheduleEntry::new(Rudder, FlightControls, VeryHigh, 2), //2,//float32 for degree
             // Without feedback
-            Event {
-                source: FlightControls,
-                destination: Ailerons,
-                priority: VeryHigh,
-                repeating: true,
-                word_count: 8, //4,//float32 for degree on each wing
-            },
+            ScheduleEntry::new(FlightControls, Ailerons, VeryHigh, 8), //4,//float32 for degree on each wing
             // Event {source: FlightControls, destination: Elevators, priority: VeryHigh, repeating: true,
             //     word_count: 8, //4,//float32 for degree on each wing
             // },
@@ -366,26 +426,14 @@ impl FighterBCScheduler {
             //     word_count: 2, //4,//float32 for degree on each wing
             // },
             // Sensors
-            Event {
-                source: Fuel,
-                destination: FlightControls,
-                priority: Lowest,
-                repeating: true,
-                word_count: 4, //one float32 for quantity, one float32 for flow
-            },
+            ScheduleEntry::new(Fuel, FlightControls, Lowest, 4), //one float32 for quantity, one float32 for flow
             // Event {source: Gyro, destination: FlightControls,    priority: Medium,   repeating: true,
             //     word_count: 10, //one float32 for heading
             // },
             // Event {source: Altimeter,  destination: FlightControls,    priority: Medium,   repeating: true,
             //     word_count: 1,
             // },
-            Event {
-                source: Positioning,
-                destination: FlightControls,
-                priority: Lowest,
-                repeating: true,
-                word_count: 6, // Lat, Long, Alt
-            },
+            ScheduleEntry::new(Positioning, FlightControls, Lowest, 6), // Lat, Long, Alt
             // Event {source: Pitch,   destination: FlightControls,    priority: Medium,   repeating: true,
             //     word_count: 6, //float32 for pitch, bank, and roll
             // },
@@ -395,11 +443,66 @@ impl FighterBCScheduler {
             // Event {source: Radar, destination: BusControl, priority: Medium, repeating: true,
             //     word_count: 1, // int of the number of words to send
             // },
+            //float32 for degree on each side
+            ScheduleEntry::new(FlightControls, Brakes, High, 4).enabled_by(Subsystem::LandingGear),
+            // Event {source: Brakes, destination: FlightControls, priority: Medium, repeating: true,
+            //     word_count: 12,  // float32 for torque (all three points of contact)
+            //                     // float32 for wheel load (all three points of contact)
+            // }
+            // We may want this to work, even with the landing gear up.  Maybe we slide the plane on its belly.
+            // Event {source: FlightControls, destination: Tailhook, priority: Medium, repeating: true,
+            //     word_count: 1,}
+            // Event {source: Tailhook, destination: FlightControls, priority: Medium, repeating: true,
+            //     word_count: 1,}
+            // Event {source: Radar, destination: FlightControls, priority: High, repeating: true, word_count: 0} (radar)
+            // Event {source: Rover, destination: FlightControls, priority: High, repeating: true, word_count: 0} (rover)
         ];
+        FighterSchedule {
+            active: vec![Subsystem::LandingGear],
+            messages,
+        }
+    }
+}
+
+impl FighterSchedule {
+    pub fn from_json(s: &str) -> Result<FighterSchedule, String> {
+        let schedule: FighterSchedule = serde_json::from_str(s).map_err(|e| e.to_string())?;
+        for entry in &schedule.messages {
+            entry.check()?;
+        }
+        Ok(schedule)
+    }
+
+    pub fn load(path: &Path) -> Result<FighterSchedule, String> {
+        let s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        FighterSchedule::from_json(&s)
+    }
+}
+
+#[derive(Clone)]
+pub struct FighterBCScheduler {
+    // to be used for BC as its event handler
+    pub priority_list: DoublePriorityQueue<Event, u128>,
+    timeout: u128,
+    landing_gear_state: SystemState,
+    landing_gear_events: Vec<Event>,
+    radar_state: SystemState,
+    radar_events: Vec<Event>,
+    rover_state: SystemState,
+    rover_events: Vec<Event>,
+    current_event: Option<Event>,
+}
+
+impl FighterBCScheduler {
+    pub fn new() -> Self {
+        FighterBCScheduler::from_schedule(&FighterSchedule::default())
+    }
+
+    pub fn from_schedule(schedule: &FighterSchedule) -> Self {
         let mut scheduler = FighterBCScheduler {
             priority_list: DoublePriorityQueue::new(),
             timeout: 0,
-            landing_gear_state: SystemState::Active, // Enable or disable landing_gear_events based on this value
+            landing_gear_state: SystemState::Inactive, // Enable or disable landing_gear_events based on this value
             landing_gear_events: Vec::new(),
             radar_state: SystemState::Inactive, // Enable or disable radar updates based on this value.  This will save updates when there is no data to be sent.
             radar_events: Vec::new(),
@@ -407,46 +510,42 @@ impl FighterBCScheduler {
             rover_events: Vec::new(),
             current_event: None,
         };
-        for event in fighter_schedule {
-            // should we randomize the events?  This would make the time series analysis a little different
-            scheduler.priority_list.push(event, 0);
+        for system in &schedule.active {
+            *scheduler.system(*system).0 = SystemState::Active;
         }
-        scheduler.landing_gear_events.push(Event {
-            source: FlightControls,
-            destination: Brakes,
-            priority: High,
-            repeating: true,
-            word_count: 4, //float32 for degree on each side
-        });
-        // scheduler.landing_gear_events.push(
-        //     Event {source: Brakes, destination: FlightControls, priority: Medium, repeating: true,
-        //         word_count: 12,  // float32 for torque (all three points of contact)
-        //                         // float32 for wheel load (all three points of contact)
-        //     }
-        // );
-        // scheduler.landing_gear_events.push( // We may want this to work, even with the landing gear up.  Maybe we slide the plane on its belly.
-        //     Event {source: FlightControls, destination: Tailhook, priority: Medium, repeating: true,
-        //     word_count: 1,}
-        // );
-        // scheduler.landing_gear_events.push(
-        //     Event {source: Tailhook, destination: FlightControls, priority: Medium, repeating: true,
-        //     word_count: 1,}
-        // );
-        // scheduler.radar_events.push(
-        //     Event {source: Radar, destination: FlightControls, priority: High, repeating: true, word_count: 0}
-        // );
-        // scheduler.rover_events.push(
-        //     Event {source: Rover, destination: FlightControls, priority: High, repeating: true, word_count: 0}
-        // );
-        for event in &scheduler.landing_gear_events {
-            scheduler.priority_list.push(*event, 0);
+        for entry in &schedule.messages {
+            let event = entry.event();
+            let active = match entry.enabled_by {
+                Some(system) => {
+                    let (state, events) = scheduler.system(system);
+                    events.push(event);
+                    matches!(state, SystemState::Active)
+                }
+                None => true,
+            };
+            if active {
+                // should we randomize the events?  This would make the time series analysis a little different
+                scheduler.priority_list.push(event, 0);
+            }
+        }
+        scheduler
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let schedule = FighterSchedule::load(path)?;
+        Ok(FighterBCScheduler::from_schedule(&schedule))
+    }
+
+    fn system(&mut self, system: Subsystem) -> (&mut SystemState, &mut Vec<Event>) {
+        match system {
+            Subsystem::LandingGear => (&mut self.landing_gear_state, &mut self.landing_gear_events),
+            Subsystem::Radar => (&mut self.radar_state, &mut self.radar_events),
+            Subsystem::Rover => (&mut self.rover_state, &mut self.rover_events),
         }
-        return scheduler;
     }
 
     fn update_priority(&mut self, event: Event, time: u128) {
-        let delay = event.priority.delay() as u128;
-        let next_time = time + delay;
+        let next_time = time + event.period;
         self.priority_list.push(event, next_time);
     }
 
@@ -460,61 +559,40 @@ impl EventHandler for FighterBCScheduler {
     {
         // We pop the next message and wait until we should send it. This cannot be preempted, but that shouldn't be a problem.
         // SR bits should only come during a message requested by the bus controller.
-        let spin_sleeper = spin_sleep::SpinSleeper::new(100_000);
         let message = self.priority_list.pop_min();
         match message {
-            Some((
-                Event {
+            Some((event, mut time)) => {
+                let Event {
                     source: src,
                     destination: dst,
-                    priority: pri,
                     repeating: repeat,
                     word_count: wc,
-                },
-                mut time,
-            )) => {
+                    ..
+                } = event;
                 self.current_event = Some(Event {
-                    source: src,
-                    destination: dst,
                     priority: MsgPri::Immediate,
+                    period: 0,
                     repeating: false,
-                    word_count: wc,
+                    ..event
                 });
                 let mut current = d.clock.elapsed().as_nanos();
                 if time >= current {
                     let wait = time - current;
-                    spin_sleeper.sleep_ns(wait.try_into().unwrap());
+                    d.clock.sleep_ns(wait.try_into().unwrap());
                 }
+                d.sub_address = event.sub_address;
                 match (src, dst) {
                     (source, _) if source as u8 == d.address => {
                         // BC to RT
                         if repeat {
-                            self.update_priority(
-                                Event {
-                                    source: src,
-                                    destination: dst,
-                                    priority: pri,
-                                    repeating: repeat,
-                                    word_count: wc,
-                                },
-                                time,
-                            );
+                            self.update_priority(event, time);
                             // not used
                         }
                     }
                     (_, destination) if destination as u8 == d.address => {
                         // RT to BC
                         if repeat {
-                            self.update_priority(
-                                Event {
-                                    source: src,
-                                    destination: dst,
-                                    priority: pri,
-                                    repeating: repeat,
-                                    word_count: wc,
-                                },
-                                time,
-                            );
+                            self.update_priority(event, time);
                         }
                         // synchronized event call (bus will wait until all involved device finished).
                         // todo: add timeout event (should be added to the bus level rather than flight level)
@@ -523,16 +601,7 @@ impl EventHandler for FighterBCScheduler {
                     _ => {
                         // RT to RT
                         if repeat {
-                            self.update_priority(
-                                Event {
-                                    source: src,
-                                    destination: dst,
-                                    priority: pri,
-                                    repeating: repeat,
-                                    word_count: wc,
-                                },
-                                time,
-                            );
+                            self.update_priority(event, time);
                         }
                         // synchronized event call (bus will wait until all involved device finished).
                         // todo: add timeout event (should be added to the bus level rather than flight level)
@@ -558,7 +627,9 @@ impl EventHandler for FighterBCScheduler {
             let item = Event {
                 source: Address::from(rt),
                 destination: dest,
+                sub_address: SA_DEFAULT,
                 priority: MsgPri::Immediate,
+                period: 0,
                 repeating: false,
                 word_count: wc,
             };
@@ -686,8 +757,14 @@ pub fn eval_fighter_sim(
     mut run_time: u64,
     attack: AttackType,
     name: String,
-) {
+    schedule: Option<&Path>,
+) -> Result<(), String> {
     // let database = "sample_data.sqlite";
+    // before anything is built
+    let scheduler = match schedule {
+        Some(path) => FighterBCScheduler::load(path)?,
+        None => FighterBCScheduler::new(),
+    };
     let devices = vec![
         Address::BusControl,
         Address::FlightControls,
@@ -704,8 +781,15 @@ pub fn eval_fighter_sim(
         Address::BusMonitor,
         Address::AttackController,
     ];
-    let total_devices = devices.len() as u32;
-    let mut sys = System::new_with_name(total_devices, w_delays, name);
+    // whole recordings: logs are written as they come, not kept in memory
+    let log_config = LogConfig {
+        stream: true,
+        retain: false,
+        ..LogConfig::default()
+    };
+    let mut builder = SystemBuilder::new(w_delays)
+        .home_dir(name)
+        .log_config(log_config);
     let mut max_device_replay_time = 0;
 
     let mut attack_controller = AttackController {
@@ -713,12 +797,13 @@ pub fn eval_fighter_sim(
         emitter: Arc::new(Mutex::new(EventHandlerEmitter {
             handler: Box::new(DefaultEventHandler {}),
         })),
+        bus: None,
     };
 
     for d in devices {
         let emitter = match d {
             Address::BusControl => Arc::new(Mutex::new(EventHandlerEmitter {
-                handler: Box::new(FighterBCScheduler::new()),
+                handler: Box::new(scheduler.clone()),
             })),
             Address::BusMonitor => Arc::new(Mutex::new(EventHandlerEmitter {
                 handler: Box::new(DefaultEventHandler {}),
@@ -754,8 +839,13 @@ pub fn eval_fighter_sim(
             Address::BusMonitor => Mode::BM,
             _ => Mode::RT,
         };
-        sys.run_d(d as u8, mode, emitter, d == Address::AttackController);
+        let spec = DeviceSpec::with_emitter(d as u8, mode, emitter);
+        builder = builder.device(match d {
+            Address::AttackController => spec.fake(),
+            _ => spec,
+        });
     }
+    let mut sys = builder.build().map_err(|e| e.to_string())?;
 
     if run_time < 1 {
         run_time = max_device_replay_time.into();
@@ -778,12 +868,17 @@ pub fn eval_fighter_sim(
     sys.sleep_ms_progress(keep_time);
     sys.stop();
     sys.join();
+    Ok(())
 }
 
-#[cfg(tests)]
+#[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sys_bus::builder::DeviceSpec;
+    use crate::sys_bus::inject::Outcome;
+    use crate::sys_bus::Message;
 
+    #[test]
     fn test_address_functions() {
         for (src, dst) in [
             (Address::FlightControls, Address::Trim),
@@ -810,17 +905,155 @@ mod tests {
             (Address::Positioning, Address::FlightControls),
             (Address::Pitch, Address::FlightControls),
         ] {
-            println!("src: {:?}, dst: {:?}", src, dst);
-            println!("priority: {:?}", src.priority(&dst));
-            println!("repeating: {:?}", src.repeat_function(&dst));
-            if src.repeat_function(&dst) {
-                println!(
-                    "Repeat freq: {:?}Hz",
-                    1.0 / (src.priority(&dst).delay() as f32 / 1_000_000_000.0)
-                );
-            }
-            println!("word_count: {:?}", src.word_count(&dst));
-            println!("------------------")
+            // every listed pairing repeats at 50Hz down to 1.5625Hz
+            assert!(src.repeat_function(&dst), "{:?} -> {:?}", src, dst);
+            let delay = src.priority(&dst).delay();
+            assert!(
+                (20_000_000..=640_000_000).contains(&delay),
+                "{:?} -> {:?}",
+                src,
+                dst
+            );
+            let words = src.word_count(&dst);
+            assert!((1..=32).contains(&words), "{:?} -> {:?}", src, dst);
         }
+        assert_eq!(
+            Address::FlightControls.priority(&Address::Engine),
+            MsgPri::VeryHigh
+        );
+        assert_eq!(Address::FlightControls.word_count(&Address::Engine), 8);
+        assert_eq!(Address::Weapons.word_count(&Address::FlightControls), 20);
+        // anything unlisted is sent once, in 2 words
+        assert!(!Address::Engine.repeat_function(&Address::Rudder));
+        assert_eq!(Address::Engine.word_count(&Address::Rudder), 2);
+    }
+
+    fn queued(scheduler: &FighterBCScheduler) -> Vec<(Address, Address)> {
+        let mut events: Vec<(Address, Address)> = scheduler
+            .priority_list
+            .iter()
+            .map(|(e, _)| (e.source, e.destination))
+            .collect();
+        events.sort_by_key(|(src, dst)| (*src as u8, *dst as u8));
+        events
+    }
+
+    #[test]
+    fn test_fighter_schedule() {
+        let schedule = FighterSchedule::default();
+        let json = serde_json::to_string(&schedule).unwrap();
+        assert_eq!(FighterSchedule::from_json(&json).unwrap(), schedule);
+        let scheduler = FighterBCScheduler::new();
+        assert_eq!(queued(&scheduler).len(), 8);
+        assert!(queued(&scheduler).contains(&(Address::FlightControls, Address::Brakes)));
+
+        let schedule = FighterSchedule::from_json(
+            r#"{"active": ["LandingGear"], "messages": [
+                {"source": "Fuel", "destination": "BusControl", "sub_address": 5,
+                 "word_count": 2, "period": 1000000, "repeating": true},
+                {"source": "FlightControls", "destination": "Brakes", "word_count": 4,
+                 "priority": "High", "repeating": true, "enabled_by": "LandingGear"},
+                {"source": "Radar", "destination": "FlightControls", "word_count": 8,
+                 "priority": "High", "repeating": true, "enabled_by": "Radar"},
+                {"source": "Rover", "destination": "BusControl", "word_count": 1,
+                 "repeating": false}
+            ]}"#,
+        )
+        .unwrap();
+        let scheduler = FighterBCScheduler::from_schedule(&schedule);
+        // not the radar message, its system being inactive
+        assert_eq!(
+            queued(&scheduler),
+            vec![
+                (Address::FlightControls, Address::Brakes),
+                (Address::Rover, Address::BusControl),
+                (Address::Fuel, Address::BusControl),
+            ]
+        );
+        assert_eq!(scheduler.radar_events.len(), 1);
+        assert_eq!(scheduler.landing_gear_events.len(), 1);
+        let (fuel, _) = scheduler
+            .priority_list
+            .iter()
+            .find(|(e, _)| e.source == Address::Fuel)
+            .unwrap();
+        assert_eq!((fuel.sub_address, fuel.period), (5, 1_000_000));
+        let (brakes, _) = scheduler
+            .priority_list
+            .iter()
+            .find(|(e, _)| e.destination == Address::Brakes)
+            .unwrap();
+        assert_eq!(
+            (brakes.sub_address, brakes.period),
+            (SA_DEFAULT, 40_000_000)
+        );
+
+        for bad in [
+            r#"{"source": "Fuel", "destination": "Fuel", "word_count": 1, "repeating": false}"#,
+            r#"{"source": "Fuel", "destination": "BusControl", "sub_address": 31, "word_count": 1, "repeating": false}"#,
+            r#"{"source": "Fuel", "destination": "BusControl", "word_count": 33, "repeating": false}"#,
+            r#"{"source": "Fuel", "destination": "BusControl", "word_count": 1, "repeating": true}"#,
+            r#"{"source": "Fuel", "destination": "BusControl", "word_count": 1, "repeating": true,
+                "priority": "Low", "period": 1000}"#,
+            r#"{"source": "Fuel", "destination": "Sonar", "word_count": 1, "repeating": false}"#,
+        ] {
+            let json = format!(r#"{{"messages": [{}]}}"#, bad);
+            assert!(FighterSchedule::from_json(&json).is_err(), "{}", bad);
+        }
+
+        let path = std::env::temp_dir().join("test_fighter_schedule.json");
+        fs::write(&path, &json).unwrap();
+        assert_eq!(queued(&FighterBCScheduler::load(&path).unwrap()).len(), 8);
+        let _ = fs::remove_file(&path);
+        assert!(FighterBCScheduler::load(Path::new("no_such_schedule.json")).is_err());
+    }
+
+    #[test]
+    fn test_fighter_schedule_run() {
+        let schedule = FighterSchedule::from_json(
+            r#"{"messages": [
+                {"source": "Fuel", "destination": "BusControl", "sub_address": 5,
+                 "word_count": 2, "period": 1000000, "repeating": true},
+                {"source": "FlightControls", "destination": "Rudder", "word_count": 4,
+                 "priority": "VeryHigh", "repeating": true}
+            ]}"#,
+        )
+        .unwrap();
+        let bc = DeviceSpec::new(
+            Address::BusControl as u8,
+            Mode::BC,
+            Box::new(FighterBCScheduler::from_schedule(&schedule)),
+        );
+        let config = LogConfig {
+            sys_logs: false,
+            ..LogConfig::default()
+        };
+        let rts = [Address::FlightControls, Address::Rudder, Address::Fuel];
+        let mut sys_bus = SystemBuilder::new(4_000)
+            .virtual_time(0)
+            .log_config(config)
+            .device(bc)
+            .rts(rts.map(|a| a as u8))
+            .build()
+            .unwrap();
+        sys_bus.go();
+        sys_bus.sleep_ms(5);
+        // sent on the default subaddress, whatever the schedule used last
+        let mut injected = sys_bus.inject(Message::RT2BC(Address::Fuel as u8, 1));
+        sys_bus.sleep_ms(5);
+        sys_bus.stop();
+        sys_bus.join();
+        assert!(matches!(
+            injected.try_outcome(),
+            Some(Outcome::Completed(_))
+        ));
+
+        let traffic = sys_bus.metrics().traffic;
+        let fuel = &traffic[&(Address::Fuel as u8)];
+        // every ms, on its own subaddress
+        assert!((9..=11).contains(&fuel[&5].messages), "{:?}", fuel);
+        assert_eq!(fuel[&SA_DEFAULT].messages, 1);
+        // every 20 ms
+        assert_eq!(traffic[&(Address::Rudder as u8)][&SA_DEFAULT].messages, 1);
     }
 }